@@ -0,0 +1,74 @@
+//! Compare the request latency with and without connection pooling against a local stub server.
+//!
+//! Run with `cargo bench --bench pooling --features http-ureq,http-reqwest`.
+
+#[path = "../tests/http/utils.rs"]
+#[allow(dead_code)]
+mod utils;
+
+use proton_api_rs::http::{
+    ClientBuilder, Method, OwnedRequest, RequestData, Sequence, StringResponse,
+};
+use std::time::{Duration, Instant};
+use utils::{ClientASync, ClientSync, StubResponse, StubServer};
+
+const REQUESTS: u32 = 500;
+
+fn ping() -> OwnedRequest<StringResponse> {
+    OwnedRequest::new(RequestData::new(Method::Get, "tests/ping"))
+}
+
+fn configs(server: &StubServer) -> [(&'static str, ClientBuilder); 2] {
+    [
+        ("pooled", server.client_builder()),
+        (
+            "unpooled",
+            server.client_builder().pool_max_idle_per_host(0),
+        ),
+    ]
+}
+
+fn report(backend: &str, config: &str, server: &StubServer, connections: usize, elapsed: Duration) {
+    println!(
+        "{backend:<8} {config:<9} {:>9.1?}/request {:>4} connections",
+        elapsed / REQUESTS,
+        server.connection_count() - connections,
+    );
+}
+
+fn bench_sync(server: &StubServer) {
+    for (config, builder) in configs(server) {
+        let client = builder.build::<ClientSync>().unwrap();
+        ping().do_sync(&client).unwrap();
+
+        let connections = server.connection_count();
+        let start = Instant::now();
+        for _ in 0..REQUESTS {
+            ping().do_sync(&client).unwrap();
+        }
+        report("ureq", config, server, connections, start.elapsed());
+    }
+}
+
+async fn bench_async(server: &StubServer) {
+    for (config, builder) in configs(server) {
+        let client = builder.build::<ClientASync>().unwrap();
+        ping().do_async(&client).await.unwrap();
+
+        let connections = server.connection_count();
+        let start = Instant::now();
+        for _ in 0..REQUESTS {
+            ping().do_async(&client).await.unwrap();
+        }
+        report("reqwest", config, server, connections, start.elapsed());
+    }
+}
+
+fn main() {
+    let server = StubServer::new(|_| StubResponse::new(200).body("pong"));
+
+    bench_sync(&server);
+    tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(bench_async(&server));
+}
@@ -1,7 +1,9 @@
 mod client;
+mod pagination;
 mod session;
 mod totp;
 
 pub use client::*;
+pub use pagination::*;
 pub use session::*;
 pub use totp::*;
@@ -0,0 +1,276 @@
+use crate::clientv2::Session;
+use crate::domain::{MessageFilter, MessageId, MessageMetadata};
+use crate::http;
+use crate::http::{ClientAsync, ClientSync, Sequence};
+use futures_util::Stream;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 150;
+
+type PageFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<(Vec<MessageMetadata>, u32), http::Error>> + Send + 'a>>;
+
+/// Lists every message matching a filter, see [`Session::messages`].
+///
+/// Pages are requested with the `EndID` of the last message of the previous page rather than
+/// with page numbers, so that messages which arrive while iterating do not shift the pages and
+/// cause messages to be listed twice or skipped.
+pub struct MessagePager<'a> {
+    session: &'a Session,
+    filter: MessageFilter,
+    page_size: u32,
+    prefetch: bool,
+}
+
+impl<'a> MessagePager<'a> {
+    pub(crate) fn new(session: &'a Session, filter: MessageFilter) -> Self {
+        Self {
+            session,
+            page_size: filter
+                .page_size
+                .unwrap_or(DEFAULT_PAGE_SIZE)
+                .clamp(1, MAX_PAGE_SIZE),
+            filter,
+            prefetch: false,
+        }
+    }
+
+    /// Number of messages requested per page, at most 150.
+    pub fn page_size(mut self, size: u32) -> Self {
+        self.page_size = size.clamp(1, MAX_PAGE_SIZE);
+        self
+    }
+
+    /// Request the next page while the messages of the current page are being consumed. Only
+    /// applies to [`MessagePager::stream`], since the blocking iterator has no way to make
+    /// progress in the background.
+    pub fn prefetch(mut self, prefetch: bool) -> Self {
+        self.prefetch = prefetch;
+        self
+    }
+
+    /// Iterate over the messages with a blocking client. Iteration stops after the first error.
+    pub fn iter<T: ClientSync>(self, client: &'a T) -> MessageIter<'a, T> {
+        MessageIter {
+            session: self.session,
+            client,
+            state: PageState::new(self.filter, self.page_size),
+            buffer: VecDeque::new(),
+        }
+    }
+
+    /// Stream the messages with an async client. The stream ends after the first error.
+    pub fn stream<T: ClientAsync>(self, client: &'a T) -> MessageStream<'a> {
+        let session = self.session;
+        MessageStream {
+            fetch: Box::new(move |filter| Box::pin(session.get_messages(filter).do_async(client))),
+            state: PageState::new(self.filter, self.page_size),
+            prefetch: self.prefetch,
+            buffer: VecDeque::new(),
+            pending: None,
+            error: None,
+        }
+    }
+}
+
+/// Position of the pagination, shared by the iterator and the stream.
+struct PageState {
+    filter: MessageFilter,
+    page_size: u32,
+    end_id: Option<MessageId>,
+    done: bool,
+}
+
+impl PageState {
+    fn new(mut filter: MessageFilter, page_size: u32) -> Self {
+        filter.page = None;
+        filter.page_size = Some(page_size);
+        Self {
+            end_id: filter.end_id.take().map(MessageId),
+            filter,
+            page_size,
+            done: false,
+        }
+    }
+
+    /// Filter requesting the page which follows the messages seen so far.
+    fn next_filter(&self) -> Option<MessageFilter> {
+        if self.done {
+            return None;
+        }
+
+        let mut filter = self.filter.clone();
+        filter.end_id = self.end_id.as_ref().map(|id| id.0.clone());
+        Some(filter)
+    }
+
+    /// Record a page of messages and get the messages which were not listed yet. The message
+    /// used as `EndID` is dropped if the server includes it again.
+    fn accept(&mut self, mut messages: Vec<MessageMetadata>) -> Vec<MessageMetadata> {
+        if messages.len() < self.page_size as usize {
+            self.done = true;
+        }
+        if self.end_id.is_some() && messages.first().map(|m| &m.id) == self.end_id.as_ref() {
+            messages.remove(0);
+        }
+
+        match messages.last() {
+            Some(last) => self.end_id = Some(last.id.clone()),
+            None => self.done = true,
+        }
+        messages
+    }
+}
+
+/// Blocking iterator over the messages of a [`MessagePager`].
+pub struct MessageIter<'a, T> {
+    session: &'a Session,
+    client: &'a T,
+    state: PageState,
+    buffer: VecDeque<MessageMetadata>,
+}
+
+impl<'a, T: ClientSync> Iterator for MessageIter<'a, T> {
+    type Item = Result<MessageMetadata, http::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(message) = self.buffer.pop_front() {
+                return Some(Ok(message));
+            }
+
+            let filter = self.state.next_filter()?;
+            match self.session.get_messages(filter).do_sync(self.client) {
+                Ok((messages, _)) => self.buffer.extend(self.state.accept(messages)),
+                Err(e) => {
+                    self.state.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+/// Async stream over the messages of a [`MessagePager`].
+pub struct MessageStream<'a> {
+    fetch: Box<dyn FnMut(MessageFilter) -> PageFuture<'a> + Send + 'a>,
+    state: PageState,
+    prefetch: bool,
+    buffer: VecDeque<MessageMetadata>,
+    pending: Option<PageFuture<'a>>,
+    error: Option<http::Error>,
+}
+
+impl MessageStream<'_> {
+    /// Whether the next page should be requested. With prefetching, the stream stays at most
+    /// one page ahead of the consumer.
+    fn should_fetch(&self) -> bool {
+        self.pending.is_none()
+            && self.error.is_none()
+            && (self.buffer.is_empty()
+                || (self.prefetch && self.buffer.len() <= self.state.page_size as usize))
+    }
+}
+
+impl Stream for MessageStream<'_> {
+    type Item = Result<MessageMetadata, http::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            if this.should_fetch() {
+                if let Some(filter) = this.state.next_filter() {
+                    this.pending = Some((this.fetch)(filter));
+                }
+            }
+
+            if let Some(pending) = this.pending.as_mut() {
+                if let Poll::Ready(result) = pending.as_mut().poll(cx) {
+                    this.pending = None;
+                    match result {
+                        Ok((messages, _)) => this.buffer.extend(this.state.accept(messages)),
+                        Err(e) => {
+                            this.state.done = true;
+                            this.error = Some(e);
+                        }
+                    }
+                    // Start prefetching the next page before handing out this one.
+                    continue;
+                }
+            }
+
+            if let Some(message) = this.buffer.pop_front() {
+                return Poll::Ready(Some(Ok(message)));
+            }
+            if let Some(e) = this.error.take() {
+                return Poll::Ready(Some(Err(e)));
+            }
+            if this.pending.is_some() {
+                return Poll::Pending;
+            }
+            if this.state.done {
+                return Poll::Ready(None);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(ids: &[&str]) -> Vec<MessageMetadata> {
+        ids.iter()
+            .map(|id| {
+                serde_json::from_value(serde_json::json!({
+                    "ID": id,
+                    "ConversationID": "conversation",
+                    "AddressID": "address",
+                    "Subject": "",
+                    "Sender": {"Name": "", "Address": "foo@bar.com"},
+                    "Time": 0,
+                    "Size": 0,
+                }))
+                .unwrap()
+            })
+            .collect()
+    }
+
+    fn ids(messages: &[MessageMetadata]) -> Vec<&str> {
+        messages.iter().map(|m| m.id.0.as_str()).collect()
+    }
+
+    #[test]
+    fn test_page_state() {
+        let filter = MessageFilter::new().with_label("0").with_page(3);
+        let mut state = PageState::new(filter, 2);
+
+        let first = state.next_filter().unwrap();
+        assert_eq!(first.page, None);
+        assert_eq!(first.page_size, Some(2));
+        assert_eq!(first.end_id, None);
+        assert_eq!(first.label_id.as_deref(), Some("0"));
+
+        assert_eq!(ids(&state.accept(page(&["a", "b"]))), ["a", "b"]);
+        assert_eq!(state.next_filter().unwrap().end_id.as_deref(), Some("b"));
+
+        // The server may include the message used as EndID again.
+        assert_eq!(ids(&state.accept(page(&["b", "c"]))), ["c"]);
+        assert_eq!(state.next_filter().unwrap().end_id.as_deref(), Some("c"));
+
+        assert_eq!(ids(&state.accept(page(&["d"]))), ["d"]);
+        assert!(state.next_filter().is_none());
+    }
+
+    #[test]
+    fn test_page_state_stops_on_empty_page() {
+        let mut state = PageState::new(MessageFilter::new(), 2);
+        assert_eq!(ids(&state.accept(page(&["a", "b"]))), ["a", "b"]);
+        assert!(state.accept(page(&["b"])).is_empty());
+        assert!(state.next_filter().is_none());
+    }
+}
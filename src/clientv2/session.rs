@@ -4,7 +4,9 @@ use crate::domain::{
     LabelType, PublicKeys, RecipientType, SecretString, TwoFactorAuth, User, UserUid,
 };
 use crate::http;
-use crate::http::{OwnedRequest, RequestDesc, Sequence, SequenceFromState, X_PM_UID_HEADER};
+use crate::http::{
+    OwnedRequest, RequestDesc, Sequence, SequenceFromState, ServerClock, X_PM_UID_HEADER,
+};
 use crate::requests::{
     AddressKeys, AuthInfoRequest, AuthInfoResponse, AuthRefreshRequest, AuthRequest, AuthResponse,
     GetAddressKeysRequest, GetAllKeysRequest, GetEventRequest, GetLabelsRequest,
@@ -14,6 +16,7 @@ use crate::requests::{
 use proton_srp::{SRPAuth, SRPProofB64};
 use secrecy::{ExposeSecret, Secret};
 use std::sync::Arc;
+use std::time::SystemTime;
 
 #[derive(Debug, thiserror::Error)]
 pub enum LoginError {
@@ -59,12 +62,14 @@ pub enum SessionType {
 #[derive(Debug, Clone)]
 pub struct Session {
     pub(super) user_auth: Arc<parking_lot::RwLock<UserAuth>>,
+    pub(super) clock: ServerClock,
 }
 
 impl Session {
-    fn new(user: UserAuth) -> Self {
+    fn new(user: UserAuth, clock: ServerClock) -> Self {
         Self {
             user_auth: Arc::new(parking_lot::RwLock::new(user)),
+            clock,
         }
     }
 
@@ -73,13 +78,17 @@ impl Session {
         password: &'a SecretString,
         human_verification: Option<HumanVerificationLoginData>,
     ) -> impl Sequence<Output = SessionType, Error = LoginError> + 'a {
+        let clock = ServerClock::new();
         let state = State {
             username,
             password,
             hv: human_verification,
+            clock: clock.clone(),
         };
 
         SequenceFromState::new(state, login_sequence_1)
+            .traced("login")
+            .sync_clock(clock)
     }
 
     pub fn submit_totp<'a>(
@@ -94,15 +103,31 @@ impl Session {
         user_uid: &'a UserUid,
         token: &'a str,
     ) -> impl Sequence<Output = Self, Error = http::Error> + 'a {
+        let clock = ServerClock::new();
+        let session_clock = clock.clone();
         AuthRefreshRequest::new(user_uid, token)
             .to_request()
-            .map(|r| {
+            .traced("session_refresh")
+            .map(move |r| {
                 let user = UserAuth::from_auth_refresh_response(r);
-                Ok(Session::new(user))
+                Ok(Session::new(user, session_clock))
             })
+            .sync_clock(clock)
+    }
+
+    /// Current time according to the server, as observed in the responses to the requests of
+    /// this session. Use it for anything the server checks against its own clock, such as TOTP
+    /// codes, scheduled sends or message expiration times.
+    pub fn server_now(&self) -> SystemTime {
+        self.clock.now()
     }
 
-    pub fn get_user(&self) -> impl Sequence<Output = User> + '_ {
+    /// Clock tracking the offset between the local and the server time.
+    pub fn server_clock(&self) -> &ServerClock {
+        &self.clock
+    }
+
+    pub fn get_user(&self) -> impl Sequence<Output = User, Error = http::Error> + '_ {
         //self.wrap_request(UserInfoRequest {}.to_request())
         //    .map(|r| -> Result<User, http::Error> { Ok(r.user) })
         self.wrap_request2(UserInfoRequest {})
@@ -160,6 +185,14 @@ impl Session {
             .map(|r| Ok((r.messages, r.total)))
     }
 
+    /// List every message matching `filter`, page by page, as an iterator or a stream.
+    pub fn messages(
+        &self,
+        filter: crate::domain::MessageFilter,
+    ) -> crate::clientv2::MessagePager<'_> {
+        crate::clientv2::MessagePager::new(self, filter)
+    }
+
     /// Get messages in a specific label/folder.
     pub fn get_messages_in_label(
         &self,
@@ -237,6 +270,33 @@ impl Session {
         self.wrap_request2(crate::requests::GetAttachmentRequest::new(attachment_id))
     }
 
+    /// Download an attachment by ID without loading it into memory.
+    /// The encrypted attachment data can be read from the returned stream.
+    pub fn get_attachment_stream<'a, 'b: 'a>(
+        &'b self,
+        attachment_id: &'a str,
+    ) -> impl Sequence<Output = http::ResponseStream, Error = http::Error> + 'a {
+        self.wrap_request2(crate::requests::GetAttachmentStreamRequest::new(
+            attachment_id,
+        ))
+    }
+
+    /// Download an attachment by ID directly into `writer`.
+    /// `progress` is called with the number of bytes written so far. Returns the attachment size.
+    pub fn download_attachment<'a, 'b: 'a, W, P>(
+        &'b self,
+        attachment_id: &'a str,
+        writer: W,
+        progress: P,
+    ) -> impl Sequence<Output = u64, Error = http::Error> + 'a
+    where
+        W: std::io::Write + Send + 'a,
+        P: FnMut(u64) + Send + 'a,
+    {
+        self.get_attachment_stream(attachment_id)
+            .write_to(writer, progress)
+    }
+
     // ========================================================================
     // Key API methods
     // ========================================================================
@@ -336,17 +396,19 @@ impl Session {
     }
 
     #[inline(always)]
-    fn wrap_request2<'a, 'b: 'a, R: RequestDesc + 'a>(
+    fn wrap_request2<'a, 'b: 'a, R: RequestDesc + Send + 'a>(
         &'b self,
         r: R,
     ) -> impl Sequence<Output = R::Output, Error = http::Error> + 'a {
         SequenceFromState::new(self, move |s| wrap_session_request(s, r))
+            .sync_clock(self.clock.clone())
     }
 }
 
 fn validate_server_proof(
     proof: &SRPProofB64,
     auth_response: AuthResponse,
+    clock: ServerClock,
 ) -> Result<SessionType, LoginError> {
     if !proof.compare_server_proof(&auth_response.server_proof) {
         return Err(LoginError::ServerProof(
@@ -357,7 +419,7 @@ fn validate_server_proof(
     let tfa_enabled = auth_response.tfa.enabled;
     let user = UserAuth::from_auth_response(auth_response);
 
-    let session = Session::new(user);
+    let session = Session::new(user, clock);
 
     match tfa_enabled {
         TFAStatus::None => Ok(SessionType::Authenticated(session)),
@@ -381,6 +443,7 @@ struct State<'a> {
     username: &'a str,
     password: &'a SecretString,
     hv: Option<HumanVerificationLoginData>,
+    clock: ServerClock,
 }
 
 struct LoginState<'a> {
@@ -388,6 +451,7 @@ struct LoginState<'a> {
     proof: SRPProofB64,
     session: String,
     hv: Option<HumanVerificationLoginData>,
+    clock: ServerClock,
 }
 
 fn generate_login_state(
@@ -414,6 +478,7 @@ fn generate_login_state(
         proof,
         session: auth_info_response.srp_session,
         hv: state.hv,
+        clock: state.clock,
     })
 }
 
@@ -428,8 +493,10 @@ fn login_sequence_2(
         human_verification: &login_state.hv,
     }
     .to_request()
+    .traced("auth")
     .map(move |auth_response| {
-        validate_server_proof(&login_state.proof, auth_response).map_err(map_human_verification_err)
+        validate_server_proof(&login_state.proof, auth_response, login_state.clock)
+            .map_err(map_human_verification_err)
     })
 }
 
@@ -438,11 +505,12 @@ fn login_sequence_1(st: State) -> impl Sequence<Output = SessionType, Error = Lo
         username: st.username,
     }
     .to_request()
+    .traced("auth_info")
     .map(move |auth_info_response| generate_login_state(st, auth_info_response))
     .state(login_sequence_2)
 }
 
-fn wrap_session_request<'a, R: RequestDesc + 'a>(
+fn wrap_session_request<'a, R: RequestDesc + Send + 'a>(
     session: &'a Session,
     r: R,
 ) -> impl Sequence<Output = R::Output, Error = http::Error> + 'a {
@@ -465,6 +533,7 @@ fn wrap_session_request<'a, R: RequestDesc + 'a>(
                         borrow.refresh_token.expose_secret(),
                     )
                     .to_request()
+                    .traced("session_refresh")
                 }
                 .chain(move |resp| {
                     let data = {
@@ -473,7 +542,7 @@ fn wrap_session_request<'a, R: RequestDesc + 'a>(
                         data.header(X_PM_UID_HEADER, writer.uid.expose_secret().as_str())
                             .bearer_token(writer.access_token.expose_secret())
                     };
-                    Ok(OwnedRequest::<R::Response>::new(data))
+                    Ok(OwnedRequest::<R::Response>::new(data).traced("retry_after_refresh"))
                 }));
             }
         }
@@ -481,3 +550,183 @@ fn wrap_session_request<'a, R: RequestDesc + 'a>(
         Err(e)
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::mock_client::{MockClient, MockMatcher, MockResponse};
+    use crate::http::Method;
+
+    fn refresh_response(access_token: &str, refresh_token: &str) -> MockResponse {
+        MockResponse::json(
+            200,
+            serde_json::json!({
+                "UID": "uid",
+                "AccessToken": access_token,
+                "RefreshToken": refresh_token,
+                "Scope": "full",
+            }),
+        )
+    }
+
+    fn user_response() -> MockResponse {
+        MockResponse::json(
+            200,
+            serde_json::json!({
+                "User": {
+                    "ID": "user-id",
+                    "Name": "foo",
+                    "DisplayName": "Foo",
+                    "Email": "foo@bar.com",
+                    "UsedSpace": 0,
+                    "MaxSpace": 0,
+                    "MaxUpload": 0,
+                    "Credit": 0,
+                    "Currency": "EUR",
+                    "Keys": [],
+                }
+            }),
+        )
+    }
+
+    fn setup_expired_session_mocks(client: &MockClient) {
+        client.mock_once(
+            MockMatcher::new(Method::Post, "auth/v4/refresh"),
+            refresh_response("access-1", "refresh-1"),
+        );
+        client.mock(
+            MockMatcher::new(Method::Get, "core/v4/users")
+                .header("authorization", "Bearer access-1"),
+            MockResponse::json(401, serde_json::json!({"Code": 401})),
+        );
+        client.mock(
+            MockMatcher::new(Method::Post, "auth/v4/refresh")
+                .json_body(|v| v["RefreshToken"] == "refresh-1"),
+            refresh_response("access-2", "refresh-2"),
+        );
+        client.mock(
+            MockMatcher::new(Method::Get, "core/v4/users")
+                .header("authorization", "Bearer access-2"),
+            user_response(),
+        );
+    }
+
+    #[test]
+    fn test_session_refresh_on_unauthorized() {
+        let client = MockClient::new();
+        setup_expired_session_mocks(&client);
+
+        let uid = UserUid::from("uid");
+        let session = Session::refresh(&uid, "refresh-0")
+            .do_sync(&client)
+            .unwrap();
+        let user = session.get_user().do_sync(&client).unwrap();
+
+        assert_eq!(user.id.as_ref(), "user-id");
+        assert_eq!(
+            session.get_refresh_data().token.expose_secret(),
+            "refresh-2"
+        );
+        assert_eq!(client.requests().len(), 4);
+    }
+
+    #[test]
+    fn test_deadline_covers_refresh_retry() {
+        let client = MockClient::new();
+        setup_expired_session_mocks(&client);
+
+        let uid = UserUid::from("uid");
+        let session = Session::refresh(&uid, "refresh-0")
+            .do_sync(&client)
+            .unwrap();
+        let token = http::CancellationToken::new();
+        session
+            .get_user()
+            .with_deadline(std::time::Duration::from_secs(30))
+            .with_cancellation(token.clone())
+            .do_sync(&client)
+            .unwrap();
+
+        // The user request, the refresh and the retried user request share the budget.
+        let requests = client.requests();
+        assert_eq!(requests.len(), 4);
+        for request in &requests[1..] {
+            assert!(request.get_timeout().unwrap() <= std::time::Duration::from_secs(30));
+            assert!(request.get_cancellation_token().is_some());
+        }
+
+        token.cancel();
+        let result = session.logout().with_cancellation(token).do_sync(&client);
+        assert!(matches!(result, Err(http::Error::Cancelled)));
+        assert_eq!(client.requests().len(), 4);
+    }
+
+    #[test]
+    fn test_session_server_now() {
+        let client = MockClient::new();
+        let server_time = SystemTime::now() - std::time::Duration::from_secs(600);
+        client.mock(
+            MockMatcher::new(Method::Post, "auth/v4/refresh"),
+            refresh_response("access-1", "refresh-1")
+                .header("Date", httpdate::fmt_http_date(server_time)),
+        );
+
+        let uid = UserUid::from("uid");
+        let session = Session::refresh(&uid, "refresh-0")
+            .do_sync(&client)
+            .unwrap();
+
+        let offset = session.server_clock().offset_millis().unwrap();
+        assert!((-601_000..=-599_000).contains(&offset));
+        assert!(session.server_now() < SystemTime::now());
+    }
+
+    #[test]
+    fn test_download_attachment() {
+        let client = MockClient::new();
+        client.mock(
+            MockMatcher::new(Method::Post, "auth/v4/refresh"),
+            refresh_response("access-1", "refresh-1"),
+        );
+        client.mock(
+            MockMatcher::new(Method::Get, "mail/v4/attachments/att-id"),
+            MockResponse::new(200).body(vec![7u8; 1000]),
+        );
+
+        let uid = UserUid::from("uid");
+        let session = Session::refresh(&uid, "refresh-0")
+            .do_sync(&client)
+            .unwrap();
+
+        let mut output = vec![];
+        let mut progress = 0;
+        let written = session
+            .download_attachment("att-id", &mut output, |n| progress = n)
+            .do_sync(&client)
+            .unwrap();
+
+        assert_eq!(written, 1000);
+        assert_eq!(progress, 1000);
+        assert_eq!(output, vec![7u8; 1000]);
+    }
+
+    #[tokio::test]
+    async fn test_session_refresh_on_unauthorized_async() {
+        let client = MockClient::new();
+        setup_expired_session_mocks(&client);
+
+        let uid = UserUid::from("uid");
+        let session = Session::refresh(&uid, "refresh-0")
+            .do_async(&client)
+            .await
+            .unwrap();
+        let user = session.get_user().do_async(&client).await.unwrap();
+
+        assert_eq!(user.id.as_ref(), "user-id");
+        assert_eq!(
+            session.get_refresh_data().token.expose_secret(),
+            "refresh-2"
+        );
+        assert_eq!(client.requests().len(), 4);
+    }
+}
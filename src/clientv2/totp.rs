@@ -1,6 +1,7 @@
 use crate::clientv2::Session;
 use crate::http;
 use crate::http::Sequence;
+use std::time::SystemTime;
 
 #[derive(Debug)]
 pub struct TotpSession(pub(super) Session);
@@ -11,9 +12,18 @@ impl TotpSession {
         code: &'a str,
     ) -> impl Sequence<Output = Session, Error = http::Error> + 'a {
         let auth = self.0.user_auth.clone();
-        self.0
-            .submit_totp(code)
-            .map(move |_| Ok(Session { user_auth: auth }))
+        let clock = self.0.clock.clone();
+        self.0.submit_totp(code).map(move |_| {
+            Ok(Session {
+                user_auth: auth,
+                clock,
+            })
+        })
+    }
+
+    /// Current time according to the server, to generate TOTP codes from.
+    pub fn server_now(&self) -> SystemTime {
+        self.0.server_now()
     }
 
     pub fn logout(&self) -> impl Sequence<Output = ()> + '_ {
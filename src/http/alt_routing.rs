@@ -0,0 +1,330 @@
+use crate::http::{Error, RequestData, Result};
+use log::debug;
+use parking_lot::Mutex;
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const DEFAULT_ROUTING_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Discovers alternative hosts which serve the API when the primary host is unreachable.
+///
+/// Resolvers are called from both the sync and the async http clients and may block. The async
+/// client calls them on the blocking thread pool of the runtime.
+pub trait AltRoutingResolver: Send + Sync {
+    /// Get alternative base urls which serve the same API as `base_url`. The candidates are
+    /// verified in the returned order.
+    fn resolve(&self, base_url: &str) -> Result<Vec<String>>;
+}
+
+/// Alternative routing configuration.
+///
+/// When a request fails with [`Error::Connection`] or [`Error::Timeout`], the http client asks
+/// the resolver for alternative hosts and verifies them by pinging `tests/ping`. The first host
+/// which answers replaces the client's base url for [`AltRouting::period`], after which the
+/// client switches back to the primary host. The failed request is sent again to the new host
+/// if it never reached the server or may be retried (see
+/// [`RetryPolicy`](crate::http::RetryPolicy)).
+///
+/// Clones share the currently active host.
+#[derive(Clone)]
+pub struct AltRouting {
+    resolver: Arc<dyn AltRoutingResolver>,
+    period: Duration,
+    active: Arc<Mutex<Option<(String, Instant)>>>,
+}
+
+impl AltRouting {
+    pub fn new(resolver: impl AltRoutingResolver + 'static) -> Self {
+        Self {
+            resolver: Arc::new(resolver),
+            period: DEFAULT_ROUTING_PERIOD,
+            active: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// How long an alternative host is used before switching back to the primary host. The
+    /// default is 24 hours.
+    pub fn period(mut self, period: Duration) -> Self {
+        self.period = period;
+        self
+    }
+
+    /// Base url of the alternative host which is currently in use, if any.
+    pub fn active_base_url(&self) -> Option<String> {
+        self.active
+            .lock()
+            .as_ref()
+            .filter(|(_, until)| Instant::now() < *until)
+            .map(|(url, _)| url.clone())
+    }
+
+    /// Get the base url requests should be sent to.
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn base_url(&self, primary: &str) -> String {
+        let mut active = self.active.lock();
+        match active.as_ref() {
+            Some((url, until)) if Instant::now() < *until => url.clone(),
+            Some((url, _)) => {
+                debug!("Alternative routing period for {url} expired, switching back to {primary}");
+                *active = None;
+                primary.to_string()
+            }
+            None => primary.to_string(),
+        }
+    }
+
+    /// Get the alternative hosts to verify after a request to `base_url` failed with `error`.
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn failover_candidates(
+        &self,
+        primary: &str,
+        base_url: &str,
+        error: &Error,
+    ) -> Vec<String> {
+        if !self.should_failover(primary, base_url, error) {
+            return vec![];
+        }
+
+        resolve(self.resolver.as_ref(), primary)
+    }
+
+    /// Async version of [`AltRouting::failover_candidates`], which runs the resolver on the
+    /// blocking thread pool so that it does not stall the runtime.
+    #[cfg(feature = "http-reqwest")]
+    pub(crate) async fn failover_candidates_async(
+        &self,
+        primary: &str,
+        base_url: &str,
+        error: &Error,
+    ) -> Vec<String> {
+        if !self.should_failover(primary, base_url, error) {
+            return vec![];
+        }
+
+        let resolver = Arc::clone(&self.resolver);
+        let primary = primary.to_string();
+        tokio::task::spawn_blocking(move || resolve(resolver.as_ref(), &primary))
+            .await
+            .unwrap_or_else(|e| {
+                debug!("Alternative host resolution failed: {e}");
+                vec![]
+            })
+    }
+
+    /// Whether alternative hosts should be resolved after a request to `base_url` failed with
+    /// `error`. Switches back to the primary host if an alternative host became unreachable.
+    fn should_failover(&self, primary: &str, base_url: &str, error: &Error) -> bool {
+        if !matches!(error, Error::Connection(_) | Error::Timeout(_)) {
+            return false;
+        }
+
+        if base_url != primary {
+            debug!("Alternative host {base_url} is unreachable, switching back to {primary}");
+            *self.active.lock() = None;
+            return false;
+        }
+
+        true
+    }
+
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn activate(&self, base_url: String) {
+        debug!("Switching to alternative host {base_url}");
+        *self.active.lock() = Some((base_url, Instant::now() + self.period));
+    }
+
+    /// Whether `request` can be sent to the alternative host after failing with `error`.
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn can_resend(request: &RequestData, error: &Error) -> bool {
+        matches!(error, Error::Connection(_))
+            || request.method.is_idempotent()
+            || request.allow_retry
+    }
+}
+
+fn resolve(resolver: &dyn AltRoutingResolver, primary: &str) -> Vec<String> {
+    match resolver.resolve(primary) {
+        Ok(candidates) => candidates,
+        Err(e) => {
+            debug!("Failed to resolve alternative hosts for {primary}: {e}");
+            vec![]
+        }
+    }
+}
+
+impl Debug for AltRouting {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AltRouting")
+            .field("period", &self.period)
+            .field("active", &self.active_base_url())
+            .finish()
+    }
+}
+
+#[cfg(any(feature = "http-ureq", feature = "http-reqwest"))]
+pub use doh::*;
+
+#[cfg(any(feature = "http-ureq", feature = "http-reqwest"))]
+mod doh {
+    use super::AltRoutingResolver;
+    use crate::http::Result;
+    use log::debug;
+    use std::time::Duration;
+
+    const DEFAULT_DOH_PROVIDERS: [&str; 2] = [
+        "https://dns.google/resolve",
+        "https://cloudflare-dns.com/dns-query",
+    ];
+    const DEFAULT_DOH_DOMAIN: &str = "protonpro.xyz";
+    const DNS_TXT_RECORD: u16 = 16;
+
+    /// Resolves alternative hosts from the DNS TXT records of `d<base32(host)>.protonpro.xyz`
+    /// using the JSON API of DNS-over-HTTPS providers.
+    #[derive(Debug, Clone)]
+    pub struct DohResolver {
+        providers: Vec<String>,
+        domain: String,
+        timeout: Duration,
+    }
+
+    impl Default for DohResolver {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl DohResolver {
+        pub fn new() -> Self {
+            Self {
+                providers: DEFAULT_DOH_PROVIDERS
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect(),
+                domain: DEFAULT_DOH_DOMAIN.to_string(),
+                timeout: Duration::from_secs(10),
+            }
+        }
+
+        /// Replace the DNS-over-HTTPS providers, which are queried in order.
+        pub fn providers(mut self, providers: &[&str]) -> Self {
+            self.providers = providers.iter().map(|p| p.to_string()).collect();
+            self
+        }
+
+        pub fn domain(mut self, domain: &str) -> Self {
+            self.domain = domain.to_string();
+            self
+        }
+
+        pub fn timeout(mut self, timeout: Duration) -> Self {
+            self.timeout = timeout;
+            self
+        }
+
+        fn query(&self, provider: &str, name: &str) -> Result<Vec<String>> {
+            let response = self.get(provider, name)?;
+            let response = serde_json::from_str::<serde_json::Value>(&response)?;
+            Ok(response["Answer"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter(|a| a["type"] == DNS_TXT_RECORD)
+                .filter_map(|a| a["data"].as_str())
+                .map(|data| data.trim_matches('"').to_string())
+                .collect())
+        }
+
+        #[cfg(feature = "http-ureq")]
+        fn get(&self, provider: &str, name: &str) -> Result<String> {
+            ureq::AgentBuilder::new()
+                .timeout(self.timeout)
+                .build()
+                .get(provider)
+                .query("name", name)
+                .query("type", "TXT")
+                .set("Accept", "application/dns-json")
+                .call()?
+                .into_string()
+                .map_err(|e| crate::http::Error::Request(e.into()))
+        }
+
+        #[cfg(not(feature = "http-ureq"))]
+        fn get(&self, provider: &str, name: &str) -> Result<String> {
+            Ok(reqwest::blocking::Client::builder()
+                .timeout(self.timeout)
+                .build()?
+                .get(provider)
+                .query(&[("name", name), ("type", "TXT")])
+                .header("Accept", "application/dns-json")
+                .send()?
+                .error_for_status()?
+                .text()?)
+        }
+    }
+
+    impl AltRoutingResolver for DohResolver {
+        fn resolve(&self, base_url: &str) -> Result<Vec<String>> {
+            let without_scheme = base_url.split_once("://").map_or(base_url, |(_, r)| r);
+            let (host, path) = without_scheme
+                .find('/')
+                .map_or((without_scheme, ""), |i| without_scheme.split_at(i));
+            let name = format!("d{}.{}", base32(host.as_bytes()), self.domain);
+
+            let mut last_error = None;
+            for provider in &self.providers {
+                match self.query(provider, &name) {
+                    Ok(hosts) if !hosts.is_empty() => {
+                        return Ok(hosts
+                            .into_iter()
+                            .map(|h| format!("https://{h}{path}"))
+                            .collect())
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        debug!("DoH query to {provider} failed: {e}");
+                        last_error = Some(e);
+                    }
+                }
+            }
+
+            match last_error {
+                Some(e) => Err(e),
+                None => Ok(vec![]),
+            }
+        }
+    }
+
+    /// RFC 4648 base32 encoding without padding.
+    fn base32(data: &[u8]) -> String {
+        const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+        let mut result = String::with_capacity((data.len() * 8).div_ceil(5));
+        let mut buffer = 0u16;
+        let mut bits = 0;
+        for &byte in data {
+            buffer = (buffer << 8) | byte as u16;
+            bits += 8;
+            while bits >= 5 {
+                bits -= 5;
+                result.push(ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+            }
+        }
+        if bits > 0 {
+            result.push(ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+        }
+        result
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_base32() {
+            assert_eq!(base32(b""), "");
+            assert_eq!(base32(b"f"), "MY");
+            assert_eq!(base32(b"foobar"), "MZXW6YTBOI");
+            assert_eq!(base32(b"api.protonmail.ch"), "MFYGSLTQOJXXI33ONVQWS3BOMNUA");
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Version of the application sent to the API with the `X-Pm-Appversion` header.
+///
+/// The API expects the format `<platform>-<product>@<major>.<minor>.<patch>`, optionally
+/// followed by a `-<pre-release>` tag and `+<build>` metadata as in semver, e.g.
+/// `linux-bridge@3.4.0-beta.2`. Requests with a missing, malformed or outdated version are
+/// rejected with [`Error::UnsupportedAppVersion`](crate::http::Error::UnsupportedAppVersion).
+///
+/// ```
+/// use proton_api_rs::http::AppVersion;
+///
+/// let version = AppVersion::new("linux", "my-app", 1, 4, 0).pre_release("beta.1");
+/// assert_eq!(version.to_string(), "linux-my-app@1.4.0-beta.1");
+/// assert_eq!("linux-my-app@1.4.0-beta.1".parse::<AppVersion>().unwrap(), version);
+/// assert!("MyApp@1.4".parse::<AppVersion>().is_err());
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AppVersion {
+    platform: String,
+    product: String,
+    major: u32,
+    minor: u32,
+    patch: u32,
+    pre_release: Option<String>,
+    build: Option<String>,
+}
+
+/// Error returned for app versions which do not follow the format expected by the API.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("Invalid app version '{0}', expected <platform>-<product>@<major>.<minor>.<patch>[-<pre-release>][+<build>]")]
+pub struct InvalidAppVersion(pub String);
+
+impl AppVersion {
+    pub fn new(platform: &str, product: &str, major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            platform: platform.to_string(),
+            product: product.to_string(),
+            major,
+            minor,
+            patch,
+            pre_release: None,
+            build: None,
+        }
+    }
+
+    /// Set the pre-release tag, e.g. `beta.1`.
+    pub fn pre_release(mut self, tag: &str) -> Self {
+        self.pre_release = Some(tag.to_string());
+        self
+    }
+
+    /// Set the build metadata.
+    pub fn build(mut self, metadata: &str) -> Self {
+        self.build = Some(metadata.to_string());
+        self
+    }
+
+    pub fn platform(&self) -> &str {
+        &self.platform
+    }
+
+    pub fn product(&self) -> &str {
+        &self.product
+    }
+
+    /// The `(major, minor, patch)` version numbers.
+    pub fn semver(&self) -> (u32, u32, u32) {
+        (self.major, self.minor, self.patch)
+    }
+
+    pub fn get_pre_release(&self) -> Option<&str> {
+        self.pre_release.as_deref()
+    }
+
+    pub fn get_build(&self) -> Option<&str> {
+        self.build.as_deref()
+    }
+
+    /// Check that the version can be sent to the API.
+    pub fn validate(&self) -> Result<(), InvalidAppVersion> {
+        let product_valid = is_identifier(&self.product)
+            && !self.product.starts_with('-')
+            && !self.product.ends_with('-');
+        let valid = !self.platform.is_empty()
+            && self.platform.chars().all(|c| c.is_ascii_alphanumeric())
+            && product_valid
+            && self.pre_release.as_deref().is_none_or(is_dot_separated)
+            && self.build.as_deref().is_none_or(is_dot_separated);
+
+        if valid {
+            Ok(())
+        } else {
+            Err(InvalidAppVersion(self.to_string()))
+        }
+    }
+}
+
+impl Display for AppVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}-{}@{}.{}.{}",
+            self.platform, self.product, self.major, self.minor, self.patch
+        )?;
+        if let Some(tag) = &self.pre_release {
+            write!(f, "-{tag}")?;
+        }
+        if let Some(build) = &self.build {
+            write!(f, "+{build}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for AppVersion {
+    type Err = InvalidAppVersion;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidAppVersion(s.to_string());
+
+        let (name, version) = s.split_once('@').ok_or_else(invalid)?;
+        let (platform, product) = name.split_once('-').ok_or_else(invalid)?;
+        let (version, build) = match version.split_once('+') {
+            Some((version, build)) => (version, Some(build)),
+            None => (version, None),
+        };
+        let (version, pre_release) = match version.split_once('-') {
+            Some((version, tag)) => (version, Some(tag)),
+            None => (version, None),
+        };
+
+        let mut numbers = version.split('.').map(parse_number);
+        let (Some(Some(major)), Some(Some(minor)), Some(Some(patch)), None) = (
+            numbers.next(),
+            numbers.next(),
+            numbers.next(),
+            numbers.next(),
+        ) else {
+            return Err(invalid());
+        };
+
+        let mut app_version = AppVersion::new(platform, product, major, minor, patch);
+        app_version.pre_release = pre_release.map(str::to_string);
+        app_version.build = build.map(str::to_string);
+        app_version.validate().map_err(|_| invalid())?;
+        Ok(app_version)
+    }
+}
+
+/// Parse a semver version number, which has no leading zeros.
+fn parse_number(value: &str) -> Option<u32> {
+    if value.is_empty() || (value.len() > 1 && value.starts_with('0')) {
+        return None;
+    }
+    value.parse().ok()
+}
+
+fn is_identifier(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+fn is_dot_separated(value: &str) -> bool {
+    value.split('.').all(is_identifier)
+}
+
+/// Check that `locale` is a language tag such as `en` or `en-US`.
+#[allow(unused)] // Only used by http implementations.
+pub(crate) fn is_valid_locale(locale: &str) -> bool {
+    let mut parts = locale.split(['-', '_']);
+    parts.next().is_some_and(|language| {
+        (2..=3).contains(&language.len()) && language.chars().all(|c| c.is_ascii_alphabetic())
+    }) && parts.all(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// Check that `timezone` is an IANA time zone name such as `Europe/Zurich` or `UTC`.
+#[allow(unused)] // Only used by http implementations.
+pub(crate) fn is_valid_timezone(timezone: &str) -> bool {
+    !timezone.is_empty()
+        && timezone
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '/' | '_' | '-' | '+'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_app_version() {
+        let version = "macos-bridge@3.4.10-beta.2+abc"
+            .parse::<AppVersion>()
+            .unwrap();
+        assert_eq!(version.platform(), "macos");
+        assert_eq!(version.product(), "bridge");
+        assert_eq!(version.semver(), (3, 4, 10));
+        assert_eq!(version.get_pre_release(), Some("beta.2"));
+        assert_eq!(version.get_build(), Some("abc"));
+        assert_eq!(version.to_string(), "macos-bridge@3.4.10-beta.2+abc");
+
+        let version = "web-account-lite@5.0.1".parse::<AppVersion>().unwrap();
+        assert_eq!(version.product(), "account-lite");
+
+        for invalid in [
+            "proton-api-rs",
+            "test@1.0.0",
+            "linux-@1.0.0",
+            "linux-app@1.0",
+            "linux-app@1.0.0.0",
+            "linux-app@01.0.0",
+            "linux-app@1.0.0-",
+            "linux-app@1.0.0-beta..1",
+            "linux app-x@1.0.0",
+        ] {
+            assert!(invalid.parse::<AppVersion>().is_err(), "{invalid}");
+        }
+
+        assert!(AppVersion::new("linux", "app", 1, 0, 0)
+            .pre_release("beta 1")
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_locale_and_timezone() {
+        assert!(is_valid_locale("en"));
+        assert!(is_valid_locale("en-US"));
+        assert!(is_valid_locale("zh_Hant_TW"));
+        assert!(!is_valid_locale("english"));
+        assert!(!is_valid_locale("en-"));
+        assert!(!is_valid_locale("en US"));
+
+        assert!(is_valid_timezone("Europe/Zurich"));
+        assert!(is_valid_timezone("America/Argentina/Buenos_Aires"));
+        assert!(is_valid_timezone("Etc/GMT+2"));
+        assert!(!is_valid_timezone(""));
+        assert!(!is_valid_timezone("Europe/Zurich\r\nX-Injected: 1"));
+    }
+}
@@ -0,0 +1,486 @@
+use crate::http::{Method, RequestData, ResponseMeta, X_PM_UID_HEADER};
+use base64::Engine;
+use bytes::Bytes;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+const IF_NONE_MATCH: &str = "If-None-Match";
+const IF_MODIFIED_SINCE: &str = "If-Modified-Since";
+
+/// Response stored in an [`HttpCache`], along with its validators.
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    pub headers: Vec<(String, String)>,
+    pub body: Bytes,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub stored_at: SystemTime,
+}
+
+impl CachedResponse {
+    /// Size of the entry used to bound the size of the stores.
+    pub fn size(&self) -> usize {
+        self.body.len()
+            + self
+                .headers
+                .iter()
+                .map(|(k, v)| k.len() + v.len())
+                .sum::<usize>()
+    }
+}
+
+/// Storage backend of an [`HttpCache`]. Keys identify a request url and the session it was
+/// sent with.
+pub trait CacheStore: Send + Sync {
+    fn get(&self, key: &str) -> Option<CachedResponse>;
+
+    fn put(&self, key: &str, response: CachedResponse);
+
+    fn remove(&self, key: &str);
+
+    /// Remove all the entries.
+    fn clear(&self);
+}
+
+/// Cache for the responses of GET requests, revalidated with `ETag` and `Last-Modified`.
+///
+/// Responses carrying a validator are stored, and the next request to the same url is sent
+/// with `If-None-Match`/`If-Modified-Since`. When the server answers with 304 Not Modified, the
+/// cached body is handed to the [`FromResponse`](crate::http::FromResponse) implementation as
+/// if it had been sent again, so typed requests work unchanged. Streamed requests, responses
+/// with `Cache-Control: no-store` and requests which already carry conditional headers are
+/// never cached.
+///
+/// Entries are keyed by url and session UID, so sessions sharing a client never see each
+/// other's responses. Clones share the same store.
+///
+/// ```
+/// use proton_api_rs::http::{ClientBuilder, HttpCache, MemoryCacheStore};
+/// use std::time::Duration;
+///
+/// let cache = HttpCache::new(MemoryCacheStore::new(16 * 1024 * 1024)).ttl(Duration::from_secs(3600));
+/// let builder = ClientBuilder::new().http_cache(cache);
+/// ```
+#[derive(Clone)]
+pub struct HttpCache {
+    store: Arc<dyn CacheStore>,
+    ttl: Option<Duration>,
+}
+
+impl Debug for HttpCache {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HttpCache(ttl={:?})", self.ttl)
+    }
+}
+
+impl HttpCache {
+    pub fn new(store: impl CacheStore + 'static) -> Self {
+        Self {
+            store: Arc::new(store),
+            ttl: None,
+        }
+    }
+
+    /// Ignore entries which were stored more than `ttl` ago. By default entries are kept until
+    /// the store evicts them.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Remove all the cached responses.
+    pub fn clear(&self) {
+        self.store.clear()
+    }
+
+    /// Look up the cached response for `request` and add the conditional headers to revalidate
+    /// it. Returns the cached response, which must be passed to [`HttpCache::complete`].
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn prepare(&self, request: &mut RequestData) -> Option<CachedResponse> {
+        if !Self::is_cacheable(request) {
+            return None;
+        }
+
+        let key = Self::key(request);
+        let cached = self.store.get(&key)?;
+        if self
+            .ttl
+            .is_some_and(|ttl| cached.stored_at.elapsed().unwrap_or_default() > ttl)
+        {
+            self.store.remove(&key);
+            return None;
+        }
+
+        if let Some(etag) = &cached.etag {
+            request
+                .headers
+                .insert(IF_NONE_MATCH.to_string(), etag.clone());
+        }
+        if let Some(last_modified) = &cached.last_modified {
+            request
+                .headers
+                .insert(IF_MODIFIED_SINCE.to_string(), last_modified.clone());
+        }
+
+        Some(cached)
+    }
+
+    /// Serve the `cached` response if the server answered with 304 Not Modified, or store the
+    /// new response if it carries a validator.
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn complete(
+        &self,
+        request: &RequestData,
+        cached: Option<CachedResponse>,
+        meta: &mut ResponseMeta,
+        body: &mut Bytes,
+    ) {
+        if request.method != Method::Get {
+            return;
+        }
+
+        if meta.status() == 304 {
+            if let Some(cached) = cached {
+                *meta = ResponseMeta::new(200, cached.headers);
+                *body = cached.body;
+            }
+            return;
+        }
+
+        let no_store = meta
+            .header("Cache-Control")
+            .is_some_and(|v| v.to_ascii_lowercase().contains("no-store"));
+        let etag = meta.header("ETag").map(str::to_string);
+        let last_modified = meta.header("Last-Modified").map(str::to_string);
+        if meta.status() != 200 || no_store || (etag.is_none() && last_modified.is_none()) {
+            return;
+        }
+
+        self.store.put(
+            &Self::key(request),
+            CachedResponse {
+                headers: meta
+                    .headers()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+                body: body.clone(),
+                etag,
+                last_modified,
+                stored_at: SystemTime::now(),
+            },
+        );
+    }
+
+    fn is_cacheable(request: &RequestData) -> bool {
+        request.method == Method::Get
+            && !request.headers.keys().any(|k| {
+                k.eq_ignore_ascii_case(IF_NONE_MATCH) || k.eq_ignore_ascii_case(IF_MODIFIED_SINCE)
+            })
+    }
+
+    fn key(request: &RequestData) -> String {
+        let uid = request
+            .headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(X_PM_UID_HEADER))
+            .map(|(_, v)| v.as_str())
+            .unwrap_or_default();
+        format!("{uid} {}", request.url)
+    }
+}
+
+/// In-memory [`CacheStore`] which evicts the least recently used entries once the total size
+/// of the entries exceeds its limit.
+#[derive(Debug)]
+pub struct MemoryCacheStore {
+    max_size: usize,
+    state: Mutex<MemoryState>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryState {
+    entries: HashMap<String, (CachedResponse, u64)>,
+    size: usize,
+    clock: u64,
+}
+
+impl MemoryCacheStore {
+    /// Create a store holding at most `max_size` bytes of responses.
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            max_size,
+            state: Mutex::new(MemoryState::default()),
+        }
+    }
+}
+
+impl CacheStore for MemoryCacheStore {
+    fn get(&self, key: &str) -> Option<CachedResponse> {
+        let mut state = self.state.lock();
+        state.clock += 1;
+        let clock = state.clock;
+        let (response, last_used) = state.entries.get_mut(key)?;
+        *last_used = clock;
+        Some(response.clone())
+    }
+
+    fn put(&self, key: &str, response: CachedResponse) {
+        self.remove(key);
+        if response.size() > self.max_size {
+            return;
+        }
+
+        let mut state = self.state.lock();
+        while state.size + response.size() > self.max_size {
+            let Some(oldest) = state
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(k, _)| k.clone())
+            else {
+                break;
+            };
+            if let Some((evicted, _)) = state.entries.remove(&oldest) {
+                state.size -= evicted.size();
+            }
+        }
+
+        state.clock += 1;
+        state.size += response.size();
+        let clock = state.clock;
+        state.entries.insert(key.to_string(), (response, clock));
+    }
+
+    fn remove(&self, key: &str) {
+        let mut state = self.state.lock();
+        if let Some((removed, _)) = state.entries.remove(key) {
+            state.size -= removed.size();
+        }
+    }
+
+    fn clear(&self) {
+        let mut state = self.state.lock();
+        state.entries.clear();
+        state.size = 0;
+    }
+}
+
+/// [`CacheStore`] which keeps every entry in a JSON file of a directory, so the cache survives
+/// restarts. Once the files exceed the size limit, the least recently written ones are
+/// removed.
+///
+/// Response bodies are written as is. Only use a directory which is private to the user, since
+/// responses such as the user keys are sensitive.
+#[derive(Debug)]
+pub struct DiskCacheStore {
+    dir: PathBuf,
+    max_size: u64,
+    lock: Mutex<()>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DiskEntry {
+    key: String,
+    headers: Vec<(String, String)>,
+    body: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    stored_at: SystemTime,
+}
+
+impl DiskCacheStore {
+    /// Store the entries in `dir`, which is created if needed, using at most `max_size` bytes.
+    pub fn new(dir: impl AsRef<Path>, max_size: u64) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir.as_ref())?;
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+            max_size,
+            lock: Mutex::new(()),
+        })
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        // FNV-1a, which is stable across builds unlike the std hasher.
+        let hash = key.bytes().fold(0xcbf29ce484222325u64, |h, b| {
+            (h ^ b as u64).wrapping_mul(0x100000001b3)
+        });
+        self.dir.join(format!("{hash:016x}.json"))
+    }
+
+    fn read(&self, key: &str) -> Option<CachedResponse> {
+        let data = std::fs::read(self.path(key)).ok()?;
+        let entry = serde_json::from_slice::<DiskEntry>(&data).ok()?;
+        if entry.key != key {
+            return None;
+        }
+
+        Some(CachedResponse {
+            headers: entry.headers,
+            body: base64::engine::general_purpose::STANDARD
+                .decode(entry.body)
+                .ok()?
+                .into(),
+            etag: entry.etag,
+            last_modified: entry.last_modified,
+            stored_at: entry.stored_at,
+        })
+    }
+
+    fn write(&self, key: &str, response: CachedResponse) -> anyhow::Result<()> {
+        let entry = DiskEntry {
+            key: key.to_string(),
+            headers: response.headers,
+            body: base64::engine::general_purpose::STANDARD.encode(&response.body),
+            etag: response.etag,
+            last_modified: response.last_modified,
+            stored_at: response.stored_at,
+        };
+
+        // Write to a temporary file first so that readers never see a partial entry.
+        let path = self.path(key);
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec(&entry)?)?;
+        std::fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    /// Remove the oldest entries until the directory fits into the size limit.
+    fn evict(&self) -> std::io::Result<()> {
+        let mut files = vec![];
+        for entry in std::fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if entry.path().extension().is_some_and(|e| e == "json") {
+                files.push((metadata.modified()?, metadata.len(), entry.path()));
+            }
+        }
+
+        let mut size = files.iter().map(|(_, len, _)| len).sum::<u64>();
+        files.sort();
+        for (_, len, path) in files {
+            if size <= self.max_size {
+                break;
+            }
+            std::fs::remove_file(path)?;
+            size -= len;
+        }
+        Ok(())
+    }
+}
+
+impl CacheStore for DiskCacheStore {
+    fn get(&self, key: &str) -> Option<CachedResponse> {
+        let _guard = self.lock.lock();
+        self.read(key)
+    }
+
+    fn put(&self, key: &str, response: CachedResponse) {
+        let _guard = self.lock.lock();
+        if let Err(e) = self.write(key, response) {
+            log::warn!("Failed to write http cache entry: {e}");
+            return;
+        }
+        if let Err(e) = self.evict() {
+            log::warn!("Failed to evict http cache entries: {e}");
+        }
+    }
+
+    fn remove(&self, key: &str) {
+        let _guard = self.lock.lock();
+        let _ = std::fs::remove_file(self.path(key));
+    }
+
+    fn clear(&self) {
+        let _guard = self.lock.lock();
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            if entry.path().extension().is_some_and(|e| e == "json") {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(body: &str) -> CachedResponse {
+        CachedResponse {
+            headers: vec![("ETag".to_string(), "\"v1\"".to_string())],
+            body: Bytes::from(body.to_string()),
+            etag: Some("\"v1\"".to_string()),
+            last_modified: None,
+            stored_at: SystemTime::now(),
+        }
+    }
+
+    #[test]
+    fn test_memory_store_evicts_least_recently_used() {
+        let entry_size = response("0123456789").size();
+        let store = MemoryCacheStore::new(entry_size * 2);
+        store.put("a", response("0123456789"));
+        store.put("b", response("0123456789"));
+        assert!(store.get("a").is_some());
+
+        store.put("c", response("0123456789"));
+        assert!(store.get("a").is_some());
+        assert!(store.get("b").is_none());
+        assert!(store.get("c").is_some());
+
+        store.put("big", response(&"x".repeat(entry_size * 2)));
+        assert!(store.get("big").is_none());
+    }
+
+    #[test]
+    fn test_disk_store() {
+        let dir = std::env::temp_dir().join(format!("http-cache-{}", std::process::id()));
+        let store = DiskCacheStore::new(&dir, 1024 * 1024).unwrap();
+        store.put("uid core/v4/users", response("user"));
+
+        let reopened = DiskCacheStore::new(&dir, 1024 * 1024).unwrap();
+        let cached = reopened.get("uid core/v4/users").unwrap();
+        assert_eq!(cached.body, "user");
+        assert_eq!(cached.etag.as_deref(), Some("\"v1\""));
+        assert!(reopened.get("other core/v4/users").is_none());
+
+        reopened.clear();
+        assert!(store.get("uid core/v4/users").is_none());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_prepare_and_complete() {
+        let cache = HttpCache::new(MemoryCacheStore::new(1024));
+        let request =
+            RequestData::new(Method::Get, "core/v4/labels?Type=1").header(X_PM_UID_HEADER, "uid");
+
+        let mut first = request.clone();
+        assert!(cache.prepare(&mut first).is_none());
+        let mut meta = ResponseMeta::new(200, vec![("ETag".to_string(), "\"v1\"".to_string())]);
+        let mut body = Bytes::from_static(b"labels");
+        cache.complete(&first, None, &mut meta, &mut body);
+
+        // Other sessions do not share the entry.
+        let mut other = RequestData::new(Method::Get, "core/v4/labels?Type=1");
+        assert!(cache.prepare(&mut other).is_none());
+
+        let mut second = request.clone();
+        let cached = cache.prepare(&mut second);
+        assert!(cached.is_some());
+        assert_eq!(second.headers.get(IF_NONE_MATCH).unwrap(), "\"v1\"");
+
+        let mut meta = ResponseMeta::new(304, vec![]);
+        let mut body = Bytes::new();
+        cache.complete(&second, cached, &mut meta, &mut body);
+        assert_eq!(meta.status(), 200);
+        assert_eq!(body, "labels");
+    }
+}
@@ -0,0 +1,315 @@
+use crate::http::{BodyReader, BodyStream, ClientRequestBuilder, Error, RequestData, Result};
+use futures_util::future::Either;
+use futures_util::Stream;
+use parking_lot::{Condvar, Mutex};
+use std::fmt::{Display, Formatter};
+use std::future::Future;
+use std::io::Read;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+/// Token which aborts the requests it is attached to once cancelled.
+///
+/// Requests fail with [`Error::Cancelled`] if the token is cancelled before they are sent,
+/// while they are waiting to be retried, or while their body is read. Async requests are also
+/// aborted while they are in flight, whereas blocking requests which are already in flight
+/// only notice the cancellation once the response body is read.
+///
+/// Clones share the same state, so the token can be cancelled from any thread.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<TokenState>);
+
+#[derive(Debug, Default)]
+struct TokenState {
+    state: Mutex<(bool, Vec<Waker>)>,
+    cancelled: Condvar,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel all the requests using this token.
+    pub fn cancel(&self) {
+        let wakers = {
+            let mut state = self.0.state.lock();
+            state.0 = true;
+            std::mem::take(&mut state.1)
+        };
+        self.0.cancelled.notify_all();
+        wakers.into_iter().for_each(Waker::wake);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.state.lock().0
+    }
+
+    /// Wait until the token is cancelled.
+    pub(crate) fn cancelled(&self) -> WaitCancelled {
+        WaitCancelled(self.clone())
+    }
+
+    /// Block for `duration` or until the token is cancelled. Returns whether it was cancelled.
+    fn sleep(&self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        let mut state = self.0.state.lock();
+        while !state.0 {
+            if self
+                .0
+                .cancelled
+                .wait_until(&mut state, deadline)
+                .timed_out()
+            {
+                break;
+            }
+        }
+        state.0
+    }
+}
+
+/// Future which completes once a [`CancellationToken`] is cancelled.
+pub(crate) struct WaitCancelled(CancellationToken);
+
+impl Future for WaitCancelled {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = (self.0).0.state.lock();
+        if state.0 {
+            return Poll::Ready(());
+        }
+
+        if !state.1.iter().any(|w| w.will_wake(cx.waker())) {
+            state.1.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+/// Error wrapped into the io errors of cancelled body reads.
+#[derive(Debug)]
+pub(crate) struct RequestCancelled;
+
+impl Display for RequestCancelled {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Request was cancelled")
+    }
+}
+
+impl std::error::Error for RequestCancelled {}
+
+/// Time budget and cancellation token of a request, checked by the http clients before every
+/// attempt and while waiting in between.
+#[derive(Debug, Clone)]
+pub(crate) struct RequestBudget {
+    deadline: Option<Instant>,
+    token: Option<CancellationToken>,
+}
+
+impl RequestBudget {
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn new(request: &RequestData) -> Self {
+        Self {
+            deadline: request.timeout.map(|t| Instant::now() + t),
+            token: request.cancellation.clone(),
+        }
+    }
+
+    /// Budget of the sequence run with `client`, see
+    /// [`Sequence::with_deadline`](crate::http::Sequence::with_deadline).
+    pub(crate) fn of_client<T: ClientRequestBuilder>(client: &T) -> Self {
+        Self {
+            deadline: client.deadline(),
+            token: client.cancellation_token().cloned(),
+        }
+    }
+
+    /// Check that the request may still be sent and get the time it has left.
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn check(&self) -> Result<Option<Duration>> {
+        if self
+            .token
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+        {
+            return Err(Error::Cancelled);
+        }
+
+        match self.deadline {
+            Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                Some(remaining) if !remaining.is_zero() => Ok(Some(remaining)),
+                _ => Err(deadline_exceeded()),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Check that waiting for `delay` before the next attempt does not exceed the deadline.
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn check_delay(&self, delay: Duration) -> Result<()> {
+        match self.check()? {
+            Some(remaining) if remaining <= delay => Err(deadline_exceeded()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Block for `delay` before the next attempt, unless the request is cancelled meanwhile.
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn sleep_blocking(&self, delay: Duration) -> Result<()> {
+        self.check_delay(delay)?;
+        match &self.token {
+            Some(token) if token.sleep(delay) => Err(Error::Cancelled),
+            Some(_) => Ok(()),
+            None => {
+                std::thread::sleep(delay);
+                Ok(())
+            }
+        }
+    }
+
+    /// Wait for `delay` before the next attempt, unless the request is cancelled meanwhile.
+    pub(crate) async fn sleep(&self, delay: Duration) -> Result<()> {
+        self.check_delay(delay)?;
+        self.run(async {
+            crate::http::sleep(delay).await;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Run `future`, aborting it if the request is cancelled.
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) async fn run<T>(&self, future: impl Future<Output = Result<T>>) -> Result<T> {
+        let Some(token) = &self.token else {
+            return future.await;
+        };
+
+        futures_util::pin_mut!(future);
+        match futures_util::future::select(future, token.cancelled()).await {
+            Either::Left((result, _)) => result,
+            Either::Right(_) => Err(Error::Cancelled),
+        }
+    }
+
+    /// Make reads from `reader` fail once the request is cancelled.
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn reader(&self, reader: BodyReader) -> BodyReader {
+        match &self.token {
+            Some(token) => Box::new(CancellableReader {
+                reader,
+                token: token.clone(),
+            }),
+            None => reader,
+        }
+    }
+
+    /// Make `stream` fail once the request is cancelled.
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn stream(&self, stream: BodyStream) -> BodyStream {
+        match &self.token {
+            Some(token) => Box::pin(CancellableStream {
+                stream: Some(stream),
+                cancelled: token.cancelled(),
+            }),
+            None => stream,
+        }
+    }
+}
+
+fn deadline_exceeded() -> Error {
+    Error::Timeout(anyhow::anyhow!("Request deadline exceeded"))
+}
+
+struct CancellableReader {
+    reader: BodyReader,
+    token: CancellationToken,
+}
+
+impl Read for CancellableReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.token.is_cancelled() {
+            return Err(std::io::Error::other(RequestCancelled));
+        }
+        self.reader.read(buf)
+    }
+}
+
+struct CancellableStream {
+    stream: Option<BodyStream>,
+    cancelled: WaitCancelled,
+}
+
+impl Stream for CancellableStream {
+    type Item = Result<bytes::Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.stream.is_none() {
+            return Poll::Ready(None);
+        }
+
+        if Pin::new(&mut self.cancelled).poll(cx).is_ready() {
+            self.stream = None;
+            return Poll::Ready(Some(Err(Error::Cancelled)));
+        }
+
+        match self.stream.as_mut() {
+            Some(stream) => stream.as_mut().poll_next(cx),
+            None => Poll::Ready(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::Method;
+
+    #[test]
+    fn test_budget() {
+        let token = CancellationToken::new();
+        let request = RequestData::new(Method::Get, "core/v4/users")
+            .timeout(Duration::from_secs(10))
+            .cancellation_token(token.clone());
+        let budget = RequestBudget::new(&request);
+
+        let remaining = budget.check().unwrap().unwrap();
+        assert!(remaining > Duration::from_secs(9) && remaining <= Duration::from_secs(10));
+        assert!(matches!(
+            budget.check_delay(Duration::from_secs(11)),
+            Err(Error::Timeout(_))
+        ));
+
+        token.cancel();
+        assert!(matches!(budget.check(), Err(Error::Cancelled)));
+
+        let expired = RequestData::new(Method::Get, "core/v4/users").timeout(Duration::ZERO);
+        assert!(matches!(
+            RequestBudget::new(&expired).check(),
+            Err(Error::Timeout(_))
+        ));
+    }
+
+    #[test]
+    fn test_cancel_wakes_sleep() {
+        let token = CancellationToken::new();
+        let request =
+            RequestData::new(Method::Get, "core/v4/users").cancellation_token(token.clone());
+        let budget = RequestBudget::new(&request);
+
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            token.cancel();
+        });
+
+        let start = Instant::now();
+        assert!(matches!(
+            budget.sleep_blocking(Duration::from_secs(10)),
+            Err(Error::Cancelled)
+        ));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        canceller.join().unwrap();
+    }
+}
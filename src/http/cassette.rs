@@ -0,0 +1,504 @@
+//! Record and replay HTTP interactions for offline regression tests.
+//!
+//! [`RecordingClient`] wraps any http client and records every request and response it sees
+//! into a [`Cassette`], which can be saved to disk as JSON. Secrets such as the authorization
+//! header, the session UID, access/refresh tokens and SRP proofs are redacted before they are
+//! recorded, so the cassettes can be committed alongside the tests.
+//!
+//! Responses which would be streamed, such as attachment downloads, are read into memory while
+//! recording so their body ends up in the cassette. They are therefore subject to
+//! [`ClientBuilder::max_body_size`](crate::http::ClientBuilder::max_body_size).
+//!
+//! [`Cassette::replay`] turns a recorded cassette into a [`MockClient`] which answers every
+//! recorded request once and fails with an error on any request which was not recorded.
+//! Requests are matched on method, url and body, with the same secrets redacted, so they may be
+//! replayed in any order. Identical requests get their responses in recording order.
+//!
+//! Since SRP proofs are random, the `ServerProof` of a recorded login can never be verified on
+//! replay. Cassettes are best suited for flows which start from an already logged in session.
+//!
+//! ```no_run
+//! # #[cfg(feature = "http-ureq")]
+//! # fn example() -> anyhow::Result<()> {
+//! use proton_api_rs::http::cassette::{Cassette, RecordingClient};
+//! use proton_api_rs::http::ureq_client::UReqClient;
+//! use proton_api_rs::http::ClientBuilder;
+//!
+//! let client = ClientBuilder::new().build::<RecordingClient<UReqClient>>()?;
+//! // ... execute requests with client ...
+//! client.save("tests/cassettes/user.json")?;
+//!
+//! let replay = Cassette::load("tests/cassettes/user.json")?.replay();
+//! // ... execute the same requests with replay ...
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::http::mock_client::{MockClient, MockMatcher, MockResponse};
+use crate::http::redact::REDACTED;
+use crate::http::{
+    split_query, CancellationToken, ClientAsync, ClientBuilder, ClientRequestBuilder, ClientSync,
+    FromResponse, Method, Middleware, Redactor, RequestData, ResponseBodyAsync, ResponseBodySync,
+    ResponseMeta, Result, ServerClock,
+};
+use base64::Engine;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::path::Path;
+#[cfg(not(feature = "async-traits"))]
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Recorded request or response body.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordedBody {
+    Json(serde_json::Value),
+    Text(String),
+    Base64(String),
+}
+
+impl RecordedBody {
+    /// Convert `body` into its recorded form, redacting secrets from JSON bodies.
+    fn record(body: &[u8], redactor: &Redactor) -> Option<Self> {
+        if body.is_empty() {
+            return None;
+        }
+
+        if let Ok(mut value) = serde_json::from_slice::<serde_json::Value>(body) {
+            redactor.redact_json(&mut value);
+            return Some(Self::Json(value));
+        }
+
+        match std::str::from_utf8(body) {
+            Ok(text) => Some(Self::Text(text.to_string())),
+            Err(_) => Some(Self::Base64(
+                base64::engine::general_purpose::STANDARD.encode(body),
+            )),
+        }
+    }
+
+    fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        Ok(match self {
+            Self::Json(value) => serde_json::to_vec(value)?,
+            Self::Text(text) => text.as_bytes().to_vec(),
+            Self::Base64(data) => base64::engine::general_purpose::STANDARD.decode(data)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: Method,
+    pub url: String,
+    pub headers: BTreeMap<String, String>,
+    pub body: Option<RecordedBody>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<RecordedBody>,
+}
+
+/// A single recorded request and the response it received.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+/// Ordered list of recorded interactions.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Cassette {
+    interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        Ok(serde_json::from_reader(std::io::BufReader::new(file))?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let file = std::fs::File::create(path)?;
+        serde_json::to_writer_pretty(std::io::BufWriter::new(file), self)?;
+        Ok(())
+    }
+
+    pub fn interactions(&self) -> &[Interaction] {
+        &self.interactions
+    }
+
+    /// Record `request` and its `response`, redacting all the secrets.
+    pub fn record(&mut self, request: &RequestData, response: &ResponseMeta, body: &[u8]) {
+        let redactor = Redactor::credentials();
+        let headers = request
+            .headers()
+            .iter()
+            .map(|(k, v)| (k.clone(), redact_header(&redactor, k, v)))
+            .collect();
+
+        let response_headers = response
+            .headers()
+            .map(|(k, v)| (k.to_string(), redact_header(&redactor, k, v)))
+            .collect();
+
+        self.interactions.push(Interaction {
+            request: RecordedRequest {
+                method: request.method(),
+                url: redactor.redact_url(request.url()),
+                headers,
+                body: request
+                    .body()
+                    .and_then(|b| RecordedBody::record(b, &redactor)),
+            },
+            response: RecordedResponse {
+                status: response.status(),
+                headers: response_headers,
+                body: RecordedBody::record(body, &redactor),
+            },
+        });
+    }
+
+    /// Create a [`MockClient`] which answers every recorded request once. Requests don't have to
+    /// be sent in recording order, but identical requests get their responses in that order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a recorded response body can't be decoded.
+    pub fn replay(&self) -> MockClient {
+        let client = MockClient::new();
+        for interaction in &self.interactions {
+            client.mock_once(
+                request_matcher(&interaction.request),
+                recorded_response(&interaction.response),
+            );
+        }
+        client
+    }
+}
+
+fn redact_header(redactor: &Redactor, name: &str, value: &str) -> String {
+    if redactor.is_sensitive_header(name) {
+        REDACTED.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn request_matcher(request: &RecordedRequest) -> MockMatcher {
+    let (path, query) = split_query(&request.url);
+    let mut matcher = MockMatcher::new(request.method, path);
+    // Redacted parameters match any value, as their recorded value is lost.
+    for (k, v) in query.into_iter().filter(|(_, v)| v != REDACTED) {
+        matcher = matcher.query(k, v);
+    }
+
+    let body = request.body.clone();
+    let redactor = Redactor::credentials();
+    matcher.body(move |b| RecordedBody::record(b, &redactor) == body)
+}
+
+fn recorded_response(response: &RecordedResponse) -> MockResponse {
+    let mut mock = MockResponse::new(response.status);
+    for (k, v) in &response.headers {
+        mock = mock.header(k, v);
+    }
+
+    if let Some(body) = &response.body {
+        mock = mock.body(body.to_bytes().expect("Failed to decode recorded body"));
+    }
+
+    mock
+}
+
+/// Middleware which records every response into a shared [`Cassette`].
+///
+/// The http clients pass streamed responses to the middlewares with an empty body, use
+/// [`RecordingClient`] to record their body as well.
+#[derive(Debug, Clone, Default)]
+pub struct CassetteRecorder {
+    cassette: Arc<Mutex<Cassette>>,
+}
+
+impl CassetteRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get a copy of everything recorded so far.
+    pub fn cassette(&self) -> Cassette {
+        self.cassette.lock().clone()
+    }
+}
+
+impl Middleware for CassetteRecorder {
+    fn on_response(
+        &self,
+        request: &RequestData,
+        response: &ResponseMeta,
+        body: &[u8],
+    ) -> Result<()> {
+        self.cassette.lock().record(request, response, body);
+        Ok(())
+    }
+}
+
+/// Wraps the http client `C` and records all its interactions with a [`CassetteRecorder`].
+#[derive(Debug, Clone)]
+pub struct RecordingClient<C> {
+    inner: C,
+    recorder: CassetteRecorder,
+}
+
+impl<C> RecordingClient<C> {
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    /// Get a copy of everything recorded so far.
+    pub fn cassette(&self) -> Cassette {
+        self.recorder.cassette()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        self.cassette().save(path)
+    }
+}
+
+impl<C: TryFrom<ClientBuilder, Error = anyhow::Error>> TryFrom<ClientBuilder>
+    for RecordingClient<C>
+{
+    type Error = anyhow::Error;
+
+    fn try_from(value: ClientBuilder) -> std::result::Result<Self, Self::Error> {
+        let recorder = CassetteRecorder::new();
+        let inner = C::try_from(value.middleware(recorder.clone()))?;
+        Ok(Self { inner, recorder })
+    }
+}
+
+impl<C: ClientRequestBuilder> ClientRequestBuilder for RecordingClient<C> {
+    type Request = C::Request;
+
+    fn new_request(&self, data: &RequestData) -> Self::Request {
+        self.inner.new_request(data)
+    }
+
+    fn server_clock(&self) -> Option<&ServerClock> {
+        self.inner.server_clock()
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.inner.deadline()
+    }
+
+    fn cancellation_token(&self) -> Option<&CancellationToken> {
+        self.inner.cancellation_token()
+    }
+}
+
+impl<C: ClientSync> ClientSync for RecordingClient<C> {
+    fn execute<R: FromResponse>(&self, request: Self::Request) -> Result<R::Output> {
+        self.inner.execute::<Recorded<R>>(request)
+    }
+}
+
+impl<C: ClientAsync> ClientAsync for RecordingClient<C> {
+    #[cfg(not(feature = "async-traits"))]
+    fn execute_async<R: FromResponse>(
+        &self,
+        request: Self::Request,
+    ) -> Pin<Box<dyn Future<Output = Result<R::Output>> + Send + '_>> {
+        self.inner.execute_async::<Recorded<R>>(request)
+    }
+
+    #[cfg(feature = "async-traits")]
+    async fn execute_async<R: FromResponse>(&self, request: Self::Request) -> Result<R::Output> {
+        self.inner.execute_async::<Recorded<R>>(request).await
+    }
+}
+
+/// Parses the response with `R`, but makes the client read streamed bodies into memory first
+/// so the [`CassetteRecorder`] sees them.
+struct Recorded<R>(PhantomData<R>);
+
+impl<R: FromResponse> FromResponse for Recorded<R> {
+    type Output = R::Output;
+
+    fn from_response_sync<T: ResponseBodySync>(response: T) -> Result<Self::Output> {
+        R::from_response_sync(response)
+    }
+
+    #[cfg(not(feature = "async-traits"))]
+    fn from_response_async<T: ResponseBodyAsync + Send + 'static>(
+        response: T,
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Output>> + Send>> {
+        R::from_response_async(response)
+    }
+
+    #[cfg(feature = "async-traits")]
+    fn from_response_async<T: ResponseBodyAsync + Send + 'static>(
+        response: T,
+    ) -> impl Future<Output = Result<Self::Output>> + Send {
+        R::from_response_async(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{Error, JsonResponse, NoResponse, OwnedRequest, Sequence, StringResponse};
+
+    fn refresh_request(token: &str) -> OwnedRequest<JsonResponse<serde_json::Value>> {
+        OwnedRequest::new(
+            RequestData::new(Method::Post, "auth/v4/refresh")
+                .header("X-Pm-Uid", "uid-1")
+                .json(serde_json::json!({"UID": "uid-1", "RefreshToken": token})),
+        )
+    }
+
+    fn recording_client() -> RecordingClient<MockClient> {
+        let client = ClientBuilder::new()
+            .build::<RecordingClient<MockClient>>()
+            .unwrap();
+        client.inner().mock(
+            MockMatcher::new(Method::Post, "auth/v4/refresh"),
+            MockResponse::json(
+                200,
+                serde_json::json!({"UID": "uid-1", "AccessToken": "access", "Scopes": ["mail"]}),
+            ),
+        );
+        client.inner().mock(
+            MockMatcher::new(Method::Get, "core/v4/users"),
+            MockResponse::json(401, serde_json::json!({"Code": 401})),
+        );
+        client
+    }
+
+    #[test]
+    fn test_record_redacts_secrets() {
+        let client = recording_client();
+        refresh_request("refresh-1").do_sync(&client).unwrap();
+
+        let cassette = client.cassette();
+        let interaction = &cassette.interactions()[0];
+        assert_eq!(interaction.request.headers["X-Pm-Uid"], REDACTED);
+        assert_eq!(
+            interaction.request.body,
+            Some(RecordedBody::Json(
+                serde_json::json!({"UID": REDACTED, "RefreshToken": REDACTED})
+            ))
+        );
+        assert_eq!(
+            interaction.response.body,
+            Some(RecordedBody::Json(serde_json::json!({
+                "UID": REDACTED,
+                "AccessToken": REDACTED,
+                "Scopes": ["mail"]
+            })))
+        );
+
+        let serialized = serde_json::to_string(&cassette).unwrap();
+        assert!(!serialized.contains("refresh-1"));
+        assert!(!serialized.contains("access"));
+    }
+
+    #[test]
+    fn test_record_redacts_query_secrets() {
+        let client = recording_client();
+        client.inner().mock(
+            MockMatcher::new(Method::Get, "core/v4/captcha"),
+            MockResponse::new(200).body("captcha"),
+        );
+        let captcha = |token: &str| {
+            OwnedRequest::<StringResponse>::new(RequestData::new(
+                Method::Get,
+                format!("core/v4/captcha?Token={token}&ForceWebMessaging=1"),
+            ))
+        };
+        captcha("secret-1").do_sync(&client).unwrap();
+
+        let cassette = client.cassette();
+        let url = &cassette.interactions()[0].request.url;
+        assert_eq!(
+            url,
+            "core/v4/captcha?Token=%3Credacted%3E&ForceWebMessaging=1"
+        );
+        assert!(!serde_json::to_string(&cassette)
+            .unwrap()
+            .contains("secret-1"));
+
+        let replay = cassette.replay();
+        assert_eq!(captcha("secret-2").do_sync(&replay).unwrap(), "captcha");
+    }
+
+    #[test]
+    fn test_replay() {
+        let client = recording_client();
+        refresh_request("refresh-1").do_sync(&client).unwrap();
+        let user = || {
+            OwnedRequest::<JsonResponse<serde_json::Value>>::new(RequestData::new(
+                Method::Get,
+                "core/v4/users",
+            ))
+        };
+        assert!(user().do_sync(&client).is_err());
+
+        let path = std::env::temp_dir().join(format!("cassette-{}.json", std::process::id()));
+        client.save(&path).unwrap();
+        let replay = Cassette::load(&path).unwrap().replay();
+        std::fs::remove_file(&path).unwrap();
+
+        // Requests may be replayed in any order. Secrets differ from the recording, but are
+        // redacted before matching.
+        assert!(matches!(user().do_sync(&replay), Err(Error::API(e)) if e.http_code == 401));
+        let v = refresh_request("refresh-2").do_sync(&replay).unwrap();
+        assert_eq!(v["Scopes"], serde_json::json!(["mail"]));
+
+        // Every interaction is replayed only once.
+        assert!(matches!(
+            refresh_request("refresh-2").do_sync(&replay),
+            Err(Error::Other(_))
+        ));
+    }
+
+    #[test]
+    fn test_recording_client_server_clock() {
+        let clock = ServerClock::new();
+        let client = ClientBuilder::new()
+            .server_clock(clock.clone())
+            .build::<RecordingClient<MockClient>>()
+            .unwrap();
+        client.inner().mock(
+            MockMatcher::new(Method::Get, "tests/ping"),
+            MockResponse::new(200).header("Date", "Thu, 01 Jan 2015 00:00:00 GMT"),
+        );
+        OwnedRequest::<NoResponse>::new(RequestData::new(Method::Get, "tests/ping"))
+            .do_sync(&client)
+            .unwrap();
+
+        assert!(clock.offset_millis().is_some());
+        assert_eq!(
+            client.server_clock().unwrap().offset_millis(),
+            clock.offset_millis()
+        );
+    }
+
+    #[test]
+    fn test_binary_body_round_trip() {
+        let body = RecordedBody::record(&[0xff, 0x00, 0x10], &Redactor::credentials()).unwrap();
+        assert!(matches!(body, RecordedBody::Base64(_)));
+        assert_eq!(body.to_bytes().unwrap(), vec![0xff, 0x00, 0x10]);
+    }
+}
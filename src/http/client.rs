@@ -1,20 +1,48 @@
-use crate::http::{Proxy, RequestData, Result, DEFAULT_APP_VERSION, DEFAULT_HOST_URL};
+use crate::http::{
+    is_valid_locale, is_valid_timezone, AltRouting, AppVersion, AppVersionMiddleware, BodyReader,
+    BodyStream, CancellationToken, DebugMiddleware, DefaultHeadersMiddleware, HttpCache,
+    MetricsHook, MetricsObserver, Middleware, MiddlewareChain, Proxy, RateLimiter, Redactor,
+    RequestData, ResponseMeta, Result, RetryPolicy, ServerClock, SpkiPins, DEFAULT_APP_VERSION,
+    DEFAULT_HOST_URL, DEFAULT_MAX_BODY_SIZE, DEFAULT_POOL_IDLE_TIMEOUT,
+    DEFAULT_POOL_MAX_IDLE_PER_HOST, X_PM_LOCALE_HEADER, X_PM_TIMEZONE_HEADER,
+};
+use std::collections::HashMap;
 use std::future::Future;
+use std::net::SocketAddr;
 #[cfg(not(feature = "async-traits"))]
 use std::pin::Pin;
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
 /// Builder for an http client
 #[derive(Debug, Clone)]
 pub struct ClientBuilder {
-    pub(super) app_version: String,
+    pub(super) app_version: Option<String>,
+    pub(super) app_version_unchecked: bool,
+    pub(super) locale: Option<String>,
+    pub(super) timezone: Option<String>,
     pub(super) base_url: String,
     pub(super) request_timeout: Option<Duration>,
     pub(super) connect_timeout: Option<Duration>,
     pub(super) user_agent: String,
     pub(super) proxy_url: Option<Proxy>,
     pub(super) debug: bool,
+    pub(super) redactor: Redactor,
     pub(super) allow_http: bool,
+    pub(super) retry_policy: Option<RetryPolicy>,
+    pub(super) middleware: MiddlewareChain,
+    pub(super) max_body_size: usize,
+    pub(super) alt_routing: Option<AltRouting>,
+    pub(super) spki_pins: Option<SpkiPins>,
+    pub(super) rate_limiter: Option<RateLimiter>,
+    pub(super) metrics: Option<MetricsHook>,
+    pub(super) server_clock: Option<ServerClock>,
+    pub(super) cache: Option<HttpCache>,
+    pub(super) pool_max_idle_per_host: usize,
+    pub(super) pool_idle_timeout: Option<Duration>,
+    pub(super) root_certificates: Vec<Vec<u8>>,
+    pub(super) system_roots: bool,
+    pub(super) dns_overrides: HashMap<String, SocketAddr>,
 }
 
 impl Default for ClientBuilder {
@@ -26,21 +54,71 @@ impl Default for ClientBuilder {
 impl ClientBuilder {
     pub fn new() -> Self {
         Self {
-            app_version: DEFAULT_APP_VERSION.to_string(),
+            app_version: None,
+            app_version_unchecked: false,
+            locale: None,
+            timezone: None,
             user_agent: "NoClient/0.1.0".to_string(),
             base_url: DEFAULT_HOST_URL.to_string(),
             request_timeout: None,
             connect_timeout: None,
             proxy_url: None,
             debug: false,
+            redactor: Redactor::new(),
             allow_http: false,
+            retry_policy: None,
+            middleware: MiddlewareChain::default(),
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            alt_routing: None,
+            spki_pins: None,
+            rate_limiter: None,
+            metrics: None,
+            server_clock: None,
+            cache: None,
+            pool_max_idle_per_host: DEFAULT_POOL_MAX_IDLE_PER_HOST,
+            pool_idle_timeout: Some(DEFAULT_POOL_IDLE_TIMEOUT),
+            root_certificates: vec![],
+            system_roots: true,
+            dns_overrides: HashMap::new(),
         }
     }
 
-    /// Set the app version for this client e.g.: my-client@1.4.0+beta.
+    /// Set the app version for this client e.g.: linux-my-client@1.4.0-beta. Building the client
+    /// fails if the version does not follow the format of [`AppVersion`].
     /// Note: The default app version is not guaranteed to be accepted by the proton servers.
     pub fn app_version(mut self, version: &str) -> Self {
-        self.app_version = version.to_string();
+        self.app_version = Some(version.to_string());
+        self.app_version_unchecked = false;
+        self
+    }
+
+    /// Set the app version for this client.
+    pub fn with_app_version(mut self, version: AppVersion) -> Self {
+        self.app_version = Some(version.to_string());
+        self.app_version_unchecked = false;
+        self
+    }
+
+    /// Set an app version which does not follow the format of [`AppVersion`], such as the
+    /// versions accepted by older releases. The version is sent as is, but the API may reject
+    /// it. Building the client still fails if the version can't be sent as a header.
+    pub fn app_version_unchecked(mut self, version: &str) -> Self {
+        self.app_version = Some(version.to_string());
+        self.app_version_unchecked = true;
+        self
+    }
+
+    /// Set the locale of the user, such as `en-US`, which the API uses to localize its
+    /// messages. It is sent with the `Accept-Language` and `X-Pm-Locale` headers.
+    pub fn locale(mut self, locale: &str) -> Self {
+        self.locale = Some(locale.to_string());
+        self
+    }
+
+    /// Set the IANA time zone of the user, such as `Europe/Zurich`, sent with the
+    /// `X-Pm-Timezone` header.
+    pub fn timezone(mut self, timezone: &str) -> Self {
+        self.timezone = Some(timezone.to_string());
         self
     }
 
@@ -80,12 +158,163 @@ impl ClientBuilder {
         self
     }
 
-    /// Enable request debugging.
+    /// Automatically retry failed requests according to `policy`. By default requests are
+    /// not retried.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Set the maximum size in bytes of a response body which is read into memory. Larger
+    /// responses fail with [`Error::BodyTooLarge`](crate::http::Error::BodyTooLarge). Streamed
+    /// responses are not subject to this limit. The default is 10MB.
+    pub fn max_body_size(mut self, bytes: usize) -> Self {
+        self.max_body_size = bytes;
+        self
+    }
+
+    /// Fail over to alternative hosts when the base url is unreachable. By default alternative
+    /// routing is disabled.
+    pub fn alternative_routing(mut self, routing: AltRouting) -> Self {
+        self.alt_routing = Some(routing);
+        self
+    }
+
+    /// Only accept TLS certificates matching `pins` for the pinned hosts, in addition to the
+    /// verification against the trusted roots. Use [`SpkiPins::proton()`] to pin the Proton API
+    /// servers. By default certificates are only verified against the system roots.
+    pub fn spki_pins(mut self, pins: SpkiPins) -> Self {
+        self.spki_pins = Some(pins);
+        self
+    }
+
+    /// Trust the certificates of the PEM encoded `pem`, which may contain several certificates,
+    /// in addition to the system roots. Invalid certificates make the client build fail.
+    pub fn root_certificate_pem(mut self, pem: impl AsRef<[u8]>) -> Self {
+        self.root_certificates.push(pem.as_ref().to_vec());
+        self
+    }
+
+    /// Only trust the certificates added with [`ClientBuilder::root_certificate_pem`], not the
+    /// system roots.
+    pub fn disable_system_roots(mut self) -> Self {
+        self.system_roots = false;
+        self
+    }
+
+    /// Connect to `addr` instead of resolving `host`. The port of `addr` is ignored in favor
+    /// of the port of the url, as DNS does not resolve ports.
+    pub fn resolve(mut self, host: &str, addr: SocketAddr) -> Self {
+        self.dns_overrides.insert(host.to_ascii_lowercase(), addr);
+        self
+    }
+
+    /// Limit the rate and concurrency of requests. Clients built from this builder, and all
+    /// their clones, share the limits. By default requests are not limited.
+    pub fn rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Report the [`RequestMetrics`](crate::http::RequestMetrics) of every request attempt to
+    /// `observer`.
+    pub fn metrics_observer(mut self, observer: impl MetricsObserver + 'static) -> Self {
+        self.metrics = Some(MetricsHook(Arc::new(observer)));
+        self
+    }
+
+    /// Track the server time with `clock`, which is then shared by all the clients built from
+    /// this builder. By default every client gets its own clock, shared by its clones.
+    pub fn server_clock(mut self, clock: ServerClock) -> Self {
+        self.server_clock = Some(clock);
+        self
+    }
+
+    /// Revalidate GET responses with `cache` instead of downloading them again when they did
+    /// not change. By default responses are not cached.
+    pub fn http_cache(mut self, cache: HttpCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Keep up to `max` idle connections per host open for reuse by later requests. Set it to 0
+    /// to open a new connection for every request. The default is 4.
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool_max_idle_per_host = max;
+        self
+    }
+
+    /// Close idle connections once they were not used for `timeout`, or never if `None`, which
+    /// risks sending requests on connections the server already closed. The default is 30
+    /// seconds.
+    pub fn pool_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.pool_idle_timeout = timeout;
+        self
+    }
+
+    /// Enable request debugging. Requests and responses are logged with `log::debug!`, with
+    /// their secrets masked by the default [`Redactor`].
     pub fn debug(mut self) -> Self {
         self.debug = true;
         self
     }
 
+    /// Enable request debugging, masking secrets with `redactor`.
+    pub fn debug_with_redactor(mut self, redactor: Redactor) -> Self {
+        self.debug = true;
+        self.redactor = redactor;
+        self
+    }
+
+    /// Register a middleware which will be executed for every request. Middlewares are executed
+    /// in registration order for requests and in reverse order for responses.
+    pub fn middleware(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// Validate the client identity and build the middleware chain including the built-in
+    /// middlewares.
+    #[allow(unused)] // Only used by http implementations.
+    pub(super) fn middleware_chain(&self) -> anyhow::Result<MiddlewareChain> {
+        let app_version = match &self.app_version {
+            Some(version) if self.app_version_unchecked => {
+                if version.is_empty() || !version.chars().all(|c| c.is_ascii_graphic()) {
+                    anyhow::bail!("Invalid app version '{version}'");
+                }
+                version.clone()
+            }
+            Some(version) => version.parse::<AppVersion>()?.to_string(),
+            None => DEFAULT_APP_VERSION.to_string(),
+        };
+
+        let mut headers = vec![];
+        if let Some(locale) = &self.locale {
+            if !is_valid_locale(locale) {
+                anyhow::bail!("Invalid locale '{locale}'");
+            }
+            headers.push(("Accept-Language".to_string(), locale.replace('_', "-")));
+            headers.push((X_PM_LOCALE_HEADER.to_string(), locale.replace('-', "_")));
+        }
+        if let Some(timezone) = &self.timezone {
+            if !is_valid_timezone(timezone) {
+                anyhow::bail!("Invalid timezone '{timezone}'");
+            }
+            headers.push((X_PM_TIMEZONE_HEADER.to_string(), timezone.clone()));
+        }
+
+        let mut chain = MiddlewareChain::default();
+        chain.push(Arc::new(AppVersionMiddleware(app_version)));
+        if !headers.is_empty() {
+            chain.push(Arc::new(DefaultHeadersMiddleware(headers)));
+        }
+        chain.extend(&self.middleware);
+        if self.debug {
+            chain.push(Arc::new(DebugMiddleware::new(self.redactor.clone())));
+        }
+        Ok(chain)
+    }
+
     pub fn build<T: TryFrom<ClientBuilder, Error = anyhow::Error> + Clone>(
         self,
     ) -> std::result::Result<T, anyhow::Error> {
@@ -103,6 +332,30 @@ pub trait ClientRequest: Sized + Send {
 pub trait ClientRequestBuilder: Clone {
     type Request: ClientRequest;
     fn new_request(&self, data: &RequestData) -> Self::Request;
+
+    /// Clock tracking the offset to the server time, if the client supports it.
+    fn server_clock(&self) -> Option<&ServerClock> {
+        None
+    }
+
+    /// Current time according to the server. Falls back to the local time if the client does
+    /// not track the server time.
+    fn server_now(&self) -> SystemTime {
+        self.server_clock()
+            .map_or_else(SystemTime::now, ServerClock::now)
+    }
+
+    /// Deadline applied to the requests of the client, see
+    /// [`Sequence::with_deadline`](crate::http::Sequence::with_deadline).
+    fn deadline(&self) -> Option<Instant> {
+        None
+    }
+
+    /// Cancellation token applied to the requests of the client, see
+    /// [`Sequence::with_cancellation`](crate::http::Sequence::with_cancellation).
+    fn cancellation_token(&self) -> Option<&CancellationToken> {
+        None
+    }
 }
 
 /// HTTP Client abstraction Sync.
@@ -118,41 +371,60 @@ pub trait ClientAsync:
     fn execute_async<R: FromResponse>(
         &self,
         request: Self::Request,
-    ) -> Pin<Box<dyn Future<Output = Result<R::Output>> + '_>>;
+    ) -> Pin<Box<dyn Future<Output = Result<R::Output>> + Send + '_>>;
 
     #[cfg(feature = "async-traits")]
     fn execute_async<R: FromResponse>(
         &self,
         request: Self::Request,
-    ) -> impl Future<Output = Result<R::Output>>;
+    ) -> impl Future<Output = Result<R::Output>> + Send;
 }
 
 pub trait ResponseBodySync {
     type Body: AsRef<[u8]>;
+
+    /// Status and headers of the response.
+    fn meta(&self) -> &ResponseMeta;
+
     fn get_body(self) -> Result<Self::Body>;
+
+    /// Read the body incrementally instead of loading it into memory.
+    fn into_reader(self) -> Result<BodyReader>;
 }
 
 pub trait ResponseBodyAsync {
     type Body: AsRef<[u8]>;
 
+    /// Status and headers of the response.
+    fn meta(&self) -> &ResponseMeta;
+
     #[cfg(not(feature = "async-traits"))]
-    fn get_body_async(self) -> Pin<Box<dyn Future<Output = Result<Self::Body>>>>;
+    fn get_body_async(self) -> Pin<Box<dyn Future<Output = Result<Self::Body>> + Send>>;
 
     #[cfg(feature = "async-traits")]
-    fn get_body_async(self) -> impl Future<Output = Result<Self::Body>>;
+    fn get_body_async(self) -> impl Future<Output = Result<Self::Body>> + Send;
+
+    /// Read the body incrementally instead of loading it into memory.
+    fn into_stream(self) -> Result<BodyStream>;
 }
 
 pub trait FromResponse {
     type Output;
+
+    /// Whether the body is consumed with [`ResponseBodySync::into_reader`] or
+    /// [`ResponseBodyAsync::into_stream`]. The clients do not read streamed bodies before
+    /// handing them over, so middlewares only see an empty body.
+    const STREAMING: bool = false;
+
     fn from_response_sync<T: ResponseBodySync>(response: T) -> Result<Self::Output>;
 
     #[cfg(not(feature = "async-traits"))]
-    fn from_response_async<T: ResponseBodyAsync + 'static>(
+    fn from_response_async<T: ResponseBodyAsync + Send + 'static>(
         response: T,
-    ) -> Pin<Box<dyn Future<Output = Result<Self::Output>>>>;
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Output>> + Send>>;
 
     #[cfg(feature = "async-traits")]
-    fn from_response_async<T: ResponseBodyAsync + 'static>(
+    fn from_response_async<T: ResponseBodyAsync + Send + 'static>(
         response: T,
-    ) -> impl Future<Output = Result<Self::Output>>;
+    ) -> impl Future<Output = Result<Self::Output>> + Send;
 }
@@ -0,0 +1,119 @@
+use crate::http::ResponseMeta;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Offset value meaning that no response with a `Date` header has been seen yet.
+const UNKNOWN_OFFSET: i64 = i64::MIN;
+
+/// Tracks the offset between the local clock and the clock of the API servers, as reported
+/// by the `Date` header of every response.
+///
+/// The `Date` header only has a resolution of one second, so the offset is accurate to about
+/// a second plus the latency of the response. Until a response was received, the local clock
+/// is used as is.
+///
+/// Clones share the same offset.
+#[derive(Debug, Clone)]
+pub struct ServerClock {
+    offset_ms: Arc<AtomicI64>,
+}
+
+impl Default for ServerClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ServerClock {
+    pub fn new() -> Self {
+        Self {
+            offset_ms: Arc::new(AtomicI64::new(UNKNOWN_OFFSET)),
+        }
+    }
+
+    /// Milliseconds by which the server clock is ahead of the local clock. Negative if the
+    /// server clock is behind. `None` if no response has been received yet.
+    pub fn offset_millis(&self) -> Option<i64> {
+        match self.offset_ms.load(Ordering::Relaxed) {
+            UNKNOWN_OFFSET => None,
+            offset => Some(offset),
+        }
+    }
+
+    /// Current time according to the server.
+    pub fn now(&self) -> SystemTime {
+        self.to_server_time(SystemTime::now())
+    }
+
+    /// Convert a local `time` into server time.
+    pub fn to_server_time(&self, time: SystemTime) -> SystemTime {
+        match self.offset_millis() {
+            Some(offset) if offset >= 0 => time + Duration::from_millis(offset as u64),
+            Some(offset) => time - Duration::from_millis(offset.unsigned_abs()),
+            None => time,
+        }
+    }
+
+    /// Current time according to the server as a unix timestamp in seconds, the format used
+    /// by the API.
+    pub fn unix_timestamp(&self) -> i64 {
+        match self.now().duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_secs() as i64,
+            Err(e) => -(e.duration().as_secs() as i64),
+        }
+    }
+
+    /// Update the offset from the `Date` header of a response which was just received.
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn update(&self, response: &ResponseMeta) {
+        if let Some(date) = response.date() {
+            self.set_offset(date, SystemTime::now());
+        }
+    }
+
+    /// Copy the offset of `other`, if it is known.
+    pub(crate) fn sync_from(&self, other: &ServerClock) {
+        if let Some(offset) = other.offset_millis() {
+            self.offset_ms.store(offset, Ordering::Relaxed);
+        }
+    }
+
+    fn set_offset(&self, server: SystemTime, local: SystemTime) {
+        let offset = match server.duration_since(local) {
+            Ok(ahead) => ahead.as_millis() as i64,
+            Err(behind) => -(behind.duration().as_millis() as i64),
+        };
+        self.offset_ms.store(offset, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offset() {
+        let clock = ServerClock::new();
+        let local = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        assert_eq!(clock.offset_millis(), None);
+        assert_eq!(clock.to_server_time(local), local);
+
+        clock.set_offset(local + Duration::from_secs(90), local);
+        assert_eq!(clock.offset_millis(), Some(90_000));
+        assert_eq!(clock.to_server_time(local), local + Duration::from_secs(90));
+
+        clock.set_offset(local - Duration::from_millis(1500), local);
+        assert_eq!(clock.offset_millis(), Some(-1500));
+        assert_eq!(
+            clock.to_server_time(local),
+            local - Duration::from_millis(1500)
+        );
+
+        let session_clock = ServerClock::new();
+        session_clock.sync_from(&ServerClock::new());
+        assert_eq!(session_clock.offset_millis(), None);
+        session_clock.sync_from(&clock);
+        assert_eq!(session_clock.offset_millis(), Some(-1500));
+    }
+}
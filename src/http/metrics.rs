@@ -0,0 +1,306 @@
+use crate::http::{error_kind, path_template, Error, Method, RequestData, ResponseMeta};
+use parking_lot::Mutex;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+use std::time::Duration;
+
+const DEFAULT_BUCKETS_MS: [u64; 11] = [5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000];
+
+/// Measurements of a single request attempt.
+#[derive(Debug, Clone)]
+pub struct RequestMetrics {
+    /// Request path with ids and query removed, e.g. `mail/v4/messages/{id}`.
+    pub endpoint: String,
+    pub method: Method,
+    /// Attempt number, starting at 1 and incremented for every retry.
+    pub attempt: u32,
+    /// Http status of the response. `None` if no response was received.
+    pub status: Option<u16>,
+    /// [`APIError::api_code`](crate::requests::APIError::api_code) of failed requests.
+    pub api_code: Option<u32>,
+    /// Class of the error the request failed with, e.g. `api`, `timeout` or `connection`.
+    pub error: Option<&'static str>,
+    /// Size of the request body. Multipart bodies are not measured and count as 0.
+    pub request_bytes: u64,
+    /// Size of the response body. Streamed responses report their `Content-Length`, if any.
+    pub response_bytes: u64,
+    /// Time until the response body was read, or until the headers were received for streamed
+    /// responses.
+    pub duration: Duration,
+}
+
+/// Receives the [`RequestMetrics`] of every request attempt of an http client.
+pub trait MetricsObserver: Send + Sync {
+    fn observe(&self, metrics: &RequestMetrics);
+}
+
+/// Observer registered on a client.
+#[derive(Clone)]
+pub(crate) struct MetricsHook(pub(crate) Arc<dyn MetricsObserver>);
+
+impl MetricsHook {
+    /// Report an attempt of `request` which completed with `outcome`, the response and the size
+    /// of its body if it was read into memory.
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn observe(
+        &self,
+        request: &RequestData,
+        attempt: u32,
+        duration: Duration,
+        outcome: Result<(&ResponseMeta, Option<usize>), &Error>,
+    ) {
+        let (status, api_code, error, response_bytes) = match outcome {
+            Ok((meta, len)) => {
+                let len = len.map(|l| l as u64).or_else(|| {
+                    meta.header("Content-Length")
+                        .and_then(|l| l.trim().parse().ok())
+                });
+                (Some(meta.status()), None, None, len.unwrap_or_default())
+            }
+            Err(e @ (Error::API(api) | Error::UnsupportedAppVersion(api))) => (
+                Some(api.http_code),
+                (api.api_code != 0).then_some(api.api_code),
+                Some(error_kind(e)),
+                0,
+            ),
+            Err(e) => (None, None, Some(error_kind(e)), 0),
+        };
+
+        self.0.observe(&RequestMetrics {
+            endpoint: path_template(request.url()),
+            method: request.method(),
+            attempt,
+            status,
+            api_code,
+            error,
+            request_bytes: request.body().map_or(0, |b| b.len() as u64),
+            response_bytes,
+            duration,
+        });
+    }
+}
+
+impl Debug for MetricsHook {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MetricsObserver")
+    }
+}
+
+/// Counts of the requests sent to one endpoint with one method.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EndpointMetrics {
+    pub endpoint: String,
+    pub method: Method,
+    /// Number of request attempts.
+    pub requests: u64,
+    /// Number of responses per http status.
+    pub statuses: BTreeMap<u16, u64>,
+    /// Number of failed requests per API error code.
+    pub api_codes: BTreeMap<u32, u64>,
+    /// Number of failed requests per error class.
+    pub errors: BTreeMap<&'static str, u64>,
+    pub request_bytes: u64,
+    pub response_bytes: u64,
+    pub duration: Histogram,
+}
+
+/// Cumulative histogram of durations, in the style of Prometheus histograms.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    /// Upper bounds of the buckets and the number of observations which are lower or equal.
+    pub buckets: Vec<(Duration, u64)>,
+    /// Number of observations.
+    pub count: u64,
+    /// Sum of all the observations.
+    pub sum: Duration,
+}
+
+impl Histogram {
+    fn new(bounds: &[Duration]) -> Self {
+        Self {
+            buckets: bounds.iter().map(|b| (*b, 0)).collect(),
+            count: 0,
+            sum: Duration::ZERO,
+        }
+    }
+
+    fn observe(&mut self, value: Duration) {
+        for (bound, count) in &mut self.buckets {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += value;
+    }
+}
+
+/// Metrics of all the endpoints at a point in time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    /// Endpoints sorted by path and method.
+    pub endpoints: Vec<EndpointMetrics>,
+}
+
+impl MetricsSnapshot {
+    /// Get the metrics of `endpoint` for `method`.
+    pub fn endpoint(&self, endpoint: &str, method: Method) -> Option<&EndpointMetrics> {
+        self.endpoints
+            .iter()
+            .find(|e| e.endpoint == endpoint && e.method == method)
+    }
+
+    /// Total number of request attempts.
+    pub fn requests(&self) -> u64 {
+        self.endpoints.iter().map(|e| e.requests).sum()
+    }
+}
+
+/// [`MetricsObserver`] which aggregates the metrics in memory.
+///
+/// Clones share the same metrics, so a clone can be registered on the client builder while
+/// the original is used to take snapshots.
+///
+/// ```
+/// use proton_api_rs::http::{ClientBuilder, InMemoryMetrics};
+///
+/// let metrics = InMemoryMetrics::new();
+/// let builder = ClientBuilder::new().metrics_observer(metrics.clone());
+/// assert_eq!(metrics.snapshot().requests(), 0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct InMemoryMetrics {
+    bounds: Arc<Vec<Duration>>,
+    endpoints: Arc<Mutex<HashMap<(String, Method), EndpointMetrics>>>,
+}
+
+impl Default for InMemoryMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InMemoryMetrics {
+    /// Create an aggregator with duration buckets from 5ms to 10s.
+    pub fn new() -> Self {
+        let bounds = DEFAULT_BUCKETS_MS
+            .iter()
+            .map(|ms| Duration::from_millis(*ms))
+            .collect::<Vec<_>>();
+        Self::with_buckets(&bounds)
+    }
+
+    /// Create an aggregator with the given upper bounds for the duration histograms.
+    pub fn with_buckets(bounds: &[Duration]) -> Self {
+        let mut bounds = bounds.to_vec();
+        bounds.sort();
+        bounds.dedup();
+        Self {
+            bounds: Arc::new(bounds),
+            endpoints: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let mut endpoints = self.endpoints.lock().values().cloned().collect::<Vec<_>>();
+        endpoints.sort_by(|a, b| (&a.endpoint, a.method as u8).cmp(&(&b.endpoint, b.method as u8)));
+        MetricsSnapshot { endpoints }
+    }
+
+    /// Clear all the metrics.
+    pub fn reset(&self) {
+        self.endpoints.lock().clear();
+    }
+}
+
+impl MetricsObserver for InMemoryMetrics {
+    fn observe(&self, metrics: &RequestMetrics) {
+        let mut endpoints = self.endpoints.lock();
+        let entry = endpoints
+            .entry((metrics.endpoint.clone(), metrics.method))
+            .or_insert_with(|| EndpointMetrics {
+                endpoint: metrics.endpoint.clone(),
+                method: metrics.method,
+                requests: 0,
+                statuses: BTreeMap::new(),
+                api_codes: BTreeMap::new(),
+                errors: BTreeMap::new(),
+                request_bytes: 0,
+                response_bytes: 0,
+                duration: Histogram::new(&self.bounds),
+            });
+
+        entry.requests += 1;
+        if let Some(status) = metrics.status {
+            *entry.statuses.entry(status).or_default() += 1;
+        }
+        if let Some(code) = metrics.api_code {
+            *entry.api_codes.entry(code).or_default() += 1;
+        }
+        if let Some(error) = metrics.error {
+            *entry.errors.entry(error).or_default() += 1;
+        }
+        entry.request_bytes += metrics.request_bytes;
+        entry.response_bytes += metrics.response_bytes;
+        entry.duration.observe(metrics.duration);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics(endpoint: &str, status: Option<u16>, duration_ms: u64) -> RequestMetrics {
+        RequestMetrics {
+            endpoint: endpoint.to_string(),
+            method: Method::Get,
+            attempt: 1,
+            status,
+            api_code: None,
+            error: None,
+            request_bytes: 1,
+            response_bytes: 10,
+            duration: Duration::from_millis(duration_ms),
+        }
+    }
+
+    #[test]
+    fn test_in_memory_metrics() {
+        let metrics_store =
+            InMemoryMetrics::with_buckets(&[Duration::from_millis(100), Duration::from_millis(10)]);
+        let observer = metrics_store.clone();
+        observer.observe(&metrics("core/v4/users", Some(200), 5));
+        observer.observe(&metrics("core/v4/users", Some(200), 50));
+        observer.observe(&RequestMetrics {
+            api_code: Some(2001),
+            error: Some("api"),
+            ..metrics("core/v4/users", Some(422), 500)
+        });
+        observer.observe(&metrics("auth/v4", None, 1));
+
+        let snapshot = metrics_store.snapshot();
+        assert_eq!(snapshot.requests(), 4);
+        assert_eq!(snapshot.endpoints[0].endpoint, "auth/v4");
+
+        let users = snapshot.endpoint("core/v4/users", Method::Get).unwrap();
+        assert_eq!(users.requests, 3);
+        assert_eq!(users.statuses, BTreeMap::from([(200, 2), (422, 1)]));
+        assert_eq!(users.api_codes, BTreeMap::from([(2001, 1)]));
+        assert_eq!(users.errors, BTreeMap::from([("api", 1)]));
+        assert_eq!(users.request_bytes, 3);
+        assert_eq!(users.response_bytes, 30);
+        assert_eq!(
+            users.duration.buckets,
+            vec![
+                (Duration::from_millis(10), 1),
+                (Duration::from_millis(100), 2)
+            ]
+        );
+        assert_eq!(users.duration.count, 3);
+        assert_eq!(users.duration.sum, Duration::from_millis(555));
+
+        metrics_store.reset();
+        assert_eq!(metrics_store.snapshot().requests(), 0);
+    }
+}
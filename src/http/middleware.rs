@@ -0,0 +1,153 @@
+use crate::http::{Redactor, RequestData, ResponseMeta, Result, X_PM_APP_VERSION_HEADER};
+use log::debug;
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+/// Hook which is executed by the http clients for every request they send.
+///
+/// `on_request` is called with the [`RequestData`] right before it is turned into a request of
+/// the http backend, and can modify it. `on_response` is called with the status, headers and
+/// body of the response before it is handed to the [`FromResponse`](crate::http::FromResponse)
+/// implementation. Requests which are retried invoke the middlewares once per attempt.
+/// Successful responses which are streamed are passed with an empty body.
+///
+/// Returning an error from either method aborts the request with that error.
+pub trait Middleware: Send + Sync {
+    fn on_request(&self, request: &mut RequestData) -> Result<()> {
+        let _ = request;
+        Ok(())
+    }
+
+    fn on_response(
+        &self,
+        request: &RequestData,
+        response: &ResponseMeta,
+        body: &[u8],
+    ) -> Result<()> {
+        let _ = (request, response, body);
+        Ok(())
+    }
+}
+
+/// Ordered list of middlewares. Requests traverse the list front to back and responses back to
+/// front.
+#[derive(Clone, Default)]
+pub(crate) struct MiddlewareChain(Vec<Arc<dyn Middleware>>);
+
+impl MiddlewareChain {
+    pub(super) fn push(&mut self, middleware: Arc<dyn Middleware>) {
+        self.0.push(middleware);
+    }
+
+    pub(super) fn extend(&mut self, other: &MiddlewareChain) {
+        self.0.extend(other.0.iter().cloned());
+    }
+
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn on_request(&self, request: &mut RequestData) -> Result<()> {
+        for m in &self.0 {
+            m.on_request(request)?;
+        }
+        Ok(())
+    }
+
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn on_response(
+        &self,
+        request: &RequestData,
+        response: &ResponseMeta,
+        body: &[u8],
+    ) -> Result<()> {
+        for m in self.0.iter().rev() {
+            m.on_response(request, response, body)?;
+        }
+        Ok(())
+    }
+}
+
+impl Debug for MiddlewareChain {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MiddlewareChain({} middlewares)", self.0.len())
+    }
+}
+
+/// Sets the `X-Pm-Appversion` header on every request.
+#[derive(Debug, Clone)]
+pub struct AppVersionMiddleware(pub String);
+
+impl Middleware for AppVersionMiddleware {
+    fn on_request(&self, request: &mut RequestData) -> Result<()> {
+        request
+            .headers_mut()
+            .insert(X_PM_APP_VERSION_HEADER.to_string(), self.0.clone());
+        Ok(())
+    }
+}
+
+/// Sets headers on every request which does not set them itself.
+#[derive(Debug, Clone)]
+pub(crate) struct DefaultHeadersMiddleware(pub(crate) Vec<(String, String)>);
+
+impl Middleware for DefaultHeadersMiddleware {
+    fn on_request(&self, request: &mut RequestData) -> Result<()> {
+        for (key, value) in &self.0 {
+            if !request
+                .headers()
+                .keys()
+                .any(|k| k.eq_ignore_ascii_case(key))
+            {
+                request.headers_mut().insert(key.clone(), value.clone());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Logs every request and response with `log::debug!`, masking secrets with its
+/// [`Redactor`].
+#[derive(Debug, Clone, Default)]
+pub struct DebugMiddleware {
+    redactor: Redactor,
+}
+
+impl DebugMiddleware {
+    pub fn new(redactor: Redactor) -> Self {
+        Self { redactor }
+    }
+}
+
+impl Middleware for DebugMiddleware {
+    fn on_request(&self, request: &mut RequestData) -> Result<()> {
+        debug!(
+            "Request: {:?} {} Headers: {} Body: {}",
+            request.method(),
+            self.redactor.redact_url(request.url()),
+            self.redactor.redact_headers(
+                request
+                    .headers()
+                    .iter()
+                    .map(|(k, v)| (k.as_str(), v.as_str()))
+            ),
+            self.redactor
+                .redact_body(request.body().map(|b| b.as_ref()).unwrap_or_default())
+        );
+        Ok(())
+    }
+
+    fn on_response(
+        &self,
+        request: &RequestData,
+        response: &ResponseMeta,
+        body: &[u8],
+    ) -> Result<()> {
+        debug!(
+            "Response: {:?} {} status={} Headers: {} Body: {}",
+            request.method(),
+            self.redactor.redact_url(request.url()),
+            response.status(),
+            self.redactor.redact_headers(response.headers()),
+            self.redactor.redact_body(body)
+        );
+        Ok(())
+    }
+}
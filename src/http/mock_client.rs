@@ -0,0 +1,413 @@
+//! In-memory HTTP client implementation for tests.
+//!
+//! [`MockClient`] never touches the network. Every request is matched against the registered
+//! [`MockMatcher`]s in registration order and answered with the scripted [`MockResponse`].
+//! All the requests are recorded and can be inspected with [`MockClient::requests`].
+//!
+//! ```
+//! use proton_api_rs::http::mock_client::{MockClient, MockMatcher, MockResponse};
+//! use proton_api_rs::http::{Method, OwnedRequest, RequestData, Sequence, StringResponse};
+//!
+//! let client = MockClient::new();
+//! client.mock(
+//!     MockMatcher::new(Method::Get, "tests/ping"),
+//!     MockResponse::new(200).body("pong"),
+//! );
+//!
+//! let request = OwnedRequest::<StringResponse>::new(RequestData::new(Method::Get, "tests/ping"));
+//! assert_eq!(request.do_sync(&client).unwrap(), "pong");
+//! assert_eq!(client.requests().len(), 1);
+//! ```
+
+use crate::http::{
+    split_query, BufferedResponse, ClientAsync, ClientBuilder, ClientRequest, ClientRequestBuilder,
+    ClientSync, Error, FromResponse, Method, MiddlewareChain, RequestBudget, RequestData,
+    ResponseMeta, ServerClock,
+};
+use bytes::Bytes;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::fmt::{Debug, Formatter};
+#[cfg(not(feature = "async-traits"))]
+use std::future::Future;
+#[cfg(not(feature = "async-traits"))]
+use std::pin::Pin;
+use std::sync::Arc;
+
+type BodyPredicate = Box<dyn Fn(&[u8]) -> bool + Send + Sync>;
+
+/// Describes which requests a mock applies to.
+pub struct MockMatcher {
+    method: Method,
+    path: String,
+    query: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    body: Option<BodyPredicate>,
+}
+
+impl MockMatcher {
+    /// Match requests with `method` whose url, without the query string, is equal to `path`.
+    pub fn new(method: Method, path: impl Into<String>) -> Self {
+        Self {
+            method,
+            path: path.into(),
+            query: vec![],
+            headers: vec![],
+            body: None,
+        }
+    }
+
+    /// Require the query parameter `key` to be present with `value`. The query of the request
+    /// is decoded before matching, so `key` and `value` must not be percent-encoded.
+    pub fn query(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.query.push((key.into(), value.into()));
+        self
+    }
+
+    /// Require the header `key` to be present with `value`.
+    pub fn header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((key.into(), value.into()));
+        self
+    }
+
+    /// Require the request body to satisfy `predicate`.
+    pub fn body(mut self, predicate: impl Fn(&[u8]) -> bool + Send + Sync + 'static) -> Self {
+        self.body = Some(Box::new(predicate));
+        self
+    }
+
+    /// Require the request body to be JSON which satisfies `predicate`.
+    pub fn json_body(
+        self,
+        predicate: impl Fn(&serde_json::Value) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.body(move |body| {
+            serde_json::from_slice::<serde_json::Value>(body)
+                .map(|v| predicate(&v))
+                .unwrap_or(false)
+        })
+    }
+
+    fn matches(&self, request: &RequestData) -> bool {
+        if self.method != request.method {
+            return false;
+        }
+
+        let (path, query) = split_query(&request.url);
+        if self.path.trim_start_matches('/') != path.trim_start_matches('/') {
+            return false;
+        }
+
+        if !self.query.iter().all(|param| query.contains(param)) {
+            return false;
+        }
+
+        if !self.headers.iter().all(|(k, v)| {
+            request
+                .headers
+                .iter()
+                .any(|(rk, rv)| rk.eq_ignore_ascii_case(k) && rv == v)
+        }) {
+            return false;
+        }
+
+        match &self.body {
+            Some(predicate) => predicate(request.body.as_deref().unwrap_or_default()),
+            None => true,
+        }
+    }
+}
+
+/// Scripted response returned by the [`MockClient`].
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Bytes,
+}
+
+impl MockResponse {
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body: Bytes::new(),
+        }
+    }
+
+    /// Respond with `value` serialized as JSON.
+    pub fn json(status: u16, value: impl Serialize) -> Self {
+        let body = serde_json::to_vec(&value).expect("Failed to serialize json");
+        Self::new(status)
+            .header("Content-Type", "application/json")
+            .body(body)
+    }
+
+    pub fn header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Bytes>) -> Self {
+        self.body = body.into();
+        self
+    }
+}
+
+struct Mock {
+    matcher: MockMatcher,
+    response: MockResponse,
+    remaining: Option<usize>,
+}
+
+#[derive(Default)]
+struct MockState {
+    mocks: Vec<Mock>,
+    requests: Vec<RequestData>,
+}
+
+/// HTTP client which serves scripted responses from memory.
+#[derive(Clone)]
+pub struct MockClient {
+    state: Arc<Mutex<MockState>>,
+    middleware: MiddlewareChain,
+    server_clock: ServerClock,
+}
+
+impl Default for MockClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockClient {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(MockState::default())),
+            middleware: MiddlewareChain::default(),
+            server_clock: ServerClock::new(),
+        }
+    }
+
+    /// Answer every request matching `matcher` with `response`.
+    pub fn mock(&self, matcher: MockMatcher, response: MockResponse) {
+        self.add_mock(matcher, response, None);
+    }
+
+    /// Answer only the next request matching `matcher` with `response`. Use this to script
+    /// a series of different responses for the same endpoint.
+    pub fn mock_once(&self, matcher: MockMatcher, response: MockResponse) {
+        self.add_mock(matcher, response, Some(1));
+    }
+
+    /// Get all the requests executed by this client so far.
+    pub fn requests(&self) -> Vec<RequestData> {
+        self.state.lock().requests.clone()
+    }
+
+    /// Remove all registered mocks and recorded requests.
+    pub fn reset(&self) {
+        let mut state = self.state.lock();
+        state.mocks.clear();
+        state.requests.clear();
+    }
+
+    fn add_mock(&self, matcher: MockMatcher, response: MockResponse, remaining: Option<usize>) {
+        self.state.lock().mocks.push(Mock {
+            matcher,
+            response,
+            remaining,
+        });
+    }
+
+    fn execute_mock(&self, request: MockRequest) -> crate::http::Result<BufferedResponse> {
+        let mut request = request.0;
+        RequestBudget::new(&request).check()?;
+        self.middleware.on_request(&mut request)?;
+
+        let response = {
+            let mut state = self.state.lock();
+            state.requests.push(request.clone());
+            let mock = state
+                .mocks
+                .iter_mut()
+                .find(|m| m.remaining != Some(0) && m.matcher.matches(&request));
+
+            let Some(mock) = mock else {
+                return Err(Error::Other(anyhow::anyhow!(
+                    "No mock matches request {:?} {}",
+                    request.method,
+                    request.url
+                )));
+            };
+
+            if let Some(remaining) = &mut mock.remaining {
+                *remaining -= 1;
+            }
+            mock.response.clone()
+        };
+
+        let meta = ResponseMeta::new(response.status, response.headers);
+        self.server_clock.update(&meta);
+        self.middleware
+            .on_response(&request, &meta, &response.body)?;
+        meta.error_for_status(&response.body)?;
+
+        Ok(BufferedResponse(meta, response.body))
+    }
+}
+
+impl Debug for MockClient {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let state = self.state.lock();
+        write!(
+            f,
+            "MockClient(mocks={}, requests={})",
+            state.mocks.len(),
+            state.requests.len()
+        )
+    }
+}
+
+impl TryFrom<ClientBuilder> for MockClient {
+    type Error = anyhow::Error;
+
+    fn try_from(value: ClientBuilder) -> Result<Self, Self::Error> {
+        Ok(Self {
+            state: Arc::new(Mutex::new(MockState::default())),
+            middleware: value.middleware_chain()?,
+            server_clock: value.server_clock.unwrap_or_default(),
+        })
+    }
+}
+
+pub struct MockRequest(RequestData);
+
+impl ClientRequest for MockRequest {
+    fn header(self, key: impl AsRef<str>, value: impl AsRef<str>) -> Self {
+        Self(self.0.header(key.as_ref(), value.as_ref()))
+    }
+}
+
+impl ClientRequestBuilder for MockClient {
+    type Request = MockRequest;
+
+    fn new_request(&self, data: &RequestData) -> Self::Request {
+        MockRequest(data.clone())
+    }
+
+    fn server_clock(&self) -> Option<&ServerClock> {
+        Some(&self.server_clock)
+    }
+}
+
+impl ClientSync for MockClient {
+    fn execute<R: FromResponse>(&self, request: Self::Request) -> crate::http::Result<R::Output> {
+        R::from_response_sync(self.execute_mock(request)?)
+    }
+}
+
+impl ClientAsync for MockClient {
+    #[cfg(not(feature = "async-traits"))]
+    fn execute_async<R: FromResponse>(
+        &self,
+        request: Self::Request,
+    ) -> Pin<Box<dyn Future<Output = crate::http::Result<R::Output>> + Send + '_>> {
+        Box::pin(async move { R::from_response_async(self.execute_mock(request)?).await })
+    }
+
+    #[cfg(feature = "async-traits")]
+    async fn execute_async<R: FromResponse>(
+        &self,
+        request: Self::Request,
+    ) -> crate::http::Result<R::Output> {
+        R::from_response_async(self.execute_mock(request)?).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{JsonResponse, OwnedRequest, Sequence};
+
+    fn labels_request(label_type: u8) -> OwnedRequest<JsonResponse<serde_json::Value>> {
+        OwnedRequest::new(RequestData::new(Method::Get, "core/v4/labels").query("Type", label_type))
+    }
+
+    #[test]
+    fn test_mock_matching_and_recording() {
+        let client = MockClient::new();
+        client.mock(
+            MockMatcher::new(Method::Get, "core/v4/labels").query("Type", "3"),
+            MockResponse::json(200, serde_json::json!({"Labels": []})),
+        );
+
+        let v = labels_request(3).do_sync(&client).unwrap();
+        assert_eq!(v, serde_json::json!({"Labels": []}));
+
+        let err = labels_request(1).do_sync(&client).unwrap_err();
+        assert!(matches!(err, Error::Other(_)));
+
+        let requests = client.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].url(), "core/v4/labels?Type=1");
+    }
+
+    #[test]
+    fn test_mock_matches_decoded_query() {
+        let client = MockClient::new();
+        client.mock(
+            MockMatcher::new(Method::Get, "core/v4/keys").query("Email", "foo+bar@proton.me"),
+            MockResponse::new(200).body("keys"),
+        );
+
+        let request = OwnedRequest::<crate::http::StringResponse>::new(
+            RequestData::new(Method::Get, "core/v4/keys").query("Email", "foo+bar@proton.me"),
+        );
+        assert_eq!(request.do_sync(&client).unwrap(), "keys");
+
+        let recorded = &client.requests()[0];
+        assert_eq!(recorded.url(), "core/v4/keys?Email=foo%2Bbar%40proton.me");
+        assert_eq!(recorded.path(), "core/v4/keys");
+        assert_eq!(
+            recorded.query_params(),
+            vec![("Email".to_string(), "foo+bar@proton.me".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_mock_once_and_body_predicates() {
+        let client = MockClient::new();
+        let matcher =
+            || MockMatcher::new(Method::Post, "auth/v4/info").json_body(|v| v["Username"] == "foo");
+        client.mock_once(
+            matcher(),
+            MockResponse::json(422, serde_json::json!({"Code": 8002})),
+        );
+        client.mock(matcher(), MockResponse::new(200).body("ok"));
+
+        let request = || {
+            OwnedRequest::<crate::http::StringResponse>::new(
+                RequestData::new(Method::Post, "auth/v4/info")
+                    .json(serde_json::json!({"Username": "foo"})),
+            )
+        };
+
+        let err = request().do_sync(&client).unwrap_err();
+        assert!(matches!(err, Error::API(e) if e.http_code == 422 && e.api_code == 8002));
+        assert_eq!(request().do_sync(&client).unwrap(), "ok");
+    }
+
+    #[tokio::test]
+    async fn test_mock_async() {
+        let client = MockClient::new();
+        client.mock(
+            MockMatcher::new(Method::Get, "core/v4/labels"),
+            MockResponse::json(200, serde_json::json!({"Labels": []})),
+        );
+
+        let v = labels_request(1).do_async(&client).await.unwrap();
+        assert_eq!(v, serde_json::json!({"Labels": []}));
+        assert_eq!(client.requests().len(), 1);
+    }
+}
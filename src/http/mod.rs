@@ -1,7 +1,9 @@
 //! Basic HTTP Protocol abstraction for the Proton API.
 
 use anyhow;
+use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
+use std::time::Duration;
 use thiserror::Error;
 
 #[cfg(feature = "http-ureq")]
@@ -10,28 +12,64 @@ pub mod ureq_client;
 #[cfg(feature = "http-reqwest")]
 pub mod reqwest_client;
 
+pub mod cassette;
+pub mod mock_client;
+
+mod alt_routing;
+mod app_version;
+mod cache;
+mod cancel;
 mod client;
+mod clock;
+mod metrics;
+mod middleware;
+mod multipart;
 mod proxy;
+mod query;
+mod rate_limit;
+mod redact;
 mod request;
 mod response;
+mod retry;
 mod sequence;
+mod tls;
+mod trace;
 
+pub use alt_routing::*;
+pub use app_version::*;
+pub use cache::*;
+pub use cancel::*;
 pub use client::*;
+pub use clock::*;
+pub use metrics::*;
+pub use middleware::*;
+pub use multipart::*;
 pub use proxy::*;
+pub(crate) use query::*;
+pub use rate_limit::*;
+pub use redact::*;
 pub use request::*;
 pub use response::*;
+pub use retry::*;
 pub use sequence::*;
+pub use tls::*;
+pub(crate) use trace::*;
 
 pub(crate) const DEFAULT_HOST_URL: &str = "https://mail.proton.me/api";
 pub(crate) const DEFAULT_APP_VERSION: &str = "proton-api-rs";
-#[allow(unused)] // it is used by the http implementations
+pub(crate) const DEFAULT_MAX_BODY_SIZE: usize = 10_000_000;
+pub(crate) const DEFAULT_POOL_MAX_IDLE_PER_HOST: usize = 4;
+pub(crate) const DEFAULT_POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
 pub(crate) const X_PM_APP_VERSION_HEADER: &str = "X-Pm-Appversion";
+pub(crate) const X_PM_LOCALE_HEADER: &str = "X-Pm-Locale";
+pub(crate) const X_PM_TIMEZONE_HEADER: &str = "X-Pm-Timezone";
 pub(crate) const X_PM_UID_HEADER: &str = "X-Pm-Uid";
 pub(crate) const X_PM_HUMAN_VERIFICATION_TOKEN: &str = "X-Pm-Human-Verification-Token";
 pub(crate) const X_PM_HUMAN_VERIFICATION_TOKEN_TYPE: &str = "X-Pm-Human-Verification-Token-Type";
 
 /// HTTP method.
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
 pub enum Method {
     Delete,
     Get,
@@ -40,25 +78,50 @@ pub enum Method {
     Patch,
 }
 
+impl Method {
+    /// Whether repeating a request with this method has the same effect as executing it once.
+    pub fn is_idempotent(&self) -> bool {
+        matches!(self, Method::Delete | Method::Get | Method::Put)
+    }
+}
+
 /// Errors that may occur during an HTTP request, mostly related to network.
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("API Error: {0}")]
-    API(#[from] crate::requests::APIError),
+    API(crate::requests::APIError),
+    #[error("App version is not supported, an update is required: {0}")]
+    UnsupportedAppVersion(crate::requests::APIError),
     #[error("A redirect error occurred at '{0}: {1}")]
     Redirect(String, #[source] anyhow::Error),
     #[error("Connection timed out")]
     Timeout(#[source] anyhow::Error),
+    #[error("Request was cancelled")]
+    Cancelled,
     #[error("Connection error: {0}")]
     Connection(#[source] anyhow::Error),
     #[error("Request/Response body error: {0}")]
     Request(#[source] anyhow::Error),
+    #[error("TLS certificate of '{0}' does not match the pinned public keys")]
+    PinMismatch(String),
+    #[error("Response body exceeds the limit of {0} bytes")]
+    BodyTooLarge(usize),
     #[error("Encoding/Decoding error: {0}")]
     EncodeOrDecode(#[source] anyhow::Error),
     #[error("Unexpected error occurred: {0}")]
     Other(#[source] anyhow::Error),
 }
 
+impl From<crate::requests::APIError> for Error {
+    fn from(value: crate::requests::APIError) -> Self {
+        if value.is_app_version_error() {
+            Self::UnsupportedAppVersion(value)
+        } else {
+            Self::API(value)
+        }
+    }
+}
+
 impl From<serde_json::Error> for Error {
     fn from(value: serde_json::Error) -> Self {
         Self::EncodeOrDecode(value.into())
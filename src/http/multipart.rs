@@ -0,0 +1,249 @@
+use crate::http::{BodyReader, Error, Result};
+use bytes::Bytes;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::fmt::{Debug, Formatter};
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Multipart/form-data request body.
+///
+/// ```
+/// use proton_api_rs::http::{Method, Multipart, Part, RequestData};
+///
+/// let request = RequestData::new(Method::Post, "mail/v4/attachments").multipart(
+///     Multipart::new()
+///         .text("MessageID", "message-id")
+///         .part(
+///             "KeyPackets",
+///             Part::bytes(vec![1, 2, 3]).content_type("application/octet-stream"),
+///         ),
+/// );
+/// assert!(request.multipart_body().is_some());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Multipart {
+    parts: Vec<(String, Part)>,
+}
+
+impl Multipart {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a text field.
+    pub fn text(self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.part(name, Part::text(value))
+    }
+
+    /// Add a part.
+    pub fn part(mut self, name: impl Into<String>, part: Part) -> Self {
+        self.parts.push((name.into(), part));
+        self
+    }
+
+    /// Iterate over the parts and their field names.
+    pub fn parts(&self) -> impl Iterator<Item = (&str, &Part)> {
+        self.parts.iter().map(|(name, part)| (name.as_str(), part))
+    }
+
+    /// Open all the parts and encode them with `boundary` into a single reader.
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn reader(&self, boundary: &str) -> Result<BodyReader> {
+        let mut readers: Vec<BodyReader> = Vec::with_capacity(self.parts.len() * 3 + 1);
+        for (name, part) in &self.parts {
+            let mut header = format!(
+                "--{boundary}\r\nContent-Disposition: form-data; name=\"{}\"",
+                escape_quoted(name)
+            );
+            if let Some(file_name) = &part.file_name {
+                header.push_str(&format!("; filename=\"{}\"", escape_quoted(file_name)));
+            }
+            header.push_str("\r\n");
+            if let Some(content_type) = &part.content_type {
+                header.push_str(&format!("Content-Type: {content_type}\r\n"));
+            }
+            header.push_str("\r\n");
+
+            readers.push(Box::new(std::io::Cursor::new(header)));
+            readers.push(part.open()?);
+            readers.push(Box::new(&b"\r\n"[..]));
+        }
+        readers.push(Box::new(std::io::Cursor::new(format!(
+            "--{boundary}--\r\n"
+        ))));
+
+        Ok(Box::new(ChainReader(readers.into())))
+    }
+}
+
+/// Generate a random multipart boundary.
+#[allow(unused)] // Only used by http implementations.
+pub(crate) fn multipart_boundary() -> String {
+    format!("proton-api-rs-{:016x}", fastrand::u64(..))
+}
+
+/// Single part of a [`Multipart`] body.
+#[derive(Debug, Clone)]
+pub struct Part {
+    body: PartBody,
+    file_name: Option<String>,
+    content_type: Option<String>,
+}
+
+#[derive(Clone)]
+enum PartBody {
+    Bytes(Bytes),
+    Reader(Arc<Mutex<Option<BodyReader>>>),
+    File(PathBuf),
+}
+
+impl Debug for PartBody {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PartBody::Bytes(b) => write!(f, "Bytes({} bytes)", b.len()),
+            PartBody::Reader(_) => write!(f, "Reader"),
+            PartBody::File(path) => write!(f, "File({path:?})"),
+        }
+    }
+}
+
+impl Part {
+    pub fn text(value: impl Into<String>) -> Self {
+        Self::new(PartBody::Bytes(Bytes::from(value.into())))
+    }
+
+    pub fn bytes(bytes: impl Into<Bytes>) -> Self {
+        Self::new(PartBody::Bytes(bytes.into()))
+    }
+
+    /// Stream the part from `reader`. The reader can only be consumed once, so requests with
+    /// such a part fail if they have to be sent again (e.g., on retry or session refresh).
+    pub fn reader(reader: impl Read + Send + 'static) -> Self {
+        Self::new(PartBody::Reader(Arc::new(Mutex::new(Some(Box::new(
+            reader,
+        ))))))
+    }
+
+    /// Stream the part from the file at `path`, which is opened every time the request is sent.
+    /// The file name defaults to the name of the file.
+    pub fn file(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string());
+        Self {
+            file_name,
+            ..Self::new(PartBody::File(path))
+        }
+    }
+
+    pub fn file_name(mut self, file_name: impl Into<String>) -> Self {
+        self.file_name = Some(file_name.into());
+        self
+    }
+
+    pub fn content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    pub fn get_file_name(&self) -> Option<&str> {
+        self.file_name.as_deref()
+    }
+
+    pub fn get_content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+
+    fn new(body: PartBody) -> Self {
+        Self {
+            body,
+            file_name: None,
+            content_type: None,
+        }
+    }
+
+    /// Get the part's body if it is already in memory.
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn in_memory(&self) -> Option<Bytes> {
+        match &self.body {
+            PartBody::Bytes(b) => Some(b.clone()),
+            _ => None,
+        }
+    }
+
+    /// Open the part's body for reading.
+    pub(crate) fn open(&self) -> Result<BodyReader> {
+        match &self.body {
+            PartBody::Bytes(b) => Ok(Box::new(std::io::Cursor::new(b.clone()))),
+            PartBody::Reader(r) => r.lock().take().ok_or_else(|| {
+                Error::Request(anyhow::anyhow!(
+                    "Multipart reader part can only be sent once"
+                ))
+            }),
+            PartBody::File(path) => match std::fs::File::open(path) {
+                Ok(f) => Ok(Box::new(f)),
+                Err(e) => Err(Error::Request(anyhow::anyhow!(
+                    "Failed to open multipart file {path:?}: {e}"
+                ))),
+            },
+        }
+    }
+}
+
+fn escape_quoted(value: &str) -> String {
+    value
+        .replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Reads from each reader in turn until all of them are exhausted.
+struct ChainReader(VecDeque<BodyReader>);
+
+impl Read for ChainReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while let Some(reader) = self.0.front_mut() {
+            let n = reader.read(buf)?;
+            if n != 0 || buf.is_empty() {
+                return Ok(n);
+            }
+            self.0.pop_front();
+        }
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multipart_encoding() {
+        let multipart = Multipart::new().text("Name", "value").part(
+            "File",
+            Part::reader(&b"data"[..])
+                .file_name("a\"b.txt")
+                .content_type("text/plain"),
+        );
+
+        let mut body = String::new();
+        multipart
+            .reader("XX")
+            .unwrap()
+            .read_to_string(&mut body)
+            .unwrap();
+
+        assert_eq!(
+            body,
+            "--XX\r\nContent-Disposition: form-data; name=\"Name\"\r\n\r\nvalue\r\n\
+             --XX\r\nContent-Disposition: form-data; name=\"File\"; filename=\"a%22b.txt\"\r\n\
+             Content-Type: text/plain\r\n\r\ndata\r\n--XX--\r\n"
+        );
+
+        // Reader parts can't be sent twice.
+        assert!(multipart.reader("XX").is_err());
+    }
+}
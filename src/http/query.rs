@@ -0,0 +1,116 @@
+//! Percent-encoding of url query strings.
+
+use std::fmt::Write;
+
+/// Percent-encode `value` for use as a query key or value. Only the unreserved characters of
+/// RFC 3986 are kept as is, so `+`, `&`, `=` and `@` are always encoded.
+pub(crate) fn encode_component(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => {
+                let _ = write!(encoded, "%{byte:02X}");
+            }
+        }
+    }
+    encoded
+}
+
+/// Decode a percent-encoded query key or value. `+` is decoded as a space, as servers do.
+/// Invalid escapes are kept as is.
+pub(crate) fn decode_component(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if bytes.get(i + 1..i + 3).is_some_and(is_hex_pair) => {
+                decoded.push(hex_value(bytes[i + 1]) << 4 | hex_value(bytes[i + 2]));
+                i += 2;
+            }
+            b => decoded.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn is_hex_pair(pair: &[u8]) -> bool {
+    pair.iter().all(u8::is_ascii_hexdigit)
+}
+
+fn hex_value(digit: u8) -> u8 {
+    match digit {
+        b'0'..=b'9' => digit - b'0',
+        b'a'..=b'f' => digit - b'a' + 10,
+        _ => digit - b'A' + 10,
+    }
+}
+
+/// Split `url` into its path and its decoded query parameters, in order. Parameters without
+/// a value have an empty value.
+pub(crate) fn split_query(url: &str) -> (&str, Vec<(String, String)>) {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let params = query
+        .split('&')
+        .filter(|kv| !kv.is_empty())
+        .map(|kv| {
+            let (k, v) = kv.split_once('=').unwrap_or((kv, ""));
+            (decode_component(k), decode_component(v))
+        })
+        .collect();
+    (path, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{Method, RequestData};
+
+    #[test]
+    fn test_encode_decode() {
+        assert_eq!(encode_component("Label-1_a.b~"), "Label-1_a.b~");
+        assert_eq!(
+            encode_component("user+alias@proton.me"),
+            "user%2Balias%40proton.me"
+        );
+        assert_eq!(encode_component("a b&c=d/é"), "a%20b%26c%3Dd%2F%C3%A9");
+
+        assert_eq!(
+            decode_component("user%2Balias%40proton.me"),
+            "user+alias@proton.me"
+        );
+        assert_eq!(decode_component("a+b%20c"), "a b c");
+        assert_eq!(decode_component("%C3%A9%zz%4"), "é%zz%4");
+    }
+
+    #[test]
+    fn test_request_query() {
+        let data = RequestData::new(Method::Get, "mail/v4/messages")
+            .query("ID[]", "a")
+            .query("ID[]", "b")
+            .query_opt("Page", Some(2))
+            .query_opt("EndID", None::<&str>);
+        assert_eq!(data.url(), "mail/v4/messages?ID%5B%5D=a&ID%5B%5D=b&Page=2");
+    }
+
+    #[test]
+    fn test_split_query() {
+        assert_eq!(split_query("core/v4/users"), ("core/v4/users", vec![]));
+        assert_eq!(
+            split_query("mail/v4/messages?ID=a&ID=b%3D%3D&Desc"),
+            (
+                "mail/v4/messages",
+                vec![
+                    ("ID".to_string(), "a".to_string()),
+                    ("ID".to_string(), "b==".to_string()),
+                    ("Desc".to_string(), String::new()),
+                ]
+            )
+        );
+    }
+}
@@ -0,0 +1,319 @@
+use crate::http::RequestData;
+use parking_lot::{Condvar, Mutex};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+/// Limits applied to a group of requests.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct RateLimit {
+    rate: Option<(u32, Duration)>,
+    burst: Option<u32>,
+    max_in_flight: Option<usize>,
+}
+
+impl RateLimit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send at most `requests` requests every `period` on average.
+    pub fn rate(mut self, requests: u32, period: Duration) -> Self {
+        self.rate = Some((requests.max(1), period));
+        self
+    }
+
+    /// Number of requests which can be sent without delay after a period of inactivity. The
+    /// default is the number of requests allowed by [`RateLimit::rate`].
+    pub fn burst(mut self, requests: u32) -> Self {
+        self.burst = Some(requests.max(1));
+        self
+    }
+
+    /// Maximum number of requests which are executed at the same time.
+    pub fn max_in_flight(mut self, requests: usize) -> Self {
+        self.max_in_flight = Some(requests.max(1));
+        self
+    }
+}
+
+/// Client-side rate and concurrency limiter.
+///
+/// The global limit applies to all the requests, while endpoint limits only apply to requests
+/// whose url starts with the endpoint prefix (e.g. `mail/v4/messages`). When several prefixes
+/// match, only the longest one applies. Every attempt of a request counts against the limits,
+/// including retries.
+///
+/// Clones share the same limits, so all the clones of an http client are limited together.
+///
+/// ```
+/// use proton_api_rs::http::{ClientBuilder, RateLimit, RateLimiter};
+/// use std::time::Duration;
+///
+/// let builder = ClientBuilder::new().rate_limiter(
+///     RateLimiter::new()
+///         .global(RateLimit::new().rate(50, Duration::from_secs(1)))
+///         .endpoint("mail/v4/messages", RateLimit::new().max_in_flight(4)),
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter {
+    global: Option<Arc<Limiter>>,
+    endpoints: Vec<(String, Arc<Limiter>)>,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit all the requests.
+    pub fn global(mut self, limit: RateLimit) -> Self {
+        self.global = Some(Arc::new(Limiter::new(limit)));
+        self
+    }
+
+    /// Limit the requests whose url starts with `prefix`.
+    pub fn endpoint(mut self, prefix: &str, limit: RateLimit) -> Self {
+        let prefix = prefix.trim_start_matches('/').to_string();
+        self.endpoints.retain(|(p, _)| *p != prefix);
+        self.endpoints.push((prefix, Arc::new(Limiter::new(limit))));
+        self
+    }
+
+    /// Reserve the rate limit tokens for `request` and return how long to wait before sending
+    /// it. The delay must be waited for before acquiring the in-flight permit, so waiting
+    /// requests don't hold a concurrency slot.
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn reserve(&self, request: &RequestData) -> Duration {
+        self.limiters(request)
+            .iter()
+            .filter_map(|l| l.bucket.as_ref())
+            .map(|b| b.reserve())
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Wait until `request` may be sent without exceeding the concurrency limits.
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn acquire_blocking(&self, request: &RequestData) -> RateLimitPermit {
+        let slots = self
+            .limiters(request)
+            .iter()
+            .filter_map(|l| l.in_flight.as_ref())
+            .map(|s| s.acquire_blocking())
+            .collect();
+        RateLimitPermit { _slots: slots }
+    }
+
+    /// Async version of [`acquire_blocking`](Self::acquire_blocking).
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) async fn acquire(&self, request: &RequestData) -> RateLimitPermit {
+        let limiters = self.limiters(request);
+        let mut slots = Vec::with_capacity(limiters.len());
+        for in_flight in limiters.iter().filter_map(|l| l.in_flight.as_ref()) {
+            slots.push(in_flight.acquire().await);
+        }
+        RateLimitPermit { _slots: slots }
+    }
+
+    /// Limiters which apply to `request`, in the order their slots must be acquired.
+    fn limiters(&self, request: &RequestData) -> Vec<Arc<Limiter>> {
+        let url = request.url.trim_start_matches('/');
+        let endpoint = self
+            .endpoints
+            .iter()
+            .filter(|(prefix, _)| url.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, limiter)| limiter.clone());
+
+        endpoint.into_iter().chain(self.global.clone()).collect()
+    }
+}
+
+/// Permission to send a request. The request counts as in flight until the permit is dropped.
+pub(crate) struct RateLimitPermit {
+    _slots: Vec<InFlightSlot>,
+}
+
+#[derive(Debug)]
+struct Limiter {
+    bucket: Option<TokenBucket>,
+    in_flight: Option<Arc<InFlight>>,
+}
+
+impl Limiter {
+    fn new(limit: RateLimit) -> Self {
+        Self {
+            bucket: limit.rate.map(|(requests, period)| {
+                TokenBucket::new(limit.burst.unwrap_or(requests), requests, period)
+            }),
+            in_flight: limit.max_in_flight.map(|max| Arc::new(InFlight::new(max))),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    tokens_per_sec: f64,
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    fn new(capacity: u32, requests: u32, period: Duration) -> Self {
+        Self {
+            capacity: capacity as f64,
+            tokens_per_sec: requests as f64 / period.as_secs_f64().max(f64::EPSILON),
+            state: Mutex::new((capacity as f64, Instant::now())),
+        }
+    }
+
+    /// Take a token and return how long to wait until it becomes available. Tokens are
+    /// reserved in advance, so concurrent callers are delayed one after the other.
+    fn reserve(&self) -> Duration {
+        let mut state = self.state.lock();
+        let (tokens, last) = &mut *state;
+        let now = Instant::now();
+        *tokens = (*tokens + now.duration_since(*last).as_secs_f64() * self.tokens_per_sec)
+            .min(self.capacity)
+            - 1.0;
+        *last = now;
+
+        if *tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-*tokens / self.tokens_per_sec)
+        }
+    }
+}
+
+/// Counting semaphore usable from both sync and async code.
+#[derive(Debug)]
+struct InFlight {
+    max: usize,
+    state: Mutex<InFlightState>,
+    released: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct InFlightState {
+    active: usize,
+    wakers: Vec<Waker>,
+}
+
+impl InFlight {
+    fn new(max: usize) -> Self {
+        Self {
+            max,
+            state: Mutex::new(InFlightState::default()),
+            released: Condvar::new(),
+        }
+    }
+
+    fn acquire_blocking(self: &Arc<Self>) -> InFlightSlot {
+        let mut state = self.state.lock();
+        while state.active >= self.max {
+            self.released.wait(&mut state);
+        }
+        state.active += 1;
+        InFlightSlot(self.clone())
+    }
+
+    fn acquire(self: &Arc<Self>) -> AcquireSlot {
+        AcquireSlot(self.clone())
+    }
+
+    fn release(&self) {
+        let wakers = {
+            let mut state = self.state.lock();
+            state.active -= 1;
+            std::mem::take(&mut state.wakers)
+        };
+        self.released.notify_one();
+        wakers.into_iter().for_each(Waker::wake);
+    }
+}
+
+struct InFlightSlot(Arc<InFlight>);
+
+impl Drop for InFlightSlot {
+    fn drop(&mut self) {
+        self.0.release();
+    }
+}
+
+struct AcquireSlot(Arc<InFlight>);
+
+impl Future for AcquireSlot {
+    type Output = InFlightSlot;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.0.state.lock();
+        if state.active < self.0.max {
+            state.active += 1;
+            return Poll::Ready(InFlightSlot(self.0.clone()));
+        }
+
+        if !state.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+            state.wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::Method;
+
+    #[test]
+    fn test_token_bucket() {
+        let bucket = TokenBucket::new(2, 10, Duration::from_secs(1));
+        assert_eq!(bucket.reserve(), Duration::ZERO);
+        assert_eq!(bucket.reserve(), Duration::ZERO);
+
+        // Every following token is available 100ms after the previous one.
+        let third = bucket.reserve();
+        let fourth = bucket.reserve();
+        assert!(third > Duration::from_millis(90) && third <= Duration::from_millis(100));
+        assert!(fourth > Duration::from_millis(190) && fourth <= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn test_endpoint_prefix() {
+        let limiter = RateLimiter::new()
+            .global(RateLimit::new().max_in_flight(10))
+            .endpoint("mail/v4", RateLimit::new().max_in_flight(1))
+            .endpoint("/mail/v4/messages", RateLimit::new().max_in_flight(2));
+
+        let limiters = limiter.limiters(&RequestData::new(Method::Get, "mail/v4/messages/id"));
+        assert_eq!(limiters.len(), 2);
+        assert_eq!(limiters[0].in_flight.as_ref().unwrap().max, 2);
+        assert_eq!(limiters[1].in_flight.as_ref().unwrap().max, 10);
+
+        let limiters = limiter.limiters(&RequestData::new(Method::Get, "core/v4/users"));
+        assert_eq!(limiters.len(), 1);
+    }
+
+    #[test]
+    fn test_in_flight_blocks_until_released() {
+        let limiter = RateLimiter::new().global(RateLimit::new().max_in_flight(1));
+        let request = RequestData::new(Method::Get, "core/v4/users");
+        let permit = limiter.acquire_blocking(&request);
+
+        let thread_limiter = limiter.clone();
+        let thread_request = request.clone();
+        let waiter = std::thread::spawn(move || {
+            let start = Instant::now();
+            drop(thread_limiter.acquire_blocking(&thread_request));
+            start.elapsed()
+        });
+
+        std::thread::sleep(Duration::from_millis(100));
+        drop(permit);
+        assert!(waiter.join().unwrap() >= Duration::from_millis(90));
+    }
+}
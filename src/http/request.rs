@@ -1,11 +1,16 @@
-use crate::http::{ClientAsync, ClientRequestBuilder, ClientSync, Error, FromResponse, Method};
+use crate::http::{
+    encode_component, split_query, CancellationToken, ClientAsync, ClientRequestBuilder,
+    ClientSync, Error, FromResponse, Method, Multipart,
+};
 use bytes::Bytes;
 use serde::Serialize;
 use std::collections::HashMap;
+use std::fmt::Display;
 use std::future::Future;
 use std::marker::PhantomData;
 #[cfg(not(feature = "async-traits"))]
 use std::pin::Pin;
+use std::time::Duration;
 
 /// HTTP Request representation.
 #[derive(Debug, Clone)]
@@ -13,9 +18,13 @@ pub struct RequestData {
     #[allow(unused)] // Only used by http implementations.
     pub(super) method: Method,
     #[allow(unused)] // Only used by http implementations.
-    pub(super) url: String,
+    pub(crate) url: String,
     pub(super) headers: HashMap<String, String>,
     pub(super) body: Option<Bytes>,
+    pub(super) multipart: Option<Multipart>,
+    pub(super) allow_retry: bool,
+    pub(super) timeout: Option<Duration>,
+    pub(super) cancellation: Option<CancellationToken>,
 }
 
 impl RequestData {
@@ -25,20 +34,107 @@ impl RequestData {
             url: url.into(),
             headers: HashMap::new(),
             body: None,
+            multipart: None,
+            allow_retry: false,
+            timeout: None,
+            cancellation: None,
         }
     }
 
+    pub fn method(&self) -> Method {
+        self.method
+    }
+
+    /// Request url relative to the client's base url.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Request url without the query string.
+    pub fn path(&self) -> &str {
+        self.url.split('?').next().unwrap_or_default()
+    }
+
+    /// Decoded query parameters of the url, in order.
+    pub fn query_params(&self) -> Vec<(String, String)> {
+        split_query(&self.url).1
+    }
+
+    pub fn headers(&self) -> &HashMap<String, String> {
+        &self.headers
+    }
+
+    pub fn headers_mut(&mut self) -> &mut HashMap<String, String> {
+        &mut self.headers
+    }
+
+    pub fn body(&self) -> Option<&Bytes> {
+        self.body.as_ref()
+    }
+
+    pub fn multipart_body(&self) -> Option<&Multipart> {
+        self.multipart.as_ref()
+    }
+
+    pub fn get_timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    pub fn get_cancellation_token(&self) -> Option<&CancellationToken> {
+        self.cancellation.as_ref()
+    }
+
     pub fn header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
         self.headers.insert(key.into(), value.into());
         self
     }
 
+    /// Append the query parameter `key=value` to the url. Both are percent-encoded, and a key
+    /// can be added several times to send a list of values.
+    pub fn query(mut self, key: &str, value: impl Display) -> Self {
+        let separator = if self.url.contains('?') { '&' } else { '?' };
+        self.url.push(separator);
+        self.url.push_str(&encode_component(key));
+        self.url.push('=');
+        self.url.push_str(&encode_component(&value.to_string()));
+        self
+    }
+
+    /// Append the query parameter `key=value` to the url if `value` is set.
+    pub fn query_opt(self, key: &str, value: Option<impl Display>) -> Self {
+        match value {
+            Some(value) => self.query(key, value),
+            None => self,
+        }
+    }
+
     pub fn bearer_token(self, token: impl AsRef<str>) -> Self {
         self.header("authorization", format!("Bearer {}", token.as_ref()))
     }
 
+    /// Allow the client's [`RetryPolicy`](crate::http::RetryPolicy) to retry this request even
+    /// if its method is not idempotent.
+    pub fn allow_retry(mut self) -> Self {
+        self.allow_retry = true;
+        self
+    }
+
+    /// Fail with [`Error::Timeout`] if the request does not complete within `timeout`,
+    /// including retries. This can only be shorter than the client's request timeout.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Abort the request with [`Error::Cancelled`] once `token` is cancelled.
+    pub fn cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
     pub fn bytes(mut self, bytes: impl Into<Bytes>) -> Self {
         self.body = Some(bytes.into());
+        self.multipart = None;
         self
     }
 
@@ -49,12 +145,21 @@ impl RequestData {
 
     pub fn json_bytes(mut self, bytes: impl Into<Bytes>) -> Self {
         self.body = Some(bytes.into());
+        self.multipart = None;
         self.header("Content-Type", "application/json")
     }
+
+    /// Send `multipart` as a multipart/form-data body. The http client sets the Content-Type
+    /// header with the boundary of the encoded body.
+    pub fn multipart(mut self, multipart: Multipart) -> Self {
+        self.body = None;
+        self.multipart = Some(multipart);
+        self
+    }
 }
 
 pub trait RequestDesc {
-    type Output: Sized;
+    type Output: Send;
     type Response: FromResponse<Output = Self::Output>;
 
     fn build(&self) -> RequestData;
@@ -63,7 +168,7 @@ pub trait RequestDesc {
     }
 }
 
-pub struct OwnedRequest<F: FromResponse>(RequestData, PhantomData<F>);
+pub struct OwnedRequest<F: FromResponse>(RequestData, PhantomData<fn() -> F>);
 
 impl<F: FromResponse> OwnedRequest<F> {
     pub fn new(r: RequestData) -> Self {
@@ -71,6 +176,12 @@ impl<F: FromResponse> OwnedRequest<F> {
     }
 }
 
+impl<F: FromResponse> Clone for OwnedRequest<F> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone(), PhantomData)
+    }
+}
+
 impl<R: RequestDesc> From<R> for OwnedRequest<R::Response> {
     fn from(value: R) -> Self {
         Self(value.build(), PhantomData)
@@ -87,7 +198,7 @@ impl<F: FromResponse> Request for OwnedRequest<F> {
 
 #[cfg(not(feature = "async-traits"))]
 type RequestFuture<'a, F> =
-    Pin<Box<dyn Future<Output = Result<<F as FromResponse>::Output, Error>> + 'a>>;
+    Pin<Box<dyn Future<Output = Result<<F as FromResponse>::Output, Error>> + Send + 'a>>;
 
 pub trait Request {
     type Response: FromResponse;
@@ -106,16 +217,15 @@ pub trait Request {
         &'a self,
         client: &'a T,
     ) -> RequestFuture<'a, Self::Response> {
-        let v = self.build(client);
-        Box::pin(async move { client.execute_async::<Self::Response>(v).await })
+        client.execute_async::<Self::Response>(self.build(client))
     }
 
     #[cfg(feature = "async-traits")]
     fn exec_async<'a, T: ClientAsync>(
         &'a self,
         client: &'a T,
-    ) -> impl Future<Output = Result<<Self::Response as FromResponse>::Output, Error>> + 'a {
-        let v = self.build(client);
-        async move { client.execute_async::<Self::Response>(v).await }
+    ) -> impl Future<Output = Result<<Self::Response as FromResponse>::Output, Error>> + Send + 'a
+    {
+        client.execute_async::<Self::Response>(self.build(client))
     }
 }
@@ -1,10 +1,14 @@
 use crate::http::{
-    ClientAsync, ClientBuilder, ClientRequest, ClientRequestBuilder, Error, FromResponse, Method,
-    RequestData, ResponseBodyAsync, X_PM_APP_VERSION_HEADER,
+    pin_mismatch_host, read_chunk, tls_config, AltRouting, BodyReader, BodyStream,
+    BufferedResponse, ClientAsync, ClientBuilder, ClientRequest, ClientRequestBuilder, Error,
+    FromResponse, HttpCache, Method, MetricsHook, MiddlewareChain, Multipart, RateLimiter,
+    Redactor, RequestBudget, RequestData, RequestSpan, ResponseBodyAsync, ResponseMeta,
+    RetryPolicy, ServerClock,
 };
-use crate::requests::APIError;
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
+use futures_util::{Stream, StreamExt};
 use reqwest;
+use std::time::{Duration, Instant};
 
 #[cfg(not(feature = "async-traits"))]
 use std::future::Future;
@@ -15,6 +19,16 @@ use std::pin::Pin;
 pub struct ReqwestClient {
     client: reqwest::Client,
     base_url: String,
+    request_timeout: Option<Duration>,
+    retry_policy: Option<RetryPolicy>,
+    middleware: MiddlewareChain,
+    max_body_size: usize,
+    alt_routing: Option<AltRouting>,
+    rate_limiter: Option<RateLimiter>,
+    metrics: Option<MetricsHook>,
+    server_clock: ServerClock,
+    cache: Option<HttpCache>,
+    redactor: Redactor,
 }
 
 impl TryFrom<ClientBuilder> for ReqwestClient {
@@ -22,15 +36,17 @@ impl TryFrom<ClientBuilder> for ReqwestClient {
 
     fn try_from(value: ClientBuilder) -> Result<Self, Self::Error> {
         use reqwest::tls::Version;
-        let mut header_map = reqwest::header::HeaderMap::new();
-        header_map.insert(
-            X_PM_APP_VERSION_HEADER,
-            reqwest::header::HeaderValue::from_str(&value.app_version)
-                .map_err(|e| anyhow::anyhow!(e))?,
-        );
-
+        let middleware = value.middleware_chain()?;
         let mut builder = reqwest::ClientBuilder::new();
 
+        if let Some(config) = tls_config(&value)? {
+            builder = builder.use_preconfigured_tls(config);
+        }
+
+        for (host, addr) in &value.dns_overrides {
+            builder = builder.resolve(host, *addr);
+        }
+
         if let Some(proxy) = value.proxy_url {
             let proxy = reqwest::Proxy::all(proxy.as_url())?;
             builder = builder.proxy(proxy);
@@ -48,25 +64,40 @@ impl TryFrom<ClientBuilder> for ReqwestClient {
             .min_tls_version(Version::TLS_1_2)
             .https_only(!value.allow_http)
             .cookie_store(true)
-            .user_agent(value.user_agent)
-            .default_headers(header_map);
+            .pool_max_idle_per_host(value.pool_max_idle_per_host)
+            .pool_idle_timeout(value.pool_idle_timeout)
+            .user_agent(value.user_agent);
 
         Ok(Self {
             client: builder.build()?,
             base_url: value.base_url,
+            request_timeout: value.request_timeout,
+            retry_policy: value.retry_policy,
+            middleware,
+            max_body_size: value.max_body_size,
+            alt_routing: value.alt_routing,
+            rate_limiter: value.rate_limiter,
+            metrics: value.metrics,
+            server_clock: value.server_clock.unwrap_or_default(),
+            cache: value.cache,
+            redactor: value.redactor,
         })
     }
 }
 
 impl From<reqwest::Error> for Error {
     fn from(value: reqwest::Error) -> Self {
+        if let Some(host) = pin_mismatch_host(&value) {
+            return Error::PinMismatch(host);
+        }
+
         // Check timeout before all other errors as it can be produced by multiple
         // reqwest error kinds.
         if value.is_timeout() {
             return Error::Timeout(anyhow::Error::new(value));
         }
 
-        if value.is_connect() {
+        if value.is_connect() || is_connection_reset(&value) {
             return Error::Connection(anyhow::Error::new(value));
         }
 
@@ -88,9 +119,27 @@ impl From<reqwest::Error> for Error {
     }
 }
 
-struct ReqwestResponse(reqwest::Response);
+/// Check whether the connection was dropped by the peer while the request was in flight.
+fn is_connection_reset(error: &reqwest::Error) -> bool {
+    let mut source = std::error::Error::source(error);
+    while let Some(e) = source {
+        if let Some(io_err) = e.downcast_ref::<std::io::Error>() {
+            if matches!(
+                io_err.kind(),
+                std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::BrokenPipe
+            ) {
+                return true;
+            }
+        }
+        source = e.source();
+    }
+
+    false
+}
 
-pub struct ReqwestRequest(reqwest::RequestBuilder);
+pub struct ReqwestRequest(RequestData);
 
 impl ClientRequest for ReqwestRequest {
     fn header(self, key: impl AsRef<str>, value: impl AsRef<str>) -> Self {
@@ -98,29 +147,26 @@ impl ClientRequest for ReqwestRequest {
     }
 }
 
-impl ResponseBodyAsync for ReqwestResponse {
-    type Body = Bytes;
+impl ClientRequestBuilder for ReqwestClient {
+    type Request = ReqwestRequest;
 
-    #[cfg(not(feature = "async-traits"))]
-    fn get_body_async(self) -> Pin<Box<dyn Future<Output = crate::http::Result<Self::Body>>>> {
-        Box::pin(async {
-            let bytes = self.0.bytes().await?;
-            Ok(bytes)
-        })
+    fn new_request(&self, data: &RequestData) -> Self::Request {
+        ReqwestRequest(data.clone())
     }
 
-    #[cfg(feature = "async-traits")]
-    async fn get_body_async(self) -> crate::http::Result<Self::Body> {
-        let bytes = self.0.bytes().await?;
-        Ok(bytes)
+    fn server_clock(&self) -> Option<&ServerClock> {
+        Some(&self.server_clock)
     }
 }
 
-impl ClientRequestBuilder for ReqwestClient {
-    type Request = ReqwestRequest;
-
-    fn new_request(&self, data: &RequestData) -> Self::Request {
-        let final_url = format!("{}/{}", self.base_url, data.url);
+impl ReqwestClient {
+    fn build_request(
+        &self,
+        base_url: &str,
+        data: &RequestData,
+        timeout: Option<Duration>,
+    ) -> crate::http::Result<reqwest::RequestBuilder> {
+        let final_url = format!("{}/{}", base_url, data.url);
 
         let mut request = match data.method {
             Method::Delete => self.client.delete(&final_url),
@@ -139,33 +185,307 @@ impl ClientRequestBuilder for ReqwestClient {
             request = request.body(body.clone())
         }
 
-        ReqwestRequest(request)
+        if let Some(multipart) = &data.multipart {
+            request = request.multipart(multipart_form(multipart)?);
+        }
+
+        // The per-request timeout replaces the client's timeout, so keep the shorter one.
+        if let Some(timeout) = timeout {
+            request = request.timeout(self.request_timeout.map_or(timeout, |t| t.min(timeout)));
+        }
+
+        Ok(request)
+    }
+
+    async fn exec_once(
+        &self,
+        base_url: &str,
+        data: &RequestData,
+        streaming: bool,
+        timeout: Option<Duration>,
+    ) -> crate::http::Result<ReqwestBody> {
+        let mut data = data.clone();
+        self.middleware.on_request(&mut data)?;
+
+        let response = self.build_request(base_url, &data, timeout)?.send().await?;
+        let meta = response_meta(&response);
+        self.server_clock.update(&meta);
+        if streaming && meta.status() < 400 {
+            self.middleware.on_response(&data, &meta, &[])?;
+            return Ok(ReqwestBody::Streaming(meta, response));
+        }
+
+        let body = read_body_limited(response, self.max_body_size).await?;
+
+        self.middleware.on_response(&data, &meta, &body)?;
+        meta.error_for_status(&body)?;
+
+        Ok(ReqwestBody::Buffered(meta, body))
+    }
+
+    /// Execute the request, failing over to an alternative host if the current host is
+    /// unreachable.
+    async fn exec_routed(
+        &self,
+        data: &RequestData,
+        streaming: bool,
+        timeout: Option<Duration>,
+    ) -> crate::http::Result<ReqwestBody> {
+        let Some(routing) = &self.alt_routing else {
+            return self
+                .exec_once(&self.base_url, data, streaming, timeout)
+                .await;
+        };
+
+        let base_url = routing.base_url(&self.base_url);
+        let err = match self.exec_once(&base_url, data, streaming, timeout).await {
+            Ok(body) => return Ok(body),
+            Err(e) => e,
+        };
+
+        let candidates = routing
+            .failover_candidates_async(&self.base_url, &base_url, &err)
+            .await;
+        for candidate in candidates {
+            if !self.verify_host(&candidate).await {
+                log::debug!("Alternative host {candidate} failed verification");
+                continue;
+            }
+
+            routing.activate(candidate.clone());
+            if AltRouting::can_resend(data, &err) {
+                return self.exec_once(&candidate, data, streaming, timeout).await;
+            }
+            break;
+        }
+
+        Err(err)
+    }
+
+    async fn verify_host(&self, base_url: &str) -> bool {
+        let response = self
+            .client
+            .get(format!("{base_url}/tests/ping"))
+            .send()
+            .await;
+        matches!(response, Ok(r) if r.status().is_success())
     }
-}
 
-impl ReqwestClient {
     pub async fn direct_exec<R: FromResponse>(
         &self,
         r: ReqwestRequest,
     ) -> crate::http::Result<R::Output> {
-        let response = r.0.send().await?;
+        let mut request = r.0;
+        let cached = match &self.cache {
+            Some(cache) if !R::STREAMING => cache.prepare(&mut request),
+            _ => None,
+        };
+        let budget = RequestBudget::new(&request);
+        let body = budget
+            .run(self.exec_attempts(&request, &budget, R::STREAMING))
+            .await?;
+
+        match body {
+            ReqwestBody::Buffered(mut meta, mut body) => {
+                if let Some(cache) = &self.cache {
+                    cache.complete(&request, cached, &mut meta, &mut body);
+                }
+                R::from_response_async(BufferedResponse(meta, body)).await
+            }
+            ReqwestBody::Streaming(meta, response) => {
+                R::from_response_async(ReqwestStreamedResponse {
+                    meta,
+                    response,
+                    max_body_size: self.max_body_size,
+                    budget,
+                })
+                .await
+            }
+        }
+    }
 
-        let status = response.status().as_u16();
+    /// Execute the request until it succeeds or the retry policy gives up.
+    async fn exec_attempts(
+        &self,
+        request: &RequestData,
+        budget: &RequestBudget,
+        streaming: bool,
+    ) -> crate::http::Result<ReqwestBody> {
+        let mut attempt = 1;
+        loop {
+            let permit = match &self.rate_limiter {
+                Some(limiter) => {
+                    budget.sleep(limiter.reserve(request)).await?;
+                    Some(limiter.acquire(request).await)
+                }
+                None => None,
+            };
+            let timeout = budget.check()?;
+            let span = RequestSpan::new(request, attempt);
+            let start = Instant::now();
+            let result = span
+                .instrument(self.exec_routed(request, streaming, timeout))
+                .await;
+            span.finish(result.as_ref().map(ReqwestBody::status));
+            if let Some(metrics) = &self.metrics {
+                let outcome = result.as_ref().map(ReqwestBody::response);
+                metrics.observe(request, attempt, start.elapsed(), outcome);
+            }
+            drop(permit);
+
+            let err = match result {
+                Ok(r) => return Ok(r),
+                Err(e) => e,
+            };
+
+            let Some(delay) = self
+                .retry_policy
+                .as_ref()
+                .and_then(|p| p.retry_delay(attempt, request, &err))
+            else {
+                return Err(err);
+            };
+
+            log::debug!(
+                "Request {:?} {} failed on attempt {attempt}, retrying in {delay:?}: {err}",
+                request.method,
+                self.redactor.redact_url(&request.url)
+            );
+            budget.check_delay(delay)?;
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
 
-        if status >= 400 {
-            let body = response
-                .bytes()
-                .await
-                .map_err(|_| Error::API(APIError::new(status)))?;
+enum ReqwestBody {
+    Buffered(ResponseMeta, Bytes),
+    Streaming(ResponseMeta, reqwest::Response),
+}
 
-            return Err(Error::API(APIError::with_status_and_body(
-                status,
-                body.as_ref(),
-            )));
+impl ReqwestBody {
+    fn status(&self) -> u16 {
+        self.response().0.status()
+    }
+
+    /// Get the response and the size of its body if it was read into memory.
+    fn response(&self) -> (&ResponseMeta, Option<usize>) {
+        match self {
+            ReqwestBody::Buffered(meta, body) => (meta, Some(body.len())),
+            ReqwestBody::Streaming(meta, _) => (meta, None),
         }
+    }
+}
+
+/// Response whose body has not been read yet.
+struct ReqwestStreamedResponse {
+    meta: ResponseMeta,
+    response: reqwest::Response,
+    max_body_size: usize,
+    budget: RequestBudget,
+}
+
+impl ResponseBodyAsync for ReqwestStreamedResponse {
+    type Body = Bytes;
+
+    fn meta(&self) -> &ResponseMeta {
+        &self.meta
+    }
 
-        R::from_response_async(ReqwestResponse(response)).await
+    #[cfg(not(feature = "async-traits"))]
+    fn get_body_async(
+        self,
+    ) -> Pin<Box<dyn Future<Output = crate::http::Result<Self::Body>> + Send>> {
+        Box::pin(async move {
+            let body = read_body_limited(self.response, self.max_body_size);
+            self.budget.run(body).await
+        })
     }
+
+    #[cfg(feature = "async-traits")]
+    async fn get_body_async(self) -> crate::http::Result<Self::Body> {
+        let body = read_body_limited(self.response, self.max_body_size);
+        self.budget.run(body).await
+    }
+
+    fn into_stream(self) -> crate::http::Result<BodyStream> {
+        Ok(self.budget.stream(Box::pin(
+            self.response
+                .bytes_stream()
+                .map(|chunk| chunk.map_err(Error::from)),
+        )))
+    }
+}
+
+/// Read the whole body into memory, failing with [`Error::BodyTooLarge`] if it is larger than
+/// `limit` bytes.
+async fn read_body_limited(
+    mut response: reqwest::Response,
+    limit: usize,
+) -> crate::http::Result<Bytes> {
+    if response
+        .content_length()
+        .is_some_and(|len| len > limit as u64)
+    {
+        return Err(Error::BodyTooLarge(limit));
+    }
+
+    let mut body = BytesMut::new();
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > limit {
+            return Err(Error::BodyTooLarge(limit));
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok(body.freeze())
+}
+
+fn multipart_form(multipart: &Multipart) -> crate::http::Result<reqwest::multipart::Form> {
+    let mut form = reqwest::multipart::Form::new();
+    for (name, part) in multipart.parts() {
+        let body = match part.in_memory() {
+            Some(bytes) => reqwest::Body::from(bytes),
+            None => reqwest::Body::wrap_stream(reader_stream(part.open()?)),
+        };
+        let mut form_part = reqwest::multipart::Part::stream(body);
+        if let Some(file_name) = part.get_file_name() {
+            form_part = form_part.file_name(file_name.to_string());
+        }
+        if let Some(content_type) = part.get_content_type() {
+            form_part = form_part.mime_str(content_type)?;
+        }
+        form = form.part(name.to_string(), form_part);
+    }
+
+    Ok(form)
+}
+
+/// Stream the contents of a multipart reader. Reads are blocking, so they run on the blocking
+/// thread pool of the runtime.
+fn reader_stream(reader: BodyReader) -> impl Stream<Item = std::io::Result<Bytes>> + Send {
+    futures_util::stream::try_unfold(reader, |mut reader| async move {
+        let (reader, chunk) = tokio::task::spawn_blocking(move || {
+            let chunk = read_chunk(&mut reader);
+            (reader, chunk)
+        })
+        .await
+        .map_err(std::io::Error::other)?;
+
+        chunk
+            .map(|chunk| chunk.map(|chunk| (chunk, reader)))
+            .transpose()
+    })
+}
+
+fn response_meta(response: &reqwest::Response) -> ResponseMeta {
+    let headers = response
+        .headers()
+        .iter()
+        .filter_map(|(k, v)| Some((k.to_string(), v.to_str().ok()?.to_string())))
+        .collect();
+
+    ResponseMeta::new(response.status().as_u16(), headers)
 }
 
 impl ClientAsync for ReqwestClient {
@@ -173,7 +493,7 @@ impl ClientAsync for ReqwestClient {
     fn execute_async<R: FromResponse>(
         &self,
         r: Self::Request,
-    ) -> Pin<Box<dyn Future<Output = crate::http::Result<R::Output>> + '_>> {
+    ) -> Pin<Box<dyn Future<Output = crate::http::Result<R::Output>> + Send + '_>> {
         Box::pin(async move { self.direct_exec::<R>(r).await })
     }
 
@@ -1,10 +1,137 @@
-use crate::http::{FromResponse, ResponseBodyAsync, ResponseBodySync, Result};
+use crate::http::{
+    parse_retry_after, Error, FromResponse, RequestCancelled, ResponseBodyAsync, ResponseBodySync,
+    Result,
+};
+use crate::requests::APIError;
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
 use serde::de::DeserializeOwned;
 #[cfg(not(feature = "async-traits"))]
 use std::future::Future;
+use std::io::{Read, Write};
 use std::marker::PhantomData;
-#[cfg(not(feature = "async-traits"))]
 use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::SystemTime;
+
+/// Blocking reader over a response body.
+pub type BodyReader = Box<dyn Read + Send>;
+
+/// Stream of response body chunks.
+pub type BodyStream = Pin<Box<dyn Stream<Item = Result<Bytes>> + Send>>;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Status and headers of an http response.
+#[derive(Debug, Clone)]
+pub struct ResponseMeta {
+    status: u16,
+    headers: Vec<(String, String)>,
+}
+
+impl ResponseMeta {
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn new(status: u16, headers: Vec<(String, String)>) -> Self {
+        Self { status, headers }
+    }
+
+    /// Http status code of the response.
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Get the first value of the header `name`. Header names are case-insensitive.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Iterate over all the response headers.
+    pub fn headers(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.headers.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Value of the `Date` header, the time at which the server sent the response.
+    pub fn date(&self) -> Option<SystemTime> {
+        self.header("Date")
+            .and_then(|d| httpdate::parse_http_date(d.trim()).ok())
+    }
+
+    /// Convert error status codes into an [`Error::API`].
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn error_for_status(&self, body: &[u8]) -> Result<()> {
+        if self.status < 400 {
+            return Ok(());
+        }
+
+        let mut error = APIError::with_status_and_body(self.status, body);
+        error.retry_after = self
+            .header("Retry-After")
+            .and_then(|v| parse_retry_after(v, self.date()));
+        Err(error.into())
+    }
+}
+
+/// Response body which has already been read into memory.
+#[allow(unused)] // Only used by http implementations.
+pub(crate) struct BufferedResponse(pub(crate) ResponseMeta, pub(crate) Bytes);
+
+impl ResponseBodySync for BufferedResponse {
+    type Body = Bytes;
+
+    fn meta(&self) -> &ResponseMeta {
+        &self.0
+    }
+
+    fn get_body(self) -> Result<Self::Body> {
+        Ok(self.1)
+    }
+
+    fn into_reader(self) -> Result<BodyReader> {
+        Ok(Box::new(std::io::Cursor::new(self.1)))
+    }
+}
+
+impl ResponseBodyAsync for BufferedResponse {
+    type Body = Bytes;
+
+    fn meta(&self) -> &ResponseMeta {
+        &self.0
+    }
+
+    #[cfg(not(feature = "async-traits"))]
+    fn get_body_async(self) -> Pin<Box<dyn Future<Output = Result<Self::Body>> + Send>> {
+        Box::pin(async move { Ok(self.1) })
+    }
+
+    #[cfg(feature = "async-traits")]
+    async fn get_body_async(self) -> Result<Self::Body> {
+        Ok(self.1)
+    }
+
+    fn into_stream(self) -> Result<BodyStream> {
+        Ok(Box::pin(futures_util::stream::iter([Ok(self.1)])))
+    }
+}
+
+/// Read all of `reader` into memory, failing with [`Error::BodyTooLarge`] if it contains more
+/// than `limit` bytes.
+#[allow(unused)] // Only used by http implementations.
+pub(crate) fn read_body_limited(reader: impl Read, limit: usize) -> Result<Vec<u8>> {
+    let mut body = vec![];
+    reader
+        .take(limit as u64 + 1)
+        .read_to_end(&mut body)
+        .map_err(read_error)?;
+
+    if body.len() > limit {
+        return Err(Error::BodyTooLarge(limit));
+    }
+
+    Ok(body)
+}
 
 #[derive(Copy, Clone)]
 pub struct NoResponse {}
@@ -17,14 +144,14 @@ impl FromResponse for NoResponse {
     }
 
     #[cfg(not(feature = "async-traits"))]
-    fn from_response_async<T: ResponseBodyAsync>(
+    fn from_response_async<T: ResponseBodyAsync + Send>(
         _: T,
-    ) -> Pin<Box<dyn Future<Output = Result<Self::Output>>>> {
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Output>> + Send>> {
         Box::pin(async { Ok(()) })
     }
 
     #[cfg(feature = "async-traits")]
-    async fn from_response_async<T: ResponseBodyAsync>(_: T) -> Result<Self::Output> {
+    async fn from_response_async<T: ResponseBodyAsync + Send>(_: T) -> Result<Self::Output> {
         Ok(())
     }
 }
@@ -41,9 +168,9 @@ impl<T: DeserializeOwned> FromResponse for JsonResponse<T> {
     }
 
     #[cfg(not(feature = "async-traits"))]
-    fn from_response_async<R: ResponseBodyAsync + 'static>(
+    fn from_response_async<R: ResponseBodyAsync + Send + 'static>(
         response: R,
-    ) -> Pin<Box<dyn Future<Output = Result<Self::Output>>>> {
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Output>> + Send>> {
         Box::pin(async move {
             let body = response.get_body_async().await?;
             let r = serde_json::from_slice(body.as_ref())?;
@@ -52,7 +179,7 @@ impl<T: DeserializeOwned> FromResponse for JsonResponse<T> {
     }
 
     #[cfg(feature = "async-traits")]
-    async fn from_response_async<R: ResponseBodyAsync + 'static>(
+    async fn from_response_async<R: ResponseBodyAsync + Send + 'static>(
         response: R,
     ) -> Result<Self::Output> {
         let body = response.get_body_async().await?;
@@ -73,9 +200,9 @@ impl FromResponse for StringResponse {
     }
 
     #[cfg(not(feature = "async-traits"))]
-    fn from_response_async<R: ResponseBodyAsync + 'static>(
+    fn from_response_async<R: ResponseBodyAsync + Send + 'static>(
         response: R,
-    ) -> Pin<Box<dyn Future<Output = Result<Self::Output>>>> {
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Output>> + Send>> {
         Box::pin(async move {
             let body = response.get_body_async().await?;
             Ok(String::from_utf8_lossy(body.as_ref()).to_string())
@@ -83,7 +210,7 @@ impl FromResponse for StringResponse {
     }
 
     #[cfg(feature = "async-traits")]
-    async fn from_response_async<R: ResponseBodyAsync + 'static>(
+    async fn from_response_async<R: ResponseBodyAsync + Send + 'static>(
         response: R,
     ) -> Result<Self::Output> {
         let body = response.get_body_async().await?;
@@ -105,9 +232,9 @@ impl FromResponse for BinaryResponse {
     }
 
     #[cfg(not(feature = "async-traits"))]
-    fn from_response_async<R: ResponseBodyAsync + 'static>(
+    fn from_response_async<R: ResponseBodyAsync + Send + 'static>(
         response: R,
-    ) -> Pin<Box<dyn Future<Output = Result<Self::Output>>>> {
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Output>> + Send>> {
         Box::pin(async move {
             let body = response.get_body_async().await?;
             Ok(body.as_ref().to_vec())
@@ -115,10 +242,191 @@ impl FromResponse for BinaryResponse {
     }
 
     #[cfg(feature = "async-traits")]
-    async fn from_response_async<R: ResponseBodyAsync + 'static>(
+    async fn from_response_async<R: ResponseBodyAsync + Send + 'static>(
         response: R,
     ) -> Result<Self::Output> {
         let body = response.get_body_async().await?;
         Ok(body.as_ref().to_vec())
     }
 }
+
+/// Response type which also returns the status and headers of the response, e.g.
+/// `WithMeta<JsonResponse<T>>`.
+pub struct WithMeta<R: FromResponse>(PhantomData<R>);
+
+/// Output of a response parsed by `R` together with the response's status and headers.
+#[derive(Debug, Clone)]
+pub struct ResponseWithMeta<T> {
+    pub meta: ResponseMeta,
+    pub value: T,
+}
+
+impl<R: FromResponse> FromResponse for WithMeta<R>
+where
+    R::Output: 'static,
+{
+    type Output = ResponseWithMeta<R::Output>;
+
+    const STREAMING: bool = R::STREAMING;
+
+    fn from_response_sync<T: ResponseBodySync>(response: T) -> Result<Self::Output> {
+        let meta = response.meta().clone();
+        let value = R::from_response_sync(response)?;
+        Ok(ResponseWithMeta { meta, value })
+    }
+
+    #[cfg(not(feature = "async-traits"))]
+    fn from_response_async<T: ResponseBodyAsync + Send + 'static>(
+        response: T,
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Output>> + Send>> {
+        let meta = response.meta().clone();
+        Box::pin(async move {
+            let value = R::from_response_async(response).await?;
+            Ok(ResponseWithMeta { meta, value })
+        })
+    }
+
+    #[cfg(feature = "async-traits")]
+    async fn from_response_async<T: ResponseBodyAsync + Send + 'static>(
+        response: T,
+    ) -> Result<Self::Output> {
+        let meta = response.meta().clone();
+        let value = R::from_response_async(response).await?;
+        Ok(ResponseWithMeta { meta, value })
+    }
+}
+
+/// Response type for bodies which should not be loaded into memory (e.g., large attachments).
+#[derive(Copy, Clone)]
+pub struct StreamingResponse {}
+
+impl FromResponse for StreamingResponse {
+    type Output = ResponseStream;
+
+    const STREAMING: bool = true;
+
+    fn from_response_sync<R: ResponseBodySync>(response: R) -> Result<Self::Output> {
+        Ok(ResponseStream(StreamBody::Reader(response.into_reader()?)))
+    }
+
+    #[cfg(not(feature = "async-traits"))]
+    fn from_response_async<R: ResponseBodyAsync + Send + 'static>(
+        response: R,
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Output>> + Send>> {
+        Box::pin(async move { Ok(ResponseStream(StreamBody::Stream(response.into_stream()?))) })
+    }
+
+    #[cfg(feature = "async-traits")]
+    async fn from_response_async<R: ResponseBodyAsync + Send + 'static>(
+        response: R,
+    ) -> Result<Self::Output> {
+        Ok(ResponseStream(StreamBody::Stream(response.into_stream()?)))
+    }
+}
+
+enum StreamBody {
+    Reader(BodyReader),
+    Stream(BodyStream),
+}
+
+/// Response body which has not been read yet.
+///
+/// Bodies produced by sync clients implement [`Read`], bodies produced by async clients
+/// implement [`Stream`]. Reading the body of an async client with [`Read`] fails, while
+/// streaming the body of a sync client performs blocking reads.
+pub struct ResponseStream(StreamBody);
+
+impl ResponseStream {
+    /// Write the whole body to `writer`. `progress` is called with the total number of bytes
+    /// written so far after every chunk. Returns the size of the body.
+    pub fn copy_to(mut self, mut writer: impl Write, mut progress: impl FnMut(u64)) -> Result<u64> {
+        let mut buffer = vec![0; CHUNK_SIZE];
+        let mut total = 0;
+        loop {
+            let n = match self.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(read_error(e)),
+            };
+
+            writer.write_all(&buffer[..n]).map_err(write_error)?;
+            total += n as u64;
+            progress(total);
+        }
+
+        writer.flush().map_err(write_error)?;
+        Ok(total)
+    }
+
+    /// Async version of [`copy_to`](Self::copy_to).
+    pub async fn copy_to_async(
+        mut self,
+        mut writer: impl Write,
+        mut progress: impl FnMut(u64),
+    ) -> Result<u64> {
+        let mut total = 0;
+        while let Some(chunk) = self.next().await {
+            let chunk = chunk?;
+            writer.write_all(&chunk).map_err(write_error)?;
+            total += chunk.len() as u64;
+            progress(total);
+        }
+
+        writer.flush().map_err(write_error)?;
+        Ok(total)
+    }
+}
+
+impl Read for ResponseStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match &mut self.0 {
+            StreamBody::Reader(r) => r.read(buf),
+            StreamBody::Stream(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "Body of an async response can only be streamed",
+            )),
+        }
+    }
+}
+
+impl Stream for ResponseStream {
+    type Item = Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match &mut self.get_mut().0 {
+            StreamBody::Stream(s) => s.as_mut().poll_next(cx),
+            StreamBody::Reader(r) => Poll::Ready(read_chunk(r).map(|c| c.map_err(read_error))),
+        }
+    }
+}
+
+/// Read the next chunk of at most 64KB from `reader`. Returns `None` at the end of the input.
+pub(crate) fn read_chunk(reader: &mut dyn Read) -> Option<std::io::Result<Bytes>> {
+    let mut buffer = vec![0; CHUNK_SIZE];
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => return None,
+            Ok(n) => {
+                buffer.truncate(n);
+                return Some(Ok(Bytes::from(buffer)));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Some(Err(e)),
+        }
+    }
+}
+
+fn read_error(e: std::io::Error) -> Error {
+    if e.get_ref().is_some_and(|e| e.is::<RequestCancelled>()) {
+        return Error::Cancelled;
+    }
+    if e.kind() == std::io::ErrorKind::TimedOut {
+        return Error::Timeout(e.into());
+    }
+    Error::Request(anyhow::anyhow!("Failed to read response body {e}"))
+}
+
+fn write_error(e: std::io::Error) -> Error {
+    Error::Other(anyhow::anyhow!("Failed to write response body {e}"))
+}
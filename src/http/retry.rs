@@ -0,0 +1,480 @@
+use crate::http::{Error, RequestData};
+use std::future::Future;
+use std::time::{Duration, SystemTime};
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+const DEFAULT_BASE_DELAY: Duration = Duration::from_millis(500);
+const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(30);
+const DEFAULT_RETRY_STATUS: [u16; 3] = [429, 502, 503];
+const TRANSIENT_STATUS: [u16; 3] = [502, 503, 504];
+const RATE_LIMIT_STATUS: u16 = 429;
+
+/// Policy which decides whether a failed request should be executed again by the http client.
+///
+/// Only idempotent methods are retried, unless the request opted in with
+/// [`RequestData::allow_retry`]. Requests are retried on connection errors and on the configured
+/// http status codes (429, 502 and 503 by default). The delay between attempts grows
+/// exponentially from [`RetryPolicy::base_delay`] and is jittered, unless the server provided a
+/// `Retry-After` header, in which case that value is used instead. If the server asks us to wait
+/// longer than [`RetryPolicy::max_delay`] the error is returned to the caller.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    jitter: bool,
+    retry_status: Vec<u16>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            base_delay: DEFAULT_BASE_DELAY,
+            max_delay: DEFAULT_MAX_DELAY,
+            jitter: true,
+            retry_status: DEFAULT_RETRY_STATUS.to_vec(),
+        }
+    }
+
+    /// Maximum number of times a request is executed, including the first attempt.
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Delay before the first retry. Every following retry doubles the delay.
+    pub fn base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+
+    /// Upper bound for the delay between two attempts.
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Disable the random jitter applied to the exponential backoff.
+    pub fn no_jitter(mut self) -> Self {
+        self.jitter = false;
+        self
+    }
+
+    /// Replace the list of http status codes which should be retried.
+    pub fn retry_on_status(mut self, status: &[u16]) -> Self {
+        self.retry_status = status.to_vec();
+        self
+    }
+
+    /// Get the delay to wait before executing `request` again after `attempt` attempts failed
+    /// with `error`. Returns `None` if the request should not be retried.
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn retry_delay(
+        &self,
+        attempt: u32,
+        request: &RequestData,
+        error: &Error,
+    ) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+
+        if !request.method.is_idempotent() && !request.allow_retry {
+            return None;
+        }
+
+        match error {
+            Error::Connection(_) => Some(self.backoff(attempt)),
+            Error::API(e) if self.retry_status.contains(&e.http_code) => match e.retry_after {
+                Some(d) if d > self.max_delay => None,
+                Some(d) => Some(d),
+                None => Some(self.backoff(attempt)),
+            },
+            _ => None,
+        }
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        backoff(self.base_delay, self.max_delay, attempt, self.jitter)
+    }
+}
+
+fn backoff(base_delay: Duration, max_delay: Duration, attempt: u32, jitter: bool) -> Duration {
+    let exp = 2u32.saturating_pow(attempt.saturating_sub(1));
+    let delay = base_delay.saturating_mul(exp).min(max_delay);
+    if !jitter {
+        return delay;
+    }
+
+    // Equal jitter: keep half of the delay and randomize the other half.
+    let half = delay / 2;
+    half + half.mul_f64(fastrand::f64())
+}
+
+/// Policy which decides whether a failed [`Sequence`](crate::http::Sequence) should be run
+/// again, see [`Sequence::retry`](crate::http::Sequence::retry).
+///
+/// Closures taking the number of failed attempts and the error can be used as policies, e.g. to
+/// retry on a specific [`APIError`](crate::requests::APIError) code:
+///
+/// ```
+/// use proton_api_rs::http::{Error, SequenceRetryPolicy};
+/// use std::time::Duration;
+///
+/// let policy = |attempt: u32, error: &Error| match error {
+///     Error::API(e) if e.api_code == 2500 && attempt < 3 => Some(Duration::from_secs(1)),
+///     _ => None,
+/// };
+/// assert_eq!(policy.retry_delay(3, &Error::Cancelled), None);
+/// ```
+pub trait SequenceRetryPolicy<E> {
+    /// Get the delay to wait before running the sequence again after `attempt` attempts failed
+    /// with `error`. Returns `None` if the error should be returned to the caller.
+    fn retry_delay(&self, attempt: u32, error: &E) -> Option<Duration>;
+}
+
+impl<E, F: Fn(u32, &E) -> Option<Duration>> SequenceRetryPolicy<E> for F {
+    fn retry_delay(&self, attempt: u32, error: &E) -> Option<Duration> {
+        self(attempt, error)
+    }
+}
+
+/// Sequence retry policy for transient failures: connection errors, timeouts and the 502, 503
+/// and 504 http status codes. The delay between attempts grows exponentially from
+/// [`TransientErrorPolicy::base_delay`] and is jittered.
+#[derive(Debug, Clone, Copy)]
+pub struct TransientErrorPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+}
+
+impl Default for TransientErrorPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TransientErrorPolicy {
+    pub fn new() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            base_delay: DEFAULT_BASE_DELAY,
+            max_delay: DEFAULT_MAX_DELAY,
+        }
+    }
+
+    /// Maximum number of times the sequence is run, including the first attempt.
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Delay before the first retry. Every following retry doubles the delay.
+    pub fn base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+
+    /// Upper bound for the delay between two attempts.
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+}
+
+impl SequenceRetryPolicy<Error> for TransientErrorPolicy {
+    fn retry_delay(&self, attempt: u32, error: &Error) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+
+        match error {
+            Error::Connection(_) | Error::Timeout(_) => {}
+            Error::API(e) if TRANSIENT_STATUS.contains(&e.http_code) => {}
+            _ => return None,
+        }
+        Some(backoff(self.base_delay, self.max_delay, attempt, true))
+    }
+}
+
+/// Sequence retry policy for rate limited requests, which failed with the 429 http status code.
+/// The sequence is run again once the delay of the `Retry-After` header has passed, or after an
+/// exponential backoff if the server did not provide one. If the server asks us to wait longer
+/// than [`RateLimitPolicy::max_delay`] the error is returned to the caller.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+}
+
+impl Default for RateLimitPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RateLimitPolicy {
+    pub fn new() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            base_delay: DEFAULT_BASE_DELAY,
+            max_delay: DEFAULT_MAX_DELAY,
+        }
+    }
+
+    /// Maximum number of times the sequence is run, including the first attempt.
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Delay before the first retry when the server did not provide a `Retry-After` header.
+    /// Every following retry doubles the delay.
+    pub fn base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+
+    /// Upper bound for the delay between two attempts.
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+}
+
+impl SequenceRetryPolicy<Error> for RateLimitPolicy {
+    fn retry_delay(&self, attempt: u32, error: &Error) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+
+        match error {
+            Error::API(e) if e.http_code == RATE_LIMIT_STATUS => match e.retry_after {
+                Some(d) if d > self.max_delay => None,
+                Some(d) => Some(d),
+                None => Some(backoff(self.base_delay, self.max_delay, attempt, true)),
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Wait for `duration` in between the attempts of async sequences, using the timer of the
+/// tokio runtime.
+#[cfg(feature = "http-reqwest")]
+pub(crate) fn sleep(duration: Duration) -> impl Future<Output = ()> + Send {
+    tokio::time::sleep(duration)
+}
+
+/// Wait for `duration` in between the attempts of async sequences. Without a runtime timer, the
+/// delay is measured on a helper thread, which exits early if the future is dropped. Sync
+/// sequences never use it, they block with `RequestBudget::sleep_blocking` instead.
+#[cfg(not(feature = "http-reqwest"))]
+pub(crate) fn sleep(duration: Duration) -> impl Future<Output = ()> + Send {
+    thread_delay::ThreadDelay::new(duration)
+}
+
+#[cfg(not(feature = "http-reqwest"))]
+mod thread_delay {
+    use parking_lot::{Condvar, Mutex};
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Waker};
+    use std::time::{Duration, Instant};
+
+    pub(super) struct ThreadDelay {
+        duration: Duration,
+        state: Option<Arc<DelayState>>,
+    }
+
+    #[derive(Default)]
+    struct DelayState {
+        state: Mutex<DelayStatus>,
+        dropped: Condvar,
+    }
+
+    #[derive(Default)]
+    struct DelayStatus {
+        elapsed: bool,
+        dropped: bool,
+        /// Waker of the task polling the delay.
+        waker: Option<Waker>,
+    }
+
+    impl ThreadDelay {
+        pub(super) fn new(duration: Duration) -> Self {
+            Self {
+                duration,
+                state: None,
+            }
+        }
+    }
+
+    impl Future for ThreadDelay {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            if self.duration.is_zero() {
+                return Poll::Ready(());
+            }
+
+            let duration = self.duration;
+            let state = self.state.get_or_insert_with(|| {
+                let state = Arc::new(DelayState::default());
+                let timer = state.clone();
+                let deadline = Instant::now() + duration;
+                std::thread::spawn(move || {
+                    let mut state = timer.state.lock();
+                    while !state.dropped {
+                        if timer.dropped.wait_until(&mut state, deadline).timed_out() {
+                            break;
+                        }
+                    }
+                    state.elapsed = true;
+                    let waker = state.waker.take();
+                    drop(state);
+                    if let Some(waker) = waker {
+                        waker.wake();
+                    }
+                });
+                state
+            });
+
+            let mut state = state.state.lock();
+            if state.elapsed {
+                return Poll::Ready(());
+            }
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    impl Drop for ThreadDelay {
+        fn drop(&mut self) {
+            if let Some(state) = &self.state {
+                state.state.lock().dropped = true;
+                state.dropped.notify_one();
+            }
+        }
+    }
+}
+
+/// Parse the value of a `Retry-After` header, which can either be a number of seconds or
+/// an http date. Dates are relative to `server_now`, the `Date` of the response, so that they
+/// are not affected by the skew of the local clock.
+pub(crate) fn parse_retry_after(value: &str, server_now: Option<SystemTime>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(
+        date.duration_since(server_now.unwrap_or_else(SystemTime::now))
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::Method;
+    use crate::requests::APIError;
+
+    fn api_error(status: u16, retry_after: Option<Duration>) -> Error {
+        let mut e = APIError::new(status);
+        e.retry_after = retry_after;
+        Error::API(e)
+    }
+
+    #[test]
+    fn test_retry_delay_respects_method_and_attempts() {
+        let policy = RetryPolicy::new()
+            .max_attempts(3)
+            .base_delay(Duration::from_millis(100))
+            .no_jitter();
+        let get = RequestData::new(Method::Get, "foo");
+        let post = RequestData::new(Method::Post, "foo");
+        let err = api_error(503, None);
+
+        assert_eq!(
+            policy.retry_delay(1, &get, &err),
+            Some(Duration::from_millis(100))
+        );
+        assert_eq!(
+            policy.retry_delay(2, &get, &err),
+            Some(Duration::from_millis(200))
+        );
+        assert_eq!(policy.retry_delay(3, &get, &err), None);
+        assert_eq!(policy.retry_delay(1, &post, &err), None);
+        assert!(policy.retry_delay(1, &post.allow_retry(), &err).is_some());
+        assert_eq!(policy.retry_delay(1, &get, &api_error(400, None)), None);
+    }
+
+    #[test]
+    fn test_retry_delay_uses_retry_after() {
+        let policy = RetryPolicy::new().max_delay(Duration::from_secs(10));
+        let get = RequestData::new(Method::Get, "foo");
+
+        assert_eq!(
+            policy.retry_delay(1, &get, &api_error(429, Some(Duration::from_secs(2)))),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(
+            policy.retry_delay(1, &get, &api_error(429, Some(Duration::from_secs(60)))),
+            None
+        );
+    }
+
+    #[test]
+    fn test_sequence_retry_policies() {
+        let transient = TransientErrorPolicy::new()
+            .max_attempts(3)
+            .base_delay(Duration::from_millis(100));
+        let connection = Error::Connection(anyhow::anyhow!("reset"));
+        let delay = transient.retry_delay(2, &connection).unwrap();
+        assert!(delay >= Duration::from_millis(100) && delay <= Duration::from_millis(200));
+        assert!(transient.retry_delay(1, &api_error(503, None)).is_some());
+        assert_eq!(transient.retry_delay(3, &connection), None);
+        assert_eq!(transient.retry_delay(1, &api_error(429, None)), None);
+        assert_eq!(transient.retry_delay(1, &Error::Cancelled), None);
+
+        let rate_limit = RateLimitPolicy::new().max_delay(Duration::from_secs(10));
+        assert_eq!(
+            rate_limit.retry_delay(1, &api_error(429, Some(Duration::from_secs(2)))),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(
+            rate_limit.retry_delay(1, &api_error(429, Some(Duration::from_secs(60)))),
+            None
+        );
+        assert!(rate_limit.retry_delay(1, &api_error(429, None)).is_some());
+        assert_eq!(rate_limit.retry_delay(1, &api_error(503, None)), None);
+        assert_eq!(rate_limit.retry_delay(1, &connection), None);
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("5", None), Some(Duration::from_secs(5)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT", None),
+            Some(Duration::ZERO)
+        );
+        let server_now = httpdate::parse_http_date("Wed, 21 Oct 2015 07:27:30 GMT").ok();
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT", server_now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(parse_retry_after("soon", None), None);
+    }
+}
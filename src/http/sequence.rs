@@ -1,16 +1,26 @@
-use crate::http::{ClientAsync, ClientSync, Error, FromResponse, Request};
+// The `impl Future` returned by `do_async` mirrors the boxed futures of the default build.
+#![cfg_attr(feature = "async-traits", allow(clippy::manual_async_fn))]
+
+use crate::http::{
+    CancellationToken, ClientAsync, ClientBuilder, ClientRequestBuilder, ClientSync, Error,
+    FromResponse, Request, RequestBudget, RequestData, ResponseStream, SequenceRetryPolicy,
+    ServerClock, StepSpan,
+};
 use std::fmt::Debug;
 use std::future::Future;
-#[cfg(not(feature = "async-traits"))]
+use std::io::Write;
 use std::pin::Pin;
+use std::time::{Duration, Instant};
 
-#[cfg(not(feature = "async-traits"))]
-type SequenceFuture<'a, O, E> = Pin<Box<dyn Future<Output = Result<O, E>> + 'a>>;
+type SequenceFuture<'a, O, E> = Pin<Box<dyn Future<Output = Result<O, E>> + Send + 'a>>;
 
 /// Trait which can be use to link a sequence of request operations.
-pub trait Sequence {
-    type Output;
-    type Error: From<Error> + Debug;
+///
+/// Sequences are `Send`, so that the futures returned by [`Sequence::do_async`] can be spawned on
+/// multi-threaded runtimes.
+pub trait Sequence: Send {
+    type Output: Send;
+    type Error: From<Error> + Debug + Send;
 
     fn do_sync<T: ClientSync>(self, client: &T) -> Result<Self::Output, Self::Error>;
 
@@ -26,7 +36,7 @@ pub trait Sequence {
     fn do_async<'a, T: ClientAsync>(
         self,
         client: &'a T,
-    ) -> impl Future<Output = Result<Self::Output, Self::Error>> + 'a
+    ) -> impl Future<Output = Result<Self::Output, Self::Error>> + Send + 'a
     where
         Self: 'a;
 
@@ -77,9 +87,110 @@ pub trait Sequence {
     {
         SequenceErrChain { s: self, f }
     }
+
+    /// Run the sequence in a `tracing` span named after `step`. Spans are only emitted when the
+    /// `tracing` feature is enabled.
+    fn traced(self, step: &'static str) -> TracedSequence<Self>
+    where
+        Self: Sized,
+    {
+        TracedSequence { s: self, step }
+    }
+
+    /// Fail with [`Error::Timeout`] if the sequence does not complete within `timeout`. Every
+    /// request of the sequence, including the requests of later steps and retries, is given the
+    /// time which is left as its timeout.
+    fn with_deadline(self, timeout: Duration) -> DeadlineSequence<Self>
+    where
+        Self: Sized,
+    {
+        DeadlineSequence {
+            s: self,
+            timeout: Some(timeout),
+            token: None,
+        }
+    }
+
+    /// Abort the requests of the sequence with [`Error::Cancelled`] once `token` is cancelled.
+    /// Requests which already have a cancellation token keep their own.
+    fn with_cancellation(self, token: CancellationToken) -> DeadlineSequence<Self>
+    where
+        Self: Sized,
+    {
+        DeadlineSequence {
+            s: self,
+            timeout: None,
+            token: Some(token),
+        }
+    }
+
+    /// Copy the server time offset of the client into `clock` once the sequence completed.
+    fn sync_clock(self, clock: ServerClock) -> ClockSyncSequence<Self>
+    where
+        Self: Sized,
+    {
+        ClockSyncSequence { s: self, clock }
+    }
+
+    /// Write the streamed response body to `writer` instead of returning it. `progress` is
+    /// called with the number of bytes written so far, and the sequence outputs the size of the
+    /// body.
+    fn write_to<W, P>(self, writer: W, progress: P) -> WriteToSequence<Self, W, P>
+    where
+        Self: Sequence<Output = ResponseStream> + Sized,
+        W: Write,
+        P: FnMut(u64),
+    {
+        WriteToSequence {
+            s: self,
+            writer,
+            progress,
+        }
+    }
+
+    /// Run the sequence together with `other` and output both results. Async clients run both
+    /// sequences concurrently while blocking clients run them one after the other. The first
+    /// error aborts the other sequence, use [`JoinSequence::collect_errors`] to get both
+    /// results instead.
+    fn join<S>(self, other: S) -> JoinSequence<Self, S>
+    where
+        Self: Sized,
+        S: Sequence<Error = Self::Error>,
+    {
+        JoinSequence { a: self, b: other }
+    }
+
+    /// Run the sequence again when it fails with an error for which `policy` returns a delay.
+    /// Every attempt runs a clone of the sequence, see [`retry_with`] for sequences which can
+    /// not be cloned.
+    ///
+    /// When run within [`Sequence::with_deadline`] or [`Sequence::with_cancellation`], the
+    /// sequence fails with [`Error::Timeout`] rather than waiting past the deadline and with
+    /// [`Error::Cancelled`] if it is cancelled while waiting.
+    fn retry<P>(self, policy: P) -> RetrySequence<impl FnMut() -> Self, P>
+    where
+        Self: Clone + Sized,
+        P: SequenceRetryPolicy<Self::Error>,
+    {
+        retry_with(move || self.clone(), policy)
+    }
+
+    /// Erase the type of the sequence so that it can be stored or returned alongside sequences
+    /// of other types, see [`BoxSequence`]. The result is no longer a [`Sequence`], so this
+    /// should be the last combinator applied.
+    fn boxed<'a, C>(self) -> BoxSequence<'a, Self::Output, Self::Error, C>
+    where
+        Self: Sized + 'a,
+    {
+        BoxSequence::new(self)
+    }
 }
 
-impl<R: Request> Sequence for R {
+impl<R> Sequence for R
+where
+    R: Request + Send,
+    <R::Response as FromResponse>::Output: Send,
+{
     type Output = <R::Response as FromResponse>::Output;
     type Error = Error;
 
@@ -91,26 +202,33 @@ impl<R: Request> Sequence for R {
     fn do_async<'a, T: ClientAsync>(
         self,
         client: &'a T,
-    ) -> Pin<Box<dyn Future<Output = Result<Self::Output, Self::Error>> + 'a>>
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Output, Self::Error>> + Send + 'a>>
     where
         Self: 'a,
     {
-        Box::pin(async move { self.exec_async(client).await })
+        Box::pin(async move {
+            let request = self.build(client);
+            client.execute_async::<R::Response>(request).await
+        })
     }
 
     #[cfg(feature = "async-traits")]
     fn do_async<'a, T: ClientAsync>(
         self,
         client: &'a T,
-    ) -> impl Future<Output = Result<<R as Sequence>::Output, <R as Sequence>::Error>> + 'a
+    ) -> impl Future<Output = Result<<R as Sequence>::Output, <R as Sequence>::Error>> + Send + 'a
     where
         R: 'a,
     {
-        async move { self.exec_async(client).await }
+        async move {
+            let request = self.build(client);
+            client.execute_async::<R::Response>(request).await
+        }
     }
 }
 
 #[doc(hidden)]
+#[derive(Clone)]
 pub struct MapSequence<C, F> {
     c: C,
     f: F,
@@ -119,8 +237,9 @@ pub struct MapSequence<C, F> {
 impl<C, O, E, F> Sequence for MapSequence<C, F>
 where
     C: Sequence,
-    F: FnOnce(C::Output) -> Result<O, E>,
-    E: From<Error> + Debug + From<C::Error>,
+    F: FnOnce(C::Output) -> Result<O, E> + Send,
+    O: Send,
+    E: From<Error> + Debug + From<C::Error> + Send,
 {
     type Output = O;
     type Error = E;
@@ -135,7 +254,7 @@ where
     fn do_async<'a, T: ClientAsync>(
         self,
         client: &'a T,
-    ) -> Pin<Box<dyn Future<Output = Result<Self::Output, Self::Error>> + 'a>>
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Output, Self::Error>> + Send + 'a>>
     where
         Self: 'a,
     {
@@ -155,7 +274,8 @@ where
             <MapSequence<C, F> as Sequence>::Output,
             <MapSequence<C, F> as Sequence>::Error,
         >,
-    > + 'a
+    > + Send
+           + 'a
     where
         F: 'a,
         C: 'a,
@@ -169,6 +289,289 @@ where
 }
 
 #[doc(hidden)]
+pub struct WriteToSequence<S, W, P> {
+    s: S,
+    writer: W,
+    progress: P,
+}
+
+impl<S, W, P> Sequence for WriteToSequence<S, W, P>
+where
+    S: Sequence<Output = ResponseStream>,
+    W: Write + Send,
+    P: FnMut(u64) + Send,
+{
+    type Output = u64;
+    type Error = S::Error;
+
+    fn do_sync<T: ClientSync>(self, client: &T) -> Result<Self::Output, Self::Error> {
+        let stream = self.s.do_sync(client)?;
+        Ok(stream.copy_to(self.writer, self.progress)?)
+    }
+
+    #[cfg(not(feature = "async-traits"))]
+    fn do_async<'a, T: ClientAsync>(
+        self,
+        client: &'a T,
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Output, Self::Error>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            let stream = self.s.do_async(client).await?;
+            Ok(stream.copy_to_async(self.writer, self.progress).await?)
+        })
+    }
+
+    #[cfg(feature = "async-traits")]
+    fn do_async<'a, T: ClientAsync>(
+        self,
+        client: &'a T,
+    ) -> impl Future<Output = Result<u64, S::Error>> + Send + 'a
+    where
+        S: 'a,
+        W: 'a,
+        P: 'a,
+    {
+        async move {
+            let stream = self.s.do_async(client).await?;
+            Ok(stream.copy_to_async(self.writer, self.progress).await?)
+        }
+    }
+}
+
+#[doc(hidden)]
+#[derive(Clone)]
+pub struct TracedSequence<S> {
+    s: S,
+    step: &'static str,
+}
+
+impl<S: Sequence> Sequence for TracedSequence<S> {
+    type Output = S::Output;
+    type Error = S::Error;
+
+    fn do_sync<T: ClientSync>(self, client: &T) -> Result<Self::Output, Self::Error> {
+        StepSpan::new(self.step).in_scope(|| self.s.do_sync(client))
+    }
+
+    #[cfg(not(feature = "async-traits"))]
+    fn do_async<'a, T: ClientAsync>(
+        self,
+        client: &'a T,
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Output, Self::Error>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(StepSpan::new(self.step).instrument(self.s.do_async(client)))
+    }
+
+    #[cfg(feature = "async-traits")]
+    fn do_async<'a, T: ClientAsync>(
+        self,
+        client: &'a T,
+    ) -> impl Future<Output = Result<S::Output, S::Error>> + Send + 'a
+    where
+        S: 'a,
+    {
+        StepSpan::new(self.step).instrument(self.s.do_async(client))
+    }
+}
+
+#[doc(hidden)]
+#[derive(Clone)]
+pub struct ClockSyncSequence<S> {
+    s: S,
+    clock: ServerClock,
+}
+
+impl<S: Sequence> Sequence for ClockSyncSequence<S> {
+    type Output = S::Output;
+    type Error = S::Error;
+
+    fn do_sync<T: ClientSync>(self, client: &T) -> Result<Self::Output, Self::Error> {
+        let result = self.s.do_sync(client);
+        sync_clock(&self.clock, client);
+        result
+    }
+
+    #[cfg(not(feature = "async-traits"))]
+    fn do_async<'a, T: ClientAsync>(
+        self,
+        client: &'a T,
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Output, Self::Error>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            let result = self.s.do_async(client).await;
+            sync_clock(&self.clock, client);
+            result
+        })
+    }
+
+    #[cfg(feature = "async-traits")]
+    fn do_async<'a, T: ClientAsync>(
+        self,
+        client: &'a T,
+    ) -> impl Future<Output = Result<S::Output, S::Error>> + Send + 'a
+    where
+        S: 'a,
+    {
+        async move {
+            let result = self.s.do_async(client).await;
+            sync_clock(&self.clock, client);
+            result
+        }
+    }
+}
+
+#[doc(hidden)]
+#[derive(Clone)]
+pub struct DeadlineSequence<S> {
+    s: S,
+    timeout: Option<Duration>,
+    token: Option<CancellationToken>,
+}
+
+impl<S> DeadlineSequence<S> {
+    fn client<T: Clone>(&self, client: &T) -> DeadlineClient<T> {
+        DeadlineClient {
+            client: client.clone(),
+            deadline: self.timeout.map(|t| Instant::now() + t),
+            token: self.token.clone(),
+        }
+    }
+}
+
+impl<S: Sequence> Sequence for DeadlineSequence<S> {
+    type Output = S::Output;
+    type Error = S::Error;
+
+    fn do_sync<T: ClientSync>(self, client: &T) -> Result<Self::Output, Self::Error> {
+        let client = self.client(client);
+        self.s.do_sync(&client)
+    }
+
+    #[cfg(not(feature = "async-traits"))]
+    fn do_async<'a, T: ClientAsync>(
+        self,
+        client: &'a T,
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Output, Self::Error>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        // The deadline starts when the future is first polled, not when it is created.
+        Box::pin(async move {
+            let client = self.client(client);
+            self.s.do_async(&client).await
+        })
+    }
+
+    #[cfg(feature = "async-traits")]
+    fn do_async<'a, T: ClientAsync>(
+        self,
+        client: &'a T,
+    ) -> impl Future<Output = Result<S::Output, S::Error>> + Send + 'a
+    where
+        S: 'a,
+    {
+        // The deadline starts when the future is first polled, not when it is created.
+        async move {
+            let client = self.client(client);
+            self.s.do_async(&client).await
+        }
+    }
+}
+
+/// Client which applies the deadline and cancellation token of a [`DeadlineSequence`] to all
+/// the requests it builds.
+#[derive(Clone)]
+struct DeadlineClient<T> {
+    client: T,
+    deadline: Option<Instant>,
+    token: Option<CancellationToken>,
+}
+
+impl<T: ClientRequestBuilder> ClientRequestBuilder for DeadlineClient<T> {
+    type Request = T::Request;
+
+    fn new_request(&self, data: &RequestData) -> Self::Request {
+        let mut data = data.clone();
+        if let Some(deadline) = self.deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            data.timeout = Some(data.timeout.map_or(remaining, |t| t.min(remaining)));
+        }
+        if data.cancellation.is_none() {
+            data.cancellation = self.token.clone();
+        }
+        self.client.new_request(&data)
+    }
+
+    fn server_clock(&self) -> Option<&ServerClock> {
+        self.client.server_clock()
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        match (self.deadline, self.client.deadline()) {
+            (Some(deadline), Some(inner)) => Some(deadline.min(inner)),
+            (deadline, inner) => deadline.or(inner),
+        }
+    }
+
+    fn cancellation_token(&self) -> Option<&CancellationToken> {
+        self.token
+            .as_ref()
+            .or_else(|| self.client.cancellation_token())
+    }
+}
+
+impl<T: TryFrom<ClientBuilder, Error = anyhow::Error>> TryFrom<ClientBuilder>
+    for DeadlineClient<T>
+{
+    type Error = anyhow::Error;
+
+    fn try_from(value: ClientBuilder) -> Result<Self, Self::Error> {
+        Ok(Self {
+            client: T::try_from(value)?,
+            deadline: None,
+            token: None,
+        })
+    }
+}
+
+impl<T: ClientSync> ClientSync for DeadlineClient<T> {
+    fn execute<R: FromResponse>(&self, request: Self::Request) -> crate::http::Result<R::Output> {
+        self.client.execute::<R>(request)
+    }
+}
+
+impl<T: ClientAsync> ClientAsync for DeadlineClient<T> {
+    #[cfg(not(feature = "async-traits"))]
+    fn execute_async<R: FromResponse>(
+        &self,
+        request: Self::Request,
+    ) -> Pin<Box<dyn Future<Output = crate::http::Result<R::Output>> + Send + '_>> {
+        self.client.execute_async::<R>(request)
+    }
+
+    #[cfg(feature = "async-traits")]
+    fn execute_async<R: FromResponse>(
+        &self,
+        request: Self::Request,
+    ) -> impl Future<Output = crate::http::Result<R::Output>> + Send {
+        self.client.execute_async::<R>(request)
+    }
+}
+
+fn sync_clock<T: ClientRequestBuilder>(clock: &ServerClock, client: &T) {
+    if let Some(client_clock) = client.server_clock() {
+        clock.sync_from(client_clock);
+    }
+}
+
+#[doc(hidden)]
+#[derive(Clone)]
 pub struct MapErrSequence<C, F> {
     c: C,
     f: F,
@@ -177,8 +580,8 @@ pub struct MapErrSequence<C, F> {
 impl<C, E, F> Sequence for MapErrSequence<C, F>
 where
     C: Sequence,
-    F: FnOnce(C::Error) -> Result<C::Output, E>,
-    E: From<Error> + Debug + From<C::Error>,
+    F: FnOnce(C::Error) -> Result<C::Output, E> + Send,
+    E: From<Error> + Debug + From<C::Error> + Send,
 {
     type Output = C::Output;
     type Error = E;
@@ -194,7 +597,7 @@ where
     fn do_async<'a, T: ClientAsync>(
         self,
         client: &'a T,
-    ) -> Pin<Box<dyn Future<Output = Result<Self::Output, Self::Error>> + 'a>>
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Output, Self::Error>> + Send + 'a>>
     where
         Self: 'a,
     {
@@ -215,20 +618,23 @@ where
             <MapErrSequence<C, F> as Sequence>::Output,
             <MapErrSequence<C, F> as Sequence>::Error,
         >,
-    > + 'a
+    > + Send
+           + 'a
     where
         F: 'a,
         C: 'a,
     {
         async move {
-            let v = self.c.do_async(client).await?;
-            let r = (self.f)(v)?;
-            Ok(r)
+            match self.c.do_async(client).await {
+                Ok(o) => Ok(o),
+                Err(e) => (self.f)(e),
+            }
         }
     }
 }
 
 #[doc(hidden)]
+#[derive(Clone)]
 pub struct SequenceWithState<S, F> {
     seq: S,
     f: F,
@@ -239,7 +645,7 @@ where
     S: Sequence,
     SS: Sequence,
     <SS as Sequence>::Error: From<<S as Sequence>::Error> + From<Error> + Debug,
-    F: FnOnce(S::Output) -> SS,
+    F: FnOnce(S::Output) -> SS + Send,
 {
     type Output = SS::Output;
     type Error = SS::Error;
@@ -254,7 +660,7 @@ where
     fn do_async<'a, T: ClientAsync>(
         self,
         client: &'a T,
-    ) -> Pin<Box<dyn Future<Output = Result<Self::Output, Self::Error>> + 'a>>
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Output, Self::Error>> + Send + 'a>>
     where
         Self: 'a,
     {
@@ -274,7 +680,8 @@ where
             <SequenceWithState<S, F> as Sequence>::Output,
             <SequenceWithState<S, F> as Sequence>::Error,
         >,
-    > + 'a
+    > + Send
+           + 'a
     where
         F: 'a,
         S: 'a,
@@ -288,6 +695,7 @@ where
 }
 
 #[doc(hidden)]
+#[derive(Clone)]
 pub struct SequenceFromState<S, F> {
     s: S,
     f: F,
@@ -302,7 +710,8 @@ impl<S, F> SequenceFromState<S, F> {
 impl<Seq, S, F> Sequence for SequenceFromState<S, F>
 where
     Seq: Sequence,
-    F: FnOnce(S) -> Seq,
+    S: Send,
+    F: FnOnce(S) -> Seq + Send,
 {
     type Output = Seq::Output;
     type Error = Seq::Error;
@@ -316,7 +725,7 @@ where
     fn do_async<'a, T: ClientAsync>(
         self,
         client: &'a T,
-    ) -> Pin<Box<dyn Future<Output = Result<Self::Output, Self::Error>> + 'a>>
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Output, Self::Error>> + Send + 'a>>
     where
         Self: 'a,
     {
@@ -335,7 +744,8 @@ where
             <SequenceFromState<S, F> as Sequence>::Output,
             <SequenceFromState<S, F> as Sequence>::Error,
         >,
-    > + 'a
+    > + Send
+           + 'a
     where
         Self: 'a,
     {
@@ -347,6 +757,7 @@ where
 }
 
 #[doc(hidden)]
+#[derive(Clone)]
 pub struct SequenceChain<S, F> {
     s: S,
     f: F,
@@ -356,7 +767,7 @@ impl<SS, S, F> Sequence for SequenceChain<S, F>
 where
     SS: Sequence<Error = S::Error>,
     S: Sequence,
-    F: FnOnce(S::Output) -> Result<SS, S::Error>,
+    F: FnOnce(S::Output) -> Result<SS, S::Error> + Send,
     <SS as Sequence>::Error: From<S::Error> + Debug,
 {
     type Output = SS::Output;
@@ -372,7 +783,7 @@ where
     fn do_async<'a, T: ClientAsync>(
         self,
         client: &'a T,
-    ) -> Pin<Box<dyn Future<Output = Result<Self::Output, Self::Error>> + 'a>>
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Output, Self::Error>> + Send + 'a>>
     where
         Self: 'a,
     {
@@ -392,7 +803,8 @@ where
             <SequenceChain<S, F> as Sequence>::Output,
             <SequenceChain<S, F> as Sequence>::Error,
         >,
-    > + 'a
+    > + Send
+           + 'a
     where
         F: 'a,
         S: 'a,
@@ -406,6 +818,7 @@ where
 }
 
 #[doc(hidden)]
+#[derive(Clone)]
 pub struct SequenceErrChain<S, F> {
     s: S,
     f: F,
@@ -414,7 +827,7 @@ impl<SS, S, F> Sequence for SequenceErrChain<S, F>
 where
     SS: Sequence<Output = S::Output, Error = S::Error>,
     S: Sequence,
-    F: FnOnce(S::Error) -> Result<SS, S::Error>,
+    F: FnOnce(S::Error) -> Result<SS, S::Error> + Send,
     <SS as Sequence>::Error: From<S::Error> + Debug,
 {
     type Output = SS::Output;
@@ -434,7 +847,7 @@ where
     fn do_async<'a, T: ClientAsync>(
         self,
         client: &'a T,
-    ) -> Pin<Box<dyn Future<Output = Result<Self::Output, Self::Error>> + 'a>>
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Output, Self::Error>> + Send + 'a>>
     where
         Self: 'a,
     {
@@ -455,10 +868,11 @@ where
         client: &'a T,
     ) -> impl Future<
         Output = Result<
-            <SequenceChain<S, F> as Sequence>::Output,
-            <SequenceChain<S, F> as Sequence>::Error,
+            <SequenceErrChain<S, F> as Sequence>::Output,
+            <SequenceErrChain<S, F> as Sequence>::Error,
         >,
-    > + 'a
+    > + Send
+           + 'a
     where
         F: 'a,
         S: 'a,
@@ -474,3 +888,446 @@ where
         }
     }
 }
+
+#[doc(hidden)]
+#[derive(Clone)]
+pub struct JoinSequence<A, B> {
+    a: A,
+    b: B,
+}
+
+impl<A, B> JoinSequence<A, B> {
+    /// Wait for both sequences to complete and output their individual results instead of
+    /// failing on the first error.
+    pub fn collect_errors(self) -> JoinSettledSequence<A, B> {
+        JoinSettledSequence {
+            a: self.a,
+            b: self.b,
+        }
+    }
+}
+
+impl<A, B> Sequence for JoinSequence<A, B>
+where
+    A: Sequence,
+    B: Sequence<Error = A::Error>,
+{
+    type Output = (A::Output, B::Output);
+    type Error = A::Error;
+
+    fn do_sync<T: ClientSync>(self, client: &T) -> Result<Self::Output, Self::Error> {
+        let a = self.a.do_sync(client)?;
+        let b = self.b.do_sync(client)?;
+        Ok((a, b))
+    }
+
+    #[cfg(not(feature = "async-traits"))]
+    fn do_async<'a, T: ClientAsync>(
+        self,
+        client: &'a T,
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Output, Self::Error>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            futures_util::future::try_join(self.a.do_async(client), self.b.do_async(client)).await
+        })
+    }
+
+    #[cfg(feature = "async-traits")]
+    fn do_async<'a, T: ClientAsync>(
+        self,
+        client: &'a T,
+    ) -> impl Future<Output = Result<(A::Output, B::Output), A::Error>> + Send + 'a
+    where
+        Self: 'a,
+    {
+        async move {
+            futures_util::future::try_join(self.a.do_async(client), self.b.do_async(client)).await
+        }
+    }
+}
+
+#[doc(hidden)]
+#[derive(Clone)]
+pub struct JoinSettledSequence<A, B> {
+    a: A,
+    b: B,
+}
+
+impl<A, B> Sequence for JoinSettledSequence<A, B>
+where
+    A: Sequence,
+    B: Sequence<Error = A::Error>,
+{
+    type Output = (Result<A::Output, A::Error>, Result<B::Output, B::Error>);
+    type Error = A::Error;
+
+    fn do_sync<T: ClientSync>(self, client: &T) -> Result<Self::Output, Self::Error> {
+        Ok((self.a.do_sync(client), self.b.do_sync(client)))
+    }
+
+    #[cfg(not(feature = "async-traits"))]
+    fn do_async<'a, T: ClientAsync>(
+        self,
+        client: &'a T,
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Output, Self::Error>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            Ok(futures_util::future::join(self.a.do_async(client), self.b.do_async(client)).await)
+        })
+    }
+
+    #[cfg(feature = "async-traits")]
+    fn do_async<'a, T: ClientAsync>(
+        self,
+        client: &'a T,
+    ) -> impl Future<
+        Output = Result<
+            <JoinSettledSequence<A, B> as Sequence>::Output,
+            <JoinSettledSequence<A, B> as Sequence>::Error,
+        >,
+    > + Send
+           + 'a
+    where
+        Self: 'a,
+    {
+        async move {
+            Ok(futures_util::future::join(self.a.do_async(client), self.b.do_async(client)).await)
+        }
+    }
+}
+
+/// Run all `sequences` and output their results in order. Async clients run the sequences
+/// concurrently while blocking clients run them one after the other. The first error aborts
+/// the remaining sequences, use [`JoinAllSequence::collect_errors`] to get every result
+/// instead.
+///
+/// ```no_run
+/// # use proton_api_rs::{domain::MessageId, http, Session};
+/// # use proton_api_rs::http::Sequence;
+/// # async fn messages<T: http::ClientAsync>(session: &Session, client: &T, ids: &[MessageId]) {
+/// let messages = http::join_all(ids.iter().map(|id| session.get_message(id)))
+///     .do_async(client)
+///     .await
+///     .unwrap();
+/// # }
+/// ```
+pub fn join_all<I>(sequences: I) -> JoinAllSequence<I::Item>
+where
+    I: IntoIterator,
+    I::Item: Sequence,
+{
+    JoinAllSequence {
+        sequences: sequences.into_iter().collect(),
+    }
+}
+
+#[doc(hidden)]
+#[derive(Clone)]
+pub struct JoinAllSequence<S> {
+    sequences: Vec<S>,
+}
+
+impl<S> JoinAllSequence<S> {
+    /// Wait for all the sequences to complete and output their individual results instead of
+    /// failing on the first error.
+    pub fn collect_errors(self) -> JoinAllSettledSequence<S> {
+        JoinAllSettledSequence {
+            sequences: self.sequences,
+        }
+    }
+}
+
+impl<S: Sequence> Sequence for JoinAllSequence<S> {
+    type Output = Vec<S::Output>;
+    type Error = S::Error;
+
+    fn do_sync<T: ClientSync>(self, client: &T) -> Result<Self::Output, Self::Error> {
+        self.sequences
+            .into_iter()
+            .map(|s| s.do_sync(client))
+            .collect()
+    }
+
+    #[cfg(not(feature = "async-traits"))]
+    fn do_async<'a, T: ClientAsync>(
+        self,
+        client: &'a T,
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Output, Self::Error>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            let futures = self.sequences.into_iter().map(|s| s.do_async(client));
+            futures_util::future::try_join_all(futures).await
+        })
+    }
+
+    #[cfg(feature = "async-traits")]
+    fn do_async<'a, T: ClientAsync>(
+        self,
+        client: &'a T,
+    ) -> impl Future<Output = Result<Vec<S::Output>, S::Error>> + Send + 'a
+    where
+        Self: 'a,
+    {
+        async move {
+            let futures = self.sequences.into_iter().map(|s| s.do_async(client));
+            futures_util::future::try_join_all(futures).await
+        }
+    }
+}
+
+#[doc(hidden)]
+#[derive(Clone)]
+pub struct JoinAllSettledSequence<S> {
+    sequences: Vec<S>,
+}
+
+impl<S: Sequence> Sequence for JoinAllSettledSequence<S> {
+    type Output = Vec<Result<S::Output, S::Error>>;
+    type Error = S::Error;
+
+    fn do_sync<T: ClientSync>(self, client: &T) -> Result<Self::Output, Self::Error> {
+        Ok(self
+            .sequences
+            .into_iter()
+            .map(|s| s.do_sync(client))
+            .collect())
+    }
+
+    #[cfg(not(feature = "async-traits"))]
+    fn do_async<'a, T: ClientAsync>(
+        self,
+        client: &'a T,
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Output, Self::Error>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            let futures = self.sequences.into_iter().map(|s| s.do_async(client));
+            Ok(futures_util::future::join_all(futures).await)
+        })
+    }
+
+    #[cfg(feature = "async-traits")]
+    fn do_async<'a, T: ClientAsync>(
+        self,
+        client: &'a T,
+    ) -> impl Future<
+        Output = Result<
+            <JoinAllSettledSequence<S> as Sequence>::Output,
+            <JoinAllSettledSequence<S> as Sequence>::Error,
+        >,
+    > + Send
+           + 'a
+    where
+        Self: 'a,
+    {
+        async move {
+            let futures = self.sequences.into_iter().map(|s| s.do_async(client));
+            Ok(futures_util::future::join_all(futures).await)
+        }
+    }
+}
+
+/// Run the sequence built by `factory` and build it again for every retry allowed by `policy`.
+///
+/// ```no_run
+/// # use proton_api_rs::{domain::MessageId, http, Session};
+/// # use proton_api_rs::http::Sequence;
+/// # fn mark_read<T: http::ClientSync>(session: &Session, client: &T, ids: &[MessageId]) {
+/// let policy = http::TransientErrorPolicy::new().max_attempts(5);
+/// http::retry_with(|| session.mark_messages_read(ids), policy)
+///     .do_sync(client)
+///     .unwrap();
+/// # }
+/// ```
+pub fn retry_with<S, F, P>(factory: F, policy: P) -> RetrySequence<F, P>
+where
+    S: Sequence,
+    F: FnMut() -> S,
+    P: SequenceRetryPolicy<S::Error>,
+{
+    RetrySequence { factory, policy }
+}
+
+#[doc(hidden)]
+#[derive(Clone)]
+pub struct RetrySequence<F, P> {
+    factory: F,
+    policy: P,
+}
+
+impl<S, F, P> Sequence for RetrySequence<F, P>
+where
+    S: Sequence,
+    F: FnMut() -> S + Send,
+    P: SequenceRetryPolicy<S::Error> + Send,
+{
+    type Output = S::Output;
+    type Error = S::Error;
+
+    fn do_sync<T: ClientSync>(mut self, client: &T) -> Result<Self::Output, Self::Error> {
+        let budget = RequestBudget::of_client(client);
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match (self.factory)().do_sync(client) {
+                Ok(v) => return Ok(v),
+                Err(e) => match self.policy.retry_delay(attempt, &e) {
+                    Some(delay) => budget.sleep_blocking(delay)?,
+                    None => return Err(e),
+                },
+            }
+        }
+    }
+
+    #[cfg(not(feature = "async-traits"))]
+    fn do_async<'a, T: ClientAsync>(
+        mut self,
+        client: &'a T,
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Output, Self::Error>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            let budget = RequestBudget::of_client(client);
+            let mut attempt = 0;
+            loop {
+                attempt += 1;
+                match (self.factory)().do_async(client).await {
+                    Ok(v) => return Ok(v),
+                    Err(e) => match self.policy.retry_delay(attempt, &e) {
+                        Some(delay) => budget.sleep(delay).await?,
+                        None => return Err(e),
+                    },
+                }
+            }
+        })
+    }
+
+    #[cfg(feature = "async-traits")]
+    fn do_async<'a, T: ClientAsync>(
+        mut self,
+        client: &'a T,
+    ) -> impl Future<Output = Result<S::Output, S::Error>> + Send + 'a
+    where
+        Self: 'a,
+    {
+        async move {
+            let budget = RequestBudget::of_client(client);
+            let mut attempt = 0;
+            loop {
+                attempt += 1;
+                match (self.factory)().do_async(client).await {
+                    Ok(v) => return Ok(v),
+                    Err(e) => match self.policy.retry_delay(attempt, &e) {
+                        Some(delay) => budget.sleep(delay).await?,
+                        None => return Err(e),
+                    },
+                }
+            }
+        }
+    }
+}
+
+/// Sequence whose concrete type is erased, which makes it possible to store sequences of
+/// different types in a collection or to return them from trait methods.
+///
+/// Since [`Sequence`] is generic over the client, the client type `C` is fixed when boxing the
+/// sequence. [`BoxSequence::do_sync`] is available for [`ClientSync`] clients and
+/// [`BoxSequence::do_async`] for [`ClientAsync`] clients.
+///
+/// For the same reason `BoxSequence` does not implement [`Sequence`] itself, which would
+/// require it to run with any client. Combinators such as [`Sequence::map`],
+/// [`Sequence::chain`], [`Sequence::retry`] or [`join_all`] can't be applied to a boxed
+/// sequence, so apply them before boxing.
+///
+/// ```no_run
+/// # use proton_api_rs::{http, Session};
+/// # use proton_api_rs::http::{BoxSequence, Sequence};
+/// # fn run<C: http::ClientSync>(session: &Session, client: &C) {
+/// let commands: Vec<BoxSequence<(), http::Error, C>> = vec![
+///     session.logout().boxed(),
+///     session.get_user().map(|_| Ok(())).boxed(),
+/// ];
+/// for command in commands {
+///     command.do_sync(client).unwrap();
+/// }
+/// # }
+/// ```
+pub struct BoxSequence<'a, O, E, C> {
+    s: Box<dyn DynSequence<'a, O, E, C> + Send + 'a>,
+}
+
+impl<'a, O, E, C> BoxSequence<'a, O, E, C> {
+    pub fn new<S>(sequence: S) -> Self
+    where
+        S: Sequence<Output = O, Error = E> + 'a,
+    {
+        Self {
+            s: Box::new(sequence),
+        }
+    }
+
+    pub fn do_sync(self, client: &C) -> Result<O, E>
+    where
+        C: ClientSync,
+    {
+        self.s.do_sync_dyn(client)
+    }
+
+    pub fn do_async<'c>(self, client: &'c C) -> SequenceFuture<'c, O, E>
+    where
+        C: ClientAsync,
+        'a: 'c,
+    {
+        self.s.do_async_dyn(client)
+    }
+}
+
+/// Object safe version of [`Sequence`] for a fixed client type.
+trait DynSequence<'a, O, E, C> {
+    fn do_sync_dyn(self: Box<Self>, client: &C) -> Result<O, E>
+    where
+        C: ClientSync;
+
+    fn do_async_dyn<'c>(self: Box<Self>, client: &'c C) -> SequenceFuture<'c, O, E>
+    where
+        C: ClientAsync,
+        'a: 'c;
+}
+
+impl<'a, S, C> DynSequence<'a, S::Output, S::Error, C> for S
+where
+    S: Sequence + 'a,
+{
+    fn do_sync_dyn(self: Box<Self>, client: &C) -> Result<S::Output, S::Error>
+    where
+        C: ClientSync,
+    {
+        (*self).do_sync(client)
+    }
+
+    #[cfg(not(feature = "async-traits"))]
+    fn do_async_dyn<'c>(self: Box<Self>, client: &'c C) -> SequenceFuture<'c, S::Output, S::Error>
+    where
+        C: ClientAsync,
+        'a: 'c,
+    {
+        (*self).do_async(client)
+    }
+
+    #[cfg(feature = "async-traits")]
+    fn do_async_dyn<'c>(self: Box<Self>, client: &'c C) -> SequenceFuture<'c, S::Output, S::Error>
+    where
+        C: ClientAsync,
+        'a: 'c,
+    {
+        Box::pin((*self).do_async(client))
+    }
+}
@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+
+/// SPKI pins of the Proton API servers, as published in Proton's open source clients.
+const PROTON_PINS: [&str; 9] = [
+    // api.protonmail.ch
+    "drtmcR2kFkM8qJClsuWgUzxgBkePfRCkRpqUesyDmeE=",
+    "YRGlaY0jyJ4Jw2/4M8FIftwbDIQfh8Sdro96CeEel54=",
+    "AfMENBVvOS8MnISprtvyPsjKlPooqh8nMB/pvCrpJpw=",
+    // protonmail.com
+    "8joiNBdqaYiQpKskgtkJsqRxF7zN0C0aqfi8DacknnI=",
+    "JMI8yrbc6jB1FYGyyWRLFTmDNgIszrNEMGlgy972e7w=",
+    "Iu44zU84EOCZ9vx/vz67/MRVrxF1IO4i4NIa8ETwiIY=",
+    // proton.me
+    "CT56BhOTmj5ZIPgb/xD5mH8rY3BLo/MlhP7oPyJUEDo=",
+    "35Dx28/uzN3LeltkCBQ8RHK0tlNSa2kCpCRGNp34Gxc=",
+    "qYIukVc63DEITct8sFT7ebIq5qsWmuscaIKeJx+5J5A=",
+];
+const PROTON_HOSTS: [&str; 6] = [
+    "proton.me",
+    "*.proton.me",
+    "protonmail.ch",
+    "*.protonmail.ch",
+    "protonmail.com",
+    "*.protonmail.com",
+];
+
+/// Public key pins of TLS servers.
+///
+/// A pin is the base64 encoded SHA-256 digest of a DER encoded SubjectPublicKeyInfo, the same
+/// format used by HTTP Public Key Pinning (`pin-sha256`). Pins are assigned to hosts, either by
+/// exact name (`mail.proton.me`) or to all the subdomains of a domain (`*.proton.me`).
+///
+/// Pinning is an additional check: the certificate chain of a pinned host is first verified
+/// against the trusted roots like any other, then the public key of its end-entity certificate
+/// must match one of the host's pins, otherwise the request fails with
+/// [`Error::PinMismatch`](crate::http::Error::PinMismatch). Hosts without pins are only
+/// verified against the trusted roots.
+///
+/// ```
+/// use proton_api_rs::http::SpkiPins;
+///
+/// let pins = SpkiPins::proton()
+///     .pin("localhost", &["47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="]);
+/// assert!(pins.get("mail.proton.me").is_some());
+/// assert!(pins.get("localhost").is_some());
+/// assert!(pins.get("example.com").is_none());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SpkiPins {
+    hosts: HashMap<String, Vec<String>>,
+}
+
+impl SpkiPins {
+    /// Create an empty pin set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pins of the Proton API servers.
+    pub fn proton() -> Self {
+        PROTON_HOSTS
+            .iter()
+            .fold(Self::new(), |pins, host| pins.pin(host, &PROTON_PINS))
+    }
+
+    /// Add `pins` to `host`. Use `*.domain` to pin all the subdomains of `domain`.
+    pub fn pin(mut self, host: &str, pins: &[&str]) -> Self {
+        self.hosts
+            .entry(host.to_ascii_lowercase())
+            .or_default()
+            .extend(pins.iter().map(|p| p.to_string()));
+        self
+    }
+
+    /// Replace all the pins of `host`.
+    pub fn replace(mut self, host: &str, pins: &[&str]) -> Self {
+        self.hosts.remove(&host.to_ascii_lowercase());
+        self.pin(host, pins)
+    }
+
+    /// Remove all the pins of `host`, which is then verified against the system roots.
+    pub fn remove(mut self, host: &str) -> Self {
+        self.hosts.remove(&host.to_ascii_lowercase());
+        self
+    }
+
+    /// Get the pins which apply to `host`. Exact matches take precedence over the pins of the
+    /// closest parent domain.
+    pub fn get(&self, host: &str) -> Option<&[String]> {
+        let host = host.to_ascii_lowercase();
+        if let Some(pins) = self.hosts.get(&host) {
+            return Some(pins);
+        }
+
+        let mut domain = host.as_str();
+        while let Some((_, parent)) = domain.split_once('.') {
+            if let Some(pins) = self.hosts.get(&format!("*.{parent}")) {
+                return Some(pins);
+            }
+            domain = parent;
+        }
+
+        None
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hosts.is_empty()
+    }
+}
+
+#[cfg(any(feature = "http-ureq", feature = "http-reqwest"))]
+pub use verifier::*;
+
+#[cfg(any(feature = "http-ureq", feature = "http-reqwest"))]
+mod verifier {
+    use super::SpkiPins;
+    use crate::http::ClientBuilder;
+    use base64::Engine;
+    use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+    use rustls::{Certificate, CertificateError, ClientConfig, ServerName};
+    use std::error::Error as StdError;
+    use std::sync::Arc;
+    use std::time::SystemTime;
+
+    /// Compute the pin of the DER encoded X.509 `certificate`. Returns `None` if the certificate
+    /// can't be parsed.
+    pub fn spki_pin(certificate: &[u8]) -> Option<String> {
+        let spki = spki(certificate)?;
+        let digest = ring::digest::digest(&ring::digest::SHA256, spki);
+        Some(base64::engine::general_purpose::STANDARD.encode(digest))
+    }
+
+    /// Build the TLS configuration shared by the http clients. Returns `None` if `builder`
+    /// does not customize certificate verification, so that the clients keep their default.
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn tls_config(builder: &ClientBuilder) -> anyhow::Result<Option<ClientConfig>> {
+        if builder.spki_pins.is_none()
+            && builder.root_certificates.is_empty()
+            && builder.system_roots
+        {
+            return Ok(None);
+        }
+
+        let mut roots = rustls::RootCertStore::empty();
+        if builder.system_roots {
+            roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+                rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+                    ta.subject,
+                    ta.spki,
+                    ta.name_constraints,
+                )
+            }));
+        }
+
+        for pem in &builder.root_certificates {
+            let certificates = rustls_pemfile::certs(&mut pem.as_slice())?;
+            if certificates.is_empty() {
+                anyhow::bail!("No certificate found in root certificate PEM");
+            }
+            for der in certificates {
+                roots.add(&Certificate(der))?;
+            }
+        }
+
+        Ok(Some(
+            ClientConfig::builder()
+                .with_safe_defaults()
+                .with_custom_certificate_verifier(Arc::new(PinningVerifier {
+                    pins: builder.spki_pins.clone().unwrap_or_default(),
+                    webpki: WebPkiVerifier::new(roots, None),
+                }))
+                .with_no_client_auth(),
+        ))
+    }
+
+    /// Find the host whose pins did not match in the chain of `error`.
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn pin_mismatch_host(error: &(dyn StdError + 'static)) -> Option<String> {
+        let mut source = Some(error);
+        while let Some(e) = source {
+            // io::Error::source() skips the wrapped error, so it has to be checked explicitly.
+            if let Some(host) = e
+                .downcast_ref::<std::io::Error>()
+                .and_then(|io| io.get_ref())
+                .and_then(|inner| pin_mismatch_host(inner))
+            {
+                return Some(host);
+            }
+
+            if let Some(rustls::Error::InvalidCertificate(CertificateError::Other(other))) =
+                e.downcast_ref::<rustls::Error>()
+            {
+                if let Some(mismatch) = other.downcast_ref::<PinMismatch>() {
+                    return Some(mismatch.0.clone());
+                }
+            }
+            source = e.source();
+        }
+
+        None
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("Certificate of {0} does not match any of the pinned public keys")]
+    struct PinMismatch(String);
+
+    struct PinningVerifier {
+        pins: SpkiPins,
+        webpki: WebPkiVerifier,
+    }
+
+    impl ServerCertVerifier for PinningVerifier {
+        fn verify_server_cert(
+            &self,
+            end_entity: &Certificate,
+            intermediates: &[Certificate],
+            server_name: &ServerName,
+            scts: &mut dyn Iterator<Item = &[u8]>,
+            ocsp_response: &[u8],
+            now: SystemTime,
+        ) -> Result<ServerCertVerified, rustls::Error> {
+            let host = match server_name {
+                ServerName::DnsName(name) => name.as_ref().to_string(),
+                ServerName::IpAddress(ip) => ip.to_string(),
+                _ => String::new(),
+            };
+
+            let verified = self.webpki.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                scts,
+                ocsp_response,
+                now,
+            )?;
+
+            // The intermediates are not checked as the server can send any certificate along
+            // with its chain, only the end-entity is known to be part of the verified chain.
+            let matches = match self.pins.get(&host) {
+                Some(pins) => spki_pin(&end_entity.0).is_some_and(|pin| pins.contains(&pin)),
+                None => true,
+            };
+
+            if matches {
+                Ok(verified)
+            } else {
+                Err(rustls::Error::InvalidCertificate(CertificateError::Other(
+                    Arc::new(PinMismatch(host)),
+                )))
+            }
+        }
+    }
+
+    /// Get the DER encoded SubjectPublicKeyInfo of a DER encoded X.509 certificate.
+    fn spki(certificate: &[u8]) -> Option<&[u8]> {
+        const SEQUENCE: u8 = 0x30;
+        const VERSION: u8 = 0xa0;
+
+        let (certificate, _) = der_content(certificate, SEQUENCE)?;
+        let (mut tbs, _) = der_content(certificate, SEQUENCE)?;
+        if tbs.first() == Some(&VERSION) {
+            tbs = der_split(tbs)?.1;
+        }
+        // Skip serial number, signature algorithm, issuer, validity and subject.
+        for _ in 0..5 {
+            tbs = der_split(tbs)?.1;
+        }
+
+        let (spki, _) = der_split(tbs)?;
+        (spki.first() == Some(&SEQUENCE)).then_some(spki)
+    }
+
+    /// Split `data` after its first element.
+    fn der_split(data: &[u8]) -> Option<(&[u8], &[u8])> {
+        let (header, len) = der_header(data)?;
+        let end = header.checked_add(len)?;
+        (end <= data.len()).then(|| data.split_at(end))
+    }
+
+    /// Get the content of the first element of `data`, which must have `tag`, and the data
+    /// following it.
+    fn der_content(data: &[u8], tag: u8) -> Option<(&[u8], &[u8])> {
+        if data.first() != Some(&tag) {
+            return None;
+        }
+        let (header, _) = der_header(data)?;
+        let (element, rest) = der_split(data)?;
+        Some((&element[header..], rest))
+    }
+
+    /// Parse the tag and length of the first element of `data`. Returns the header size and
+    /// the content length.
+    fn der_header(data: &[u8]) -> Option<(usize, usize)> {
+        let first = *data.get(1)?;
+        if first < 0x80 {
+            return Some((2, first as usize));
+        }
+
+        let len_bytes = (first & 0x7f) as usize;
+        if len_bytes == 0 || len_bytes > 4 {
+            return None;
+        }
+        let len = data
+            .get(2..2 + len_bytes)?
+            .iter()
+            .fold(0usize, |len, b| (len << 8) | *b as usize);
+        Some((2 + len_bytes, len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pin_lookup() {
+        let pins = SpkiPins::new()
+            .pin("*.proton.me", &["a"])
+            .pin("mail.proton.me", &["b"])
+            .pin("proton.me", &["c"]);
+
+        assert_eq!(pins.get("mail.proton.me").unwrap(), ["b"]);
+        assert_eq!(pins.get("Account.Proton.me").unwrap(), ["a"]);
+        assert_eq!(pins.get("a.b.proton.me").unwrap(), ["a"]);
+        assert_eq!(pins.get("proton.me").unwrap(), ["c"]);
+        assert!(pins.get("notproton.me").is_none());
+
+        let pins = pins.replace("mail.proton.me", &["d"]).remove("proton.me");
+        assert_eq!(pins.get("mail.proton.me").unwrap(), ["d"]);
+        assert!(pins.get("proton.me").is_none());
+    }
+}
@@ -0,0 +1,203 @@
+//! Spans emitted when the `tracing` feature is enabled. Spans only record the method, a path
+//! template without ids or query, status codes, error kinds and timings, never headers or
+//! bodies.
+
+use crate::http::{Error, Method, RequestData};
+use std::future::Future;
+
+/// Span covering a single attempt of an http request.
+pub(crate) struct RequestSpan {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+    #[cfg(feature = "tracing")]
+    start: std::time::Instant,
+}
+
+impl RequestSpan {
+    #[cfg(feature = "tracing")]
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn new(request: &RequestData, attempt: u32) -> Self {
+        use tracing::field::Empty;
+        Self {
+            span: tracing::info_span!(
+                "http_request",
+                method = method_name(request.method),
+                path = %path_template(&request.url),
+                attempt,
+                status = Empty,
+                error = Empty,
+                latency_ms = Empty,
+            ),
+            start: std::time::Instant::now(),
+        }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn new(_: &RequestData, _: u32) -> Self {
+        Self {}
+    }
+
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn in_scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        #[cfg(feature = "tracing")]
+        let _guard = self.span.enter();
+        f()
+    }
+
+    #[cfg(feature = "tracing")]
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn instrument<F: Future>(&self, future: F) -> impl Future<Output = F::Output> {
+        tracing::Instrument::instrument(future, self.span.clone())
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn instrument<F: Future>(&self, future: F) -> impl Future<Output = F::Output> {
+        future
+    }
+
+    /// Record the status of the response or the error the attempt failed with.
+    #[allow(unused)] // Only used by http implementations.
+    pub(crate) fn finish(&self, outcome: Result<u16, &Error>) {
+        #[cfg(feature = "tracing")]
+        {
+            let status = match outcome {
+                Ok(status) => Some(status),
+                Err(Error::API(e) | Error::UnsupportedAppVersion(e)) => Some(e.http_code),
+                Err(_) => None,
+            };
+            if let Some(status) = status {
+                self.span.record("status", status);
+            }
+            if let Err(error) = outcome {
+                self.span.record("error", error_kind(error));
+            }
+            self.span
+                .record("latency_ms", self.start.elapsed().as_millis() as u64);
+        }
+        #[cfg(not(feature = "tracing"))]
+        let _ = outcome;
+    }
+}
+
+/// Span covering a named step of a [`Sequence`](crate::http::Sequence).
+pub(crate) struct StepSpan {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+impl StepSpan {
+    #[cfg(feature = "tracing")]
+    pub(crate) fn new(step: &'static str) -> Self {
+        Self {
+            span: tracing::info_span!("sequence_step", step, failed = tracing::field::Empty),
+        }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    pub(crate) fn new(_: &'static str) -> Self {
+        Self {}
+    }
+
+    pub(crate) fn in_scope<T, E>(&self, f: impl FnOnce() -> Result<T, E>) -> Result<T, E> {
+        #[cfg(feature = "tracing")]
+        let _guard = self.span.enter();
+        let result = f();
+        #[cfg(feature = "tracing")]
+        self.span.record("failed", result.is_err());
+        result
+    }
+
+    #[cfg(feature = "tracing")]
+    pub(crate) async fn instrument<T, E>(
+        self,
+        future: impl Future<Output = Result<T, E>>,
+    ) -> Result<T, E> {
+        let result = tracing::Instrument::instrument(future, self.span.clone()).await;
+        self.span.record("failed", result.is_err());
+        result
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    pub(crate) async fn instrument<T, E>(
+        self,
+        future: impl Future<Output = Result<T, E>>,
+    ) -> Result<T, E> {
+        future.await
+    }
+}
+
+#[cfg_attr(not(feature = "tracing"), allow(unused))]
+fn method_name(method: Method) -> &'static str {
+    match method {
+        Method::Delete => "DELETE",
+        Method::Get => "GET",
+        Method::Put => "PUT",
+        Method::Post => "POST",
+        Method::Patch => "PATCH",
+    }
+}
+
+/// Short name of the class of `error`, used in spans and metrics.
+#[allow(unused)] // Only used by http implementations.
+pub(crate) fn error_kind(error: &Error) -> &'static str {
+    match error {
+        Error::API(_) => "api",
+        Error::UnsupportedAppVersion(_) => "unsupported_app_version",
+        Error::Redirect(..) => "redirect",
+        Error::Timeout(_) => "timeout",
+        Error::Cancelled => "cancelled",
+        Error::Connection(_) => "connection",
+        Error::Request(_) => "request",
+        Error::PinMismatch(_) => "pin_mismatch",
+        Error::BodyTooLarge(_) => "body_too_large",
+        Error::EncodeOrDecode(_) => "encode_or_decode",
+        Error::Other(_) => "other",
+    }
+}
+
+/// Strip the query from `url` and replace the segments which look like ids with `{id}`, so
+/// that requests to the same endpoint share the same path. Proton ids are base64 strings, so
+/// segments containing upper case letters or `=`, or longer than 24 characters, are ids.
+#[allow(unused)] // Only used by http implementations.
+pub(crate) fn path_template(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    path.split('/')
+        .map(|segment| {
+            let is_id = segment.len() > 24
+                || segment
+                    .chars()
+                    .any(|c| c.is_ascii_uppercase() || c == '=' || c == '%');
+            if is_id {
+                "{id}"
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_template() {
+        assert_eq!(path_template("core/v4/users"), "core/v4/users");
+        assert_eq!(
+            path_template("mail/v4/messages/a3Fv-_9Qx0PzDbl6RXkg==?Page=1"),
+            "mail/v4/messages/{id}"
+        );
+        assert_eq!(
+            path_template("core/v4/keys?Email=user%40proton.me"),
+            "core/v4/keys"
+        );
+        assert_eq!(
+            path_template("mail/v4/attachments/abcdefghijklmnopqrstuvwxyz"),
+            "mail/v4/attachments/{id}"
+        );
+        assert_eq!(path_template("auth/v4/2fa"), "auth/v4/2fa");
+    }
+}
@@ -1,133 +1,261 @@
 //! UReq HTTP client implementation.
 
-use crate::http::X_PM_APP_VERSION_HEADER;
 use crate::http::{
-    ClientBuilder, ClientRequest, ClientRequestBuilder, ClientSync, Error, FromResponse, Method,
-    RequestData, ResponseBodySync,
+    multipart_boundary, parse_retry_after, pin_mismatch_host, read_body_limited, tls_config,
+    AltRouting, BodyReader, BufferedResponse, ClientBuilder, ClientRequest, ClientRequestBuilder,
+    ClientSync, Error, FromResponse, HttpCache, Method, MetricsHook, MiddlewareChain, RateLimiter,
+    Redactor, RequestBudget, RequestData, RequestSpan, ResponseBodySync, ResponseMeta, RetryPolicy,
+    ServerClock, DEFAULT_MAX_BODY_SIZE,
 };
 use crate::requests::APIError;
+use bytes::Bytes;
 use log::debug;
-use std::io;
-use std::io::Read;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use ureq;
 
 #[derive(Debug, Clone)]
 pub struct UReqClient {
-    agent: ureq::Agent,
-    app_version: String,
+    agent: PooledAgent,
     base_url: String,
-    debug: bool,
+    request_timeout: Option<Duration>,
+    retry_policy: Option<RetryPolicy>,
+    middleware: MiddlewareChain,
+    max_body_size: usize,
+    alt_routing: Option<AltRouting>,
+    rate_limiter: Option<RateLimiter>,
+    metrics: Option<MetricsHook>,
+    server_clock: ServerClock,
+    cache: Option<HttpCache>,
+    redactor: Redactor,
 }
 
 impl TryFrom<ClientBuilder> for UReqClient {
     type Error = anyhow::Error;
 
     fn try_from(value: ClientBuilder) -> Result<Self, Self::Error> {
-        let mut builder = ureq::AgentBuilder::new();
+        let middleware = value.middleware_chain()?;
+        let proxy = match &value.proxy_url {
+            Some(proxy) => Some(ureq::Proxy::new(proxy.as_url())?),
+            None => None,
+        };
+        let tls = tls_config(&value)?.map(Arc::new);
+        let dns_overrides = Arc::new(value.dns_overrides.clone());
+
+        let request_timeout = value.request_timeout;
+        let connect_timeout = value.connect_timeout;
+        let allow_http = value.allow_http;
+        let user_agent = value.user_agent.clone();
+        let max_idle_per_host = value.pool_max_idle_per_host;
+        let agent = PooledAgent::new(value.pool_idle_timeout, move || {
+            let mut builder = ureq::AgentBuilder::new();
+
+            if let Some(d) = request_timeout {
+                builder = builder.timeout(d);
+            }
 
-        if let Some(d) = value.request_timeout {
-            builder = builder.timeout(d);
-        }
+            if let Some(d) = connect_timeout {
+                builder = builder.timeout_connect(d)
+            }
 
-        if let Some(d) = value.connect_timeout {
-            builder = builder.timeout_connect(d)
-        }
+            if let Some(proxy) = &proxy {
+                builder = builder.proxy(proxy.clone());
+            }
 
-        if let Some(proxy) = value.proxy_url {
-            let proxy = ureq::Proxy::new(proxy.as_url())?;
-            builder = builder.proxy(proxy);
-        }
+            if !allow_http {
+                builder = builder.https_only(true)
+            }
 
-        if !value.allow_http {
-            builder = builder.https_only(true)
-        }
+            if let Some(tls) = &tls {
+                builder = builder.tls_config(tls.clone());
+            }
+
+            if !dns_overrides.is_empty() {
+                let overrides = dns_overrides.clone();
+                builder = builder.resolver(move |netloc: &str| resolve(&overrides, netloc));
+            }
 
-        let agent = builder
-            .user_agent(&value.user_agent)
-            .max_idle_connections(0)
-            .max_idle_connections_per_host(0)
-            .build();
+            builder
+                .user_agent(&user_agent)
+                .max_idle_connections(max_idle_per_host.max(DEFAULT_MAX_IDLE_CONNECTIONS))
+                .max_idle_connections_per_host(max_idle_per_host)
+                .build()
+        });
 
         Ok(Self {
             agent,
-            app_version: value.app_version,
             base_url: value.base_url,
-            debug: value.debug,
+            request_timeout: value.request_timeout,
+            retry_policy: value.retry_policy,
+            middleware,
+            max_body_size: value.max_body_size,
+            alt_routing: value.alt_routing,
+            rate_limiter: value.rate_limiter,
+            metrics: value.metrics,
+            server_clock: value.server_clock.unwrap_or_default(),
+            cache: value.cache,
+            redactor: value.redactor,
         })
     }
 }
 
+/// Upper bound of idle connections across all hosts.
+const DEFAULT_MAX_IDLE_CONNECTIONS: usize = 100;
+
+/// Agent whose pooled connections are dropped once they may have been idle for longer than
+/// the idle timeout.
+///
+/// ureq keeps idle connections until the server closes them and then only resends idempotent
+/// requests without a body, so a token refresh on a stale connection would fail. Since ureq
+/// has no idle timeout, the agent is rebuilt with an empty pool instead. Requests are assumed
+/// to leave their connection idle when they start, which overestimates the idle time.
+#[derive(Clone)]
+struct PooledAgent {
+    idle_timeout: Option<Duration>,
+    factory: Arc<dyn Fn() -> ureq::Agent + Send + Sync>,
+    state: Arc<Mutex<PoolState>>,
+}
+
+struct PoolState {
+    agent: ureq::Agent,
+    last_used: HashMap<String, Instant>,
+}
+
+impl std::fmt::Debug for PooledAgent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PooledAgent(idle_timeout={:?})", self.idle_timeout)
+    }
+}
+
+impl PooledAgent {
+    fn new(
+        idle_timeout: Option<Duration>,
+        factory: impl Fn() -> ureq::Agent + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            idle_timeout,
+            state: Arc::new(Mutex::new(PoolState {
+                agent: factory(),
+                last_used: HashMap::new(),
+            })),
+            factory: Arc::new(factory),
+        }
+    }
+
+    /// Get the agent to send a request to `base_url` with.
+    fn get(&self, base_url: &str) -> ureq::Agent {
+        let mut state = self.state.lock();
+        let now = Instant::now();
+        let expired = match (self.idle_timeout, state.last_used.get(base_url)) {
+            (Some(timeout), Some(last_used)) => now.duration_since(*last_used) > timeout,
+            _ => false,
+        };
+
+        if expired {
+            debug!("Idle connections to {base_url} expired, dropping the connection pool");
+            state.agent = (self.factory)();
+            state.last_used.clear();
+        }
+
+        state.last_used.insert(base_url.to_string(), now);
+        state.agent.clone()
+    }
+}
+
 impl From<ureq::Error> for Error {
     fn from(value: ureq::Error) -> Self {
         match value {
             ureq::Error::Status(status, response) => {
-                if let Ok(body) = safe_read_body(response) {
-                    return Error::API(APIError::with_status_and_body(status, &body));
+                let retry_after = response.header("Retry-After").and_then(|v| {
+                    let date = response.header("Date");
+                    parse_retry_after(v, date.and_then(|d| httpdate::parse_http_date(d).ok()))
+                });
+                let body = read_body_limited(response.into_reader(), DEFAULT_MAX_BODY_SIZE);
+                let mut error = match body {
+                    Ok(body) => APIError::with_status_and_body(status, &body),
+                    Err(_) => APIError::new(status),
+                };
+                error.retry_after = retry_after;
+                error.into()
+            }
+            ureq::Error::Transport(t) => {
+                if let Some(host) = pin_mismatch_host(&t) {
+                    return Error::PinMismatch(host);
                 }
 
-                Error::API(APIError::new(status))
+                match t.kind() {
+                    ureq::ErrorKind::InvalidUrl => Error::Request(t.into()),
+                    ureq::ErrorKind::UnknownScheme => Error::Request(t.into()),
+                    ureq::ErrorKind::Dns => Error::Connection(t.into()),
+                    ureq::ErrorKind::InsecureRequestHttpsOnly => Error::Request(t.into()),
+                    ureq::ErrorKind::ConnectionFailed => Error::Connection(t.into()),
+                    ureq::ErrorKind::TooManyRedirects => Error::Redirect(
+                        t.url()
+                            .map(|u| u.to_string())
+                            .unwrap_or("Unknown url".to_string()),
+                        t.into(),
+                    ),
+                    ureq::ErrorKind::BadStatus => Error::Request(t.into()),
+                    ureq::ErrorKind::BadHeader => Error::Request(t.into()),
+                    ureq::ErrorKind::Io if is_timeout(&t) => Error::Timeout(t.into()),
+                    ureq::ErrorKind::Io => Error::Connection(t.into()),
+                    ureq::ErrorKind::InvalidProxyUrl => Error::Connection(t.into()),
+                    ureq::ErrorKind::ProxyConnect => Error::Connection(t.into()),
+                    ureq::ErrorKind::ProxyUnauthorized => Error::Connection(t.into()),
+                    ureq::ErrorKind::HTTP => Error::Request(t.into()),
+                }
             }
-            ureq::Error::Transport(t) => match t.kind() {
-                ureq::ErrorKind::InvalidUrl => Error::Request(t.into()),
-                ureq::ErrorKind::UnknownScheme => Error::Request(t.into()),
-                ureq::ErrorKind::Dns => Error::Connection(t.into()),
-                ureq::ErrorKind::InsecureRequestHttpsOnly => Error::Request(t.into()),
-                ureq::ErrorKind::ConnectionFailed => Error::Connection(t.into()),
-                ureq::ErrorKind::TooManyRedirects => Error::Redirect(
-                    t.url()
-                        .map(|u| u.to_string())
-                        .unwrap_or("Unknown url".to_string()),
-                    t.into(),
-                ),
-                ureq::ErrorKind::BadStatus => Error::Request(t.into()),
-                ureq::ErrorKind::BadHeader => Error::Request(t.into()),
-                ureq::ErrorKind::Io => Error::Connection(t.into()),
-                ureq::ErrorKind::InvalidProxyUrl => Error::Connection(t.into()),
-                ureq::ErrorKind::ProxyConnect => Error::Connection(t.into()),
-                ureq::ErrorKind::ProxyUnauthorized => Error::Connection(t.into()),
-                ureq::ErrorKind::HTTP => Error::Request(t.into()),
-            },
         }
     }
 }
 
-struct UReqResponse(ureq::Response);
-
-impl ResponseBodySync for UReqResponse {
-    type Body = Vec<u8>;
-
-    fn get_body(self) -> crate::http::Result<Self::Body> {
-        let body = safe_read_body(self.0)
-            .map_err(|e| Error::Request(anyhow::anyhow!("Failed to read response body {e}")))?;
-        Ok(body)
+/// Resolve `netloc`, a `host:port` pair, unless its host is overridden.
+fn resolve(
+    overrides: &HashMap<String, SocketAddr>,
+    netloc: &str,
+) -> std::io::Result<Vec<SocketAddr>> {
+    let Some((host, port)) = netloc.rsplit_once(':') else {
+        return netloc.to_socket_addrs().map(Iterator::collect);
+    };
+
+    match overrides.get(&host.to_ascii_lowercase()) {
+        Some(addr) => {
+            let mut addr = *addr;
+            if let Ok(port) = port.parse() {
+                addr.set_port(port);
+            }
+            Ok(vec![addr])
+        }
+        None => netloc.to_socket_addrs().map(Iterator::collect),
     }
 }
 
-struct UReqDebugResponse(ureq::Response);
-
-impl ResponseBodySync for UReqDebugResponse {
-    type Body = Vec<u8>;
-
-    fn get_body(self) -> crate::http::Result<Self::Body> {
-        let body = safe_read_body(self.0)
-            .map_err(|e| Error::Request(anyhow::anyhow!("Failed to read response body {e}")))?;
-
-        let body_str = String::from_utf8_lossy(&body);
-        debug!("Request Body: {}", body_str);
-
-        Ok(body)
+/// Check whether the request failed because the timeout elapsed.
+fn is_timeout(error: &ureq::Transport) -> bool {
+    let mut source = std::error::Error::source(error);
+    while let Some(e) = source {
+        if let Some(io_err) = e.downcast_ref::<std::io::Error>() {
+            if matches!(
+                io_err.kind(),
+                std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+            ) {
+                return true;
+            }
+        }
+        source = e.source();
     }
-}
 
-pub struct UReqRequest {
-    request: ureq::Request,
-    body: Option<bytes::Bytes>,
+    false
 }
 
+pub struct UReqRequest(RequestData);
+
 impl ClientRequest for UReqRequest {
-    fn header(mut self, key: impl AsRef<str>, value: impl AsRef<str>) -> Self {
-        self.request = self.request.set(key.as_ref(), value.as_ref());
-        self
+    fn header(self, key: impl AsRef<str>, value: impl AsRef<str>) -> Self {
+        Self(self.0.header(key.as_ref(), value.as_ref()))
     }
 }
 
@@ -135,62 +263,255 @@ impl ClientRequestBuilder for UReqClient {
     type Request = UReqRequest;
 
     fn new_request(&self, request: &RequestData) -> Self::Request {
-        let final_url = format!("{}/{}", self.base_url, request.url);
+        UReqRequest(request.clone())
+    }
+
+    fn server_clock(&self) -> Option<&ServerClock> {
+        Some(&self.server_clock)
+    }
+}
+
+impl UReqClient {
+    fn build_request(
+        &self,
+        base_url: &str,
+        request: &RequestData,
+        timeout: Option<Duration>,
+    ) -> ureq::Request {
+        let final_url = format!("{}/{}", base_url, request.url);
+        let agent = self.agent.get(base_url);
         let mut ureq_request = match request.method {
-            Method::Delete => self.agent.delete(&final_url),
-            Method::Get => self.agent.get(&final_url),
-            Method::Put => self.agent.put(&final_url),
-            Method::Post => self.agent.post(&final_url),
-            Method::Patch => self.agent.patch(&final_url),
+            Method::Delete => agent.delete(&final_url),
+            Method::Get => agent.get(&final_url),
+            Method::Put => agent.put(&final_url),
+            Method::Post => agent.post(&final_url),
+            Method::Patch => agent.patch(&final_url),
         };
 
-        // Set app version.
-        ureq_request = ureq_request.set(X_PM_APP_VERSION_HEADER, &self.app_version);
-
         // Set headers.
         for (header, value) in &request.headers {
             ureq_request = ureq_request.set(header, value);
         }
 
-        Self::Request {
-            request: ureq_request,
-            body: request.body.clone(),
+        // The per-request timeout replaces the agent's timeout, so keep the shorter one.
+        if let Some(timeout) = timeout {
+            let timeout = self.request_timeout.map_or(timeout, |t| t.min(timeout));
+            ureq_request = ureq_request.timeout(timeout);
+        }
+
+        ureq_request
+    }
+
+    fn execute_once(
+        &self,
+        base_url: &str,
+        request: &RequestData,
+        streaming: bool,
+        timeout: Option<Duration>,
+    ) -> Result<UReqBody, Error> {
+        let mut request = request.clone();
+        self.middleware.on_request(&mut request)?;
+
+        let ureq_request = self.build_request(base_url, &request, timeout);
+        let result = if let Some(multipart) = &request.multipart {
+            let boundary = multipart_boundary();
+            let reader = multipart.reader(&boundary)?;
+            ureq_request
+                .set(
+                    "Content-Type",
+                    &format!("multipart/form-data; boundary={boundary}"),
+                )
+                .send(reader)
+        } else if let Some(body) = &request.body {
+            ureq_request.send_bytes(body.as_ref())
+        } else {
+            ureq_request.call()
+        };
+
+        let response = match result {
+            Ok(r) => r,
+            Err(ureq::Error::Status(_, r)) => r,
+            Err(e) => return Err(e.into()),
+        };
+
+        let meta = response_meta(&response);
+        self.server_clock.update(&meta);
+        if streaming && meta.status() < 400 {
+            self.middleware.on_response(&request, &meta, &[])?;
+            return Ok(UReqBody::Streaming(meta, response.into_reader()));
+        }
+
+        let body = read_body_limited(response.into_reader(), self.max_body_size)?;
+
+        self.middleware.on_response(&request, &meta, &body)?;
+        meta.error_for_status(&body)?;
+
+        Ok(UReqBody::Buffered(meta, body.into()))
+    }
+
+    /// Execute the request, failing over to an alternative host if the current host is
+    /// unreachable.
+    fn execute_routed(
+        &self,
+        request: &RequestData,
+        streaming: bool,
+        timeout: Option<Duration>,
+    ) -> Result<UReqBody, Error> {
+        let Some(routing) = &self.alt_routing else {
+            return self.execute_once(&self.base_url, request, streaming, timeout);
+        };
+
+        let base_url = routing.base_url(&self.base_url);
+        let err = match self.execute_once(&base_url, request, streaming, timeout) {
+            Ok(body) => return Ok(body),
+            Err(e) => e,
+        };
+
+        for candidate in routing.failover_candidates(&self.base_url, &base_url, &err) {
+            if !self.verify_host(&candidate) {
+                debug!("Alternative host {candidate} failed verification");
+                continue;
+            }
+
+            routing.activate(candidate.clone());
+            if AltRouting::can_resend(request, &err) {
+                return self.execute_once(&candidate, request, streaming, timeout);
+            }
+            break;
         }
+
+        Err(err)
+    }
+
+    fn verify_host(&self, base_url: &str) -> bool {
+        self.agent
+            .get(base_url)
+            .get(&format!("{base_url}/tests/ping"))
+            .call()
+            .is_ok()
     }
 }
 
 impl ClientSync for UReqClient {
     fn execute<R: FromResponse>(&self, request: Self::Request) -> Result<R::Output, Error> {
-        let ureq_response = if let Some(body) = request.body {
-            request.request.send_bytes(body.as_ref())?
-        } else {
-            request.request.call()?
+        let mut request = request.0;
+        let cached = match &self.cache {
+            Some(cache) if !R::STREAMING => cache.prepare(&mut request),
+            _ => None,
+        };
+        let budget = RequestBudget::new(&request);
+        let mut attempt = 1;
+        let body = loop {
+            let permit = match &self.rate_limiter {
+                Some(limiter) => {
+                    budget.sleep_blocking(limiter.reserve(&request))?;
+                    Some(limiter.acquire_blocking(&request))
+                }
+                None => None,
+            };
+            let timeout = budget.check()?;
+            let span = RequestSpan::new(&request, attempt);
+            let start = Instant::now();
+            let result = span.in_scope(|| self.execute_routed(&request, R::STREAMING, timeout));
+            span.finish(result.as_ref().map(UReqBody::status));
+            if let Some(metrics) = &self.metrics {
+                let outcome = result.as_ref().map(UReqBody::response);
+                metrics.observe(&request, attempt, start.elapsed(), outcome);
+            }
+            drop(permit);
+
+            let err = match result {
+                Ok(r) => break r,
+                Err(e) => e,
+            };
+
+            let Some(delay) = self
+                .retry_policy
+                .as_ref()
+                .and_then(|p| p.retry_delay(attempt, &request, &err))
+            else {
+                return Err(err);
+            };
+
+            debug!(
+                "Request {:?} {} failed on attempt {attempt}, retrying in {delay:?}: {err}",
+                request.method,
+                self.redactor.redact_url(&request.url)
+            );
+            budget.sleep_blocking(delay)?;
+            attempt += 1;
         };
 
-        if !self.debug {
-            R::from_response_sync(UReqResponse(ureq_response))
-        } else {
-            R::from_response_sync(UReqDebugResponse(ureq_response))
+        match body {
+            UReqBody::Buffered(mut meta, mut body) => {
+                if let Some(cache) = &self.cache {
+                    cache.complete(&request, cached, &mut meta, &mut body);
+                }
+                R::from_response_sync(BufferedResponse(meta, body))
+            }
+            UReqBody::Streaming(meta, reader) => R::from_response_sync(UReqStreamedResponse {
+                meta,
+                reader: budget.reader(reader),
+                max_body_size: self.max_body_size,
+            }),
         }
     }
 }
 
-fn safe_read_body(response: ureq::Response) -> Result<Vec<u8>, io::Error> {
-    let mut vec = vec![];
+enum UReqBody {
+    Buffered(ResponseMeta, Bytes),
+    Streaming(ResponseMeta, BodyReader),
+}
 
-    if let Some(length) = response.header("Content-Length") {
-        if let Ok(len) = length.parse::<usize>() {
-            if len == 0 {
-                return Ok(vec![]);
-            }
-            vec.reserve(len);
+impl UReqBody {
+    fn status(&self) -> u16 {
+        self.response().0.status()
+    }
+
+    /// Get the response and the size of its body if it was read into memory.
+    fn response(&self) -> (&ResponseMeta, Option<usize>) {
+        match self {
+            UReqBody::Buffered(meta, body) => (meta, Some(body.len())),
+            UReqBody::Streaming(meta, _) => (meta, None),
         }
     }
+}
 
-    let _ = response
-        .into_reader()
-        .take(10_000_000)
-        .read_to_end(&mut vec)?;
+/// Response whose body has not been read yet.
+struct UReqStreamedResponse {
+    meta: ResponseMeta,
+    reader: BodyReader,
+    max_body_size: usize,
+}
+
+impl ResponseBodySync for UReqStreamedResponse {
+    type Body = Vec<u8>;
+
+    fn meta(&self) -> &ResponseMeta {
+        &self.meta
+    }
+
+    fn get_body(self) -> Result<Self::Body, Error> {
+        read_body_limited(self.reader, self.max_body_size)
+    }
+
+    fn into_reader(self) -> Result<BodyReader, Error> {
+        Ok(self.reader)
+    }
+}
+
+fn response_meta(response: &ureq::Response) -> ResponseMeta {
+    let headers = response
+        .headers_names()
+        .into_iter()
+        .flat_map(|name| {
+            response
+                .all(&name)
+                .into_iter()
+                .map(|v| (name.clone(), v.to_string()))
+                .collect::<Vec<_>>()
+        })
+        .collect();
 
-    Ok(vec)
+    ResponseMeta::new(response.status(), headers)
 }
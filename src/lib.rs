@@ -44,7 +44,7 @@
 //!     let client = http::ClientBuilder::new()
 //!         .user_agent("MyUserAgent/0.0.0")
 //!         .base_url("server_url")
-//!         .app_version("MyApp@0.1.1")
+//!         .app_version("linux-myapp@0.1.1")
 //!         .build::<T>().unwrap();
 //!
 //!     let session = match Session::login(&"my_address@proton.me", &SecretString::new("my_proton_password".into()), None).do_async(&client).await.unwrap(){
@@ -71,7 +71,7 @@
 //!     let client = http::ClientBuilder::new()
 //!         .user_agent("MyUserAgent/0.0.0")
 //!         .base_url("server_url")
-//!         .app_version("MyApp@0.1.1")
+//!         .app_version("linux-myapp@0.1.1")
 //!         .build::<T>().unwrap();
 //!
 //!     let session = match Session::login("my_address@proton.me", &SecretString::new("my_proton_password".into()), None).do_sync(&client).unwrap(){
@@ -101,7 +101,7 @@
 //!     let client = http::ClientBuilder::new()
 //!         .user_agent("MyUserAgent/0.0.0")
 //!         .base_url("server_url")
-//!         .app_version("MyApp@0.1.1")
+//!         .app_version("linux-myapp@0.1.1")
 //!         .build::<T>().unwrap();
 //!
 //!     let session = Session::refresh(&user_uid, &user_refresh_token).do_async(&client).await.unwrap();
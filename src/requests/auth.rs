@@ -287,12 +287,28 @@ impl<'a> http::RequestDesc for CaptchaRequest<'a> {
     type Response = http::StringResponse;
 
     fn build(&self) -> RequestData {
-        let url = if self.force_web {
-            format!("core/v4/captcha?ForceWebMessaging=1&Token={}", self.token)
+        let request = RequestData::new(http::Method::Get, "core/v4/captcha");
+        let request = if self.force_web {
+            request.query("ForceWebMessaging", 1)
         } else {
-            format!("core/v4/captcha?Token={}", self.token)
+            request
         };
 
-        RequestData::new(http::Method::Get, url)
+        request.query("Token", self.token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::RequestDesc;
+
+    #[test]
+    fn test_captcha_request_build() {
+        let data = CaptchaRequest::new("abc+/=&x", false).build();
+        assert_eq!(data.url, "core/v4/captcha?Token=abc%2B%2F%3D%26x");
+
+        let data = CaptchaRequest::new("token", true).build();
+        assert_eq!(data.url, "core/v4/captcha?ForceWebMessaging=1&Token=token");
     }
 }
@@ -1,9 +1,13 @@
 use crate::domain::{HumanVerification, HumanVerificationType};
 use anyhow::anyhow;
 use serde::Deserialize;
+use std::time::Duration;
 use thiserror::Error;
 
 const HUMAN_VERIFICATION_REQUESTED: u32 = 9001;
+const APP_VERSION_MISSING: u32 = 5001;
+const APP_VERSION_BAD: u32 = 5003;
+const API_VERSION_BAD: u32 = 5005;
 
 #[derive(Deserialize)]
 #[serde(rename_all = "PascalCase")]
@@ -14,7 +18,11 @@ pub struct APIErrorDesc {
 }
 
 /// Representation of the Proton API Error.
+///
+/// Fields may be added in future releases, create errors with [`APIError::new`] and set the
+/// fields instead of using a struct literal.
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub struct APIError {
     /// Http Code for the error.
     pub http_code: u16,
@@ -24,6 +32,8 @@ pub struct APIError {
     pub message: Option<String>,
     /// Optional JSON type with error details.
     pub details: Option<serde_json::Value>,
+    /// Delay requested by the server through the `Retry-After` header.
+    pub retry_after: Option<Duration>,
 }
 
 #[derive(Debug, Error)]
@@ -37,6 +47,15 @@ pub enum GetHumanVerificationError {
 }
 
 impl APIError {
+    /// Whether the API rejected the request because the app version is missing, malformed or
+    /// no longer supported, which requires an update of the app.
+    pub fn is_app_version_error(&self) -> bool {
+        matches!(
+            self.api_code,
+            APP_VERSION_MISSING | APP_VERSION_BAD | API_VERSION_BAD
+        )
+    }
+
     pub fn is_human_verification_request(&self) -> bool {
         self.api_code == HUMAN_VERIFICATION_REQUESTED
     }
@@ -104,6 +123,7 @@ impl APIError {
             api_code: 0,
             message: None,
             details: None,
+            retry_after: None,
         }
     }
 
@@ -118,6 +138,7 @@ impl APIError {
                 api_code: e.code,
                 message: e.error,
                 details: e.details,
+                retry_after: None,
             },
             Err(_) => Self::new(http_status),
         }
@@ -89,10 +89,7 @@ impl<'a> http::RequestDesc for GetPublicKeysRequest<'a> {
     type Response = http::JsonResponse<Self::Output>;
 
     fn build(&self) -> RequestData {
-        RequestData::new(
-            http::Method::Get,
-            format!("core/v4/keys?Email={}", self.email),
-        )
+        RequestData::new(http::Method::Get, "core/v4/keys").query("Email", self.email)
     }
 }
 
@@ -137,6 +134,7 @@ impl http::RequestDesc for GetAllKeysRequest {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::http::RequestDesc;
 
     #[test]
     fn test_get_user_keys_request_build() {
@@ -155,9 +153,9 @@ mod tests {
 
     #[test]
     fn test_get_public_keys_request_build() {
-        let req = GetPublicKeysRequest::new("test@example.com");
+        let req = GetPublicKeysRequest::new("test+alias@example.com");
         let data = req.build();
-        assert_eq!(data.url, "core/v4/keys?Email=test@example.com");
+        assert_eq!(data.url, "core/v4/keys?Email=test%2Balias%40example.com");
     }
 
     #[test]
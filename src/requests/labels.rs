@@ -25,9 +25,18 @@ impl http::RequestDesc for GetLabelsRequest {
     type Response = http::JsonResponse<Self::Output>;
 
     fn build(&self) -> RequestData {
-        RequestData::new(
-            http::Method::Get,
-            format!("core/v4/labels?Type={}", self.label_type as u8),
-        )
+        RequestData::new(http::Method::Get, "core/v4/labels").query("Type", self.label_type as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::RequestDesc;
+
+    #[test]
+    fn test_get_labels_request_build() {
+        let data = GetLabelsRequest::new(LabelType::Folder).build();
+        assert_eq!(data.url, "core/v4/labels?Type=3");
     }
 }
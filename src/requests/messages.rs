@@ -41,31 +41,15 @@ impl http::RequestDesc for GetMessagesRequest {
     type Response = http::JsonResponse<Self::Output>;
 
     fn build(&self) -> RequestData {
-        let mut query_parts = Vec::new();
-
-        if let Some(ref label_id) = self.filter.label_id {
-            query_parts.push(format!("LabelID={}", label_id));
-        }
-        if let Some(page) = self.filter.page {
-            query_parts.push(format!("Page={}", page));
-        }
-        if let Some(page_size) = self.filter.page_size {
-            query_parts.push(format!("PageSize={}", page_size));
-        }
-        if let Some(ref end_id) = self.filter.end_id {
-            query_parts.push(format!("EndID={}", end_id));
-        }
-        if self.filter.desc.is_some() {
-            query_parts.push("Desc=1".to_string());
-        }
-
-        let path = if query_parts.is_empty() {
-            "mail/v4/messages".to_string()
-        } else {
-            format!("mail/v4/messages?{}", query_parts.join("&"))
-        };
-
-        RequestData::new(http::Method::Get, path)
+        let filter = &self.filter;
+        RequestData::new(http::Method::Get, "mail/v4/messages")
+            .query_opt("LabelID", filter.label_id.as_ref())
+            .query_opt("Page", filter.page)
+            .query_opt("PageSize", filter.page_size)
+            .query_opt("EndID", filter.end_id.as_ref())
+            .query_opt("Desc", filter.desc.map(|d| d as u8))
+            .query_opt("Subject", filter.subject.as_ref())
+            .query_opt("AddressID", filter.address_id.as_ref())
     }
 }
 
@@ -351,6 +335,29 @@ impl<'a> http::RequestDesc for GetAttachmentRequest<'a> {
     }
 }
 
+/// Request to download an attachment by ID without loading it into memory.
+pub struct GetAttachmentStreamRequest<'a> {
+    attachment_id: &'a str,
+}
+
+impl<'a> GetAttachmentStreamRequest<'a> {
+    pub fn new(attachment_id: &'a str) -> Self {
+        Self { attachment_id }
+    }
+}
+
+impl<'a> http::RequestDesc for GetAttachmentStreamRequest<'a> {
+    type Output = http::ResponseStream;
+    type Response = http::StreamingResponse;
+
+    fn build(&self) -> RequestData {
+        RequestData::new(
+            http::Method::Get,
+            format!("mail/v4/attachments/{}", self.attachment_id),
+        )
+    }
+}
+
 // ============================================================================
 // POST /mail/v4/messages - Create draft message
 // ============================================================================
@@ -481,3 +488,35 @@ impl<'a> http::RequestDesc for SendMessageRequest<'a> {
         RequestData::new(http::Method::Post, format!("mail/v4/messages/{}", self.id)).json(&body)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Boolean;
+    use crate::http::RequestDesc;
+
+    #[test]
+    fn test_get_messages_request_build() {
+        let data = GetMessagesRequest::new(MessageFilter::new()).build();
+        assert_eq!(data.url, "mail/v4/messages");
+
+        let data = GetMessagesRequest::for_label("0").build();
+        assert_eq!(data.url, "mail/v4/messages?LabelID=0");
+
+        let filter = MessageFilter {
+            label_id: Some("a/b==".to_string()),
+            page: Some(2),
+            page_size: Some(50),
+            desc: Some(Boolean::True),
+            end_id: Some("x+y".to_string()),
+            subject: Some("Re: hello & bye".to_string()),
+            address_id: Some("addr=".to_string()),
+        };
+        let data = GetMessagesRequest::new(filter).build();
+        assert_eq!(
+            data.url,
+            "mail/v4/messages?LabelID=a%2Fb%3D%3D&Page=2&PageSize=50&EndID=x%2By&Desc=1\
+             &Subject=Re%3A%20hello%20%26%20bye&AddressID=addr%3D"
+        );
+    }
+}
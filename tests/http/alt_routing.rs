@@ -0,0 +1,180 @@
+use crate::utils::{ClientASync, ClientSync, StubResponse, StubServer};
+use proton_api_rs::http::{
+    AltRouting, AltRoutingResolver, ClientBuilder, Error, Method, OwnedRequest, RequestData,
+    Sequence, StringResponse,
+};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
+/// Url of a local port on which nothing is listening.
+fn unreachable_url() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    format!("http://127.0.0.1:{}", listener.local_addr().unwrap().port())
+}
+
+#[derive(Clone)]
+struct FakeResolver {
+    hosts: Vec<String>,
+    calls: Arc<AtomicUsize>,
+}
+
+impl AltRoutingResolver for FakeResolver {
+    fn resolve(&self, _: &str) -> proton_api_rs::http::Result<Vec<String>> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Ok(self.hosts.clone())
+    }
+}
+
+struct Setup {
+    server: StubServer,
+    resolver: FakeResolver,
+    routing: AltRouting,
+    builder: ClientBuilder,
+}
+
+fn setup(period: Duration) -> Setup {
+    let server = StubServer::new(|_| StubResponse::new(200).body("alt"));
+    let resolver = FakeResolver {
+        hosts: vec![unreachable_url(), server.url()],
+        calls: Arc::new(AtomicUsize::new(0)),
+    };
+    let routing = AltRouting::new(resolver.clone()).period(period);
+    let builder = ClientBuilder::new()
+        .base_url(&unreachable_url())
+        .allow_http()
+        .alternative_routing(routing.clone());
+
+    Setup {
+        server,
+        resolver,
+        routing,
+        builder,
+    }
+}
+
+fn request() -> OwnedRequest<StringResponse> {
+    OwnedRequest::new(RequestData::new(Method::Get, "core/v4/users"))
+}
+
+fn paths(server: &StubServer) -> Vec<String> {
+    server.requests().into_iter().map(|r| r.path).collect()
+}
+
+#[test]
+fn alt_routing_sync() {
+    let setup = setup(Duration::from_secs(60));
+    let client = setup.builder.build::<ClientSync>().unwrap();
+
+    assert_eq!(request().do_sync(&client).unwrap(), "alt");
+    assert_eq!(request().do_sync(&client).unwrap(), "alt");
+
+    assert_eq!(setup.resolver.calls.load(Ordering::SeqCst), 1);
+    assert_eq!(setup.routing.active_base_url(), Some(setup.server.url()));
+    assert_eq!(
+        paths(&setup.server),
+        vec!["/tests/ping", "/core/v4/users", "/core/v4/users"]
+    );
+}
+
+#[tokio::test]
+async fn alt_routing_async() {
+    let setup = setup(Duration::from_secs(60));
+    let client = setup.builder.build::<ClientASync>().unwrap();
+
+    assert_eq!(request().do_async(&client).await.unwrap(), "alt");
+    assert_eq!(request().do_async(&client).await.unwrap(), "alt");
+
+    assert_eq!(setup.resolver.calls.load(Ordering::SeqCst), 1);
+    assert_eq!(setup.routing.active_base_url(), Some(setup.server.url()));
+    assert_eq!(
+        paths(&setup.server),
+        vec!["/tests/ping", "/core/v4/users", "/core/v4/users"]
+    );
+}
+
+#[test]
+fn alt_routing_switches_back_after_period() {
+    let setup = setup(Duration::from_millis(100));
+    let client = setup.builder.build::<ClientSync>().unwrap();
+
+    assert_eq!(request().do_sync(&client).unwrap(), "alt");
+    std::thread::sleep(Duration::from_millis(150));
+    assert_eq!(setup.routing.active_base_url(), None);
+
+    // The primary host is tried first again and fails over once more.
+    assert_eq!(request().do_sync(&client).unwrap(), "alt");
+    assert_eq!(setup.resolver.calls.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn alt_routing_without_reachable_hosts() {
+    let resolver = FakeResolver {
+        hosts: vec![unreachable_url()],
+        calls: Arc::new(AtomicUsize::new(0)),
+    };
+    let client = ClientBuilder::new()
+        .base_url(&unreachable_url())
+        .allow_http()
+        .alternative_routing(AltRouting::new(resolver.clone()))
+        .build::<ClientSync>()
+        .unwrap();
+
+    assert!(matches!(
+        request().do_sync(&client),
+        Err(Error::Connection(_))
+    ));
+    assert_eq!(resolver.calls.load(Ordering::SeqCst), 1);
+}
+
+/// Resolver which only answers once the runtime made progress on another task after it was
+/// called.
+struct BlockingResolver {
+    hosts: Vec<String>,
+    called: Mutex<mpsc::Sender<()>>,
+    ready: Mutex<mpsc::Receiver<()>>,
+}
+
+impl AltRoutingResolver for BlockingResolver {
+    fn resolve(&self, _: &str) -> proton_api_rs::http::Result<Vec<String>> {
+        self.called.lock().unwrap().send(()).unwrap();
+        match self
+            .ready
+            .lock()
+            .unwrap()
+            .recv_timeout(Duration::from_secs(2))
+        {
+            Ok(()) => Ok(self.hosts.clone()),
+            Err(_) => Ok(vec![]),
+        }
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn alt_routing_async_does_not_block_runtime() {
+    let server = StubServer::new(|_| StubResponse::new(200).body("alt"));
+    let (called_sender, called) = mpsc::channel();
+    let (ready, ready_receiver) = mpsc::channel();
+    let resolver = BlockingResolver {
+        hosts: vec![server.url()],
+        called: Mutex::new(called_sender),
+        ready: Mutex::new(ready_receiver),
+    };
+    let client = ClientBuilder::new()
+        .base_url(&unreachable_url())
+        .allow_http()
+        .alternative_routing(AltRouting::new(resolver))
+        .build::<ClientASync>()
+        .unwrap();
+
+    // The single runtime thread only gets to run this task while the resolver waits if the
+    // resolver does not block it.
+    tokio::spawn(async move {
+        while called.try_recv().is_err() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        ready.send(()).unwrap();
+    });
+    assert_eq!(request().do_async(&client).await.unwrap(), "alt");
+}
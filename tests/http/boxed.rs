@@ -0,0 +1,75 @@
+use crate::utils::{ClientASync, ClientSync, StubResponse, StubServer};
+use proton_api_rs::http::{
+    BoxSequence, Error, Method, OwnedRequest, RequestData, Sequence, StringResponse,
+};
+
+fn echo_server() -> StubServer {
+    StubServer::new(|req| StubResponse::new(200).body(req.path.clone()))
+}
+
+fn get(path: &str) -> OwnedRequest<StringResponse> {
+    OwnedRequest::new(RequestData::new(Method::Get, path))
+}
+
+/// Command which builds a sequence for the client `C`.
+trait Command<C> {
+    fn sequence(&self) -> BoxSequence<'_, usize, Error, C>;
+}
+
+struct Length(&'static str);
+
+impl<C> Command<C> for Length {
+    fn sequence(&self) -> BoxSequence<'_, usize, Error, C> {
+        get(self.0).map(|body| Ok::<_, Error>(body.len())).boxed()
+    }
+}
+
+struct TotalLength(Vec<&'static str>);
+
+impl<C> Command<C> for TotalLength {
+    fn sequence(&self) -> BoxSequence<'_, usize, Error, C> {
+        let lengths = self
+            .0
+            .iter()
+            .map(|path| get(path).map(|body| Ok::<_, Error>(body.len())));
+        proton_api_rs::http::join_all(lengths)
+            .map(|lengths| Ok::<_, Error>(lengths.iter().sum()))
+            .boxed()
+    }
+}
+
+#[test]
+fn boxed_sequences_sync() {
+    let server = echo_server();
+    let client = server.client_builder().build::<ClientSync>().unwrap();
+
+    let sequences: Vec<BoxSequence<String, Error, ClientSync>> = vec![
+        get("tests/a").boxed(),
+        get("tests/b")
+            .chain(|_| Ok(get("tests/c")))
+            .map(|body| Ok::<_, Error>(body.to_uppercase()))
+            .boxed(),
+    ];
+    let outputs = sequences
+        .into_iter()
+        .map(|s| s.do_sync(&client).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(outputs, ["/tests/a", "/TESTS/C"]);
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn boxed_sequences_from_trait_async() {
+    let server = echo_server();
+    let client = server.client_builder().build::<ClientASync>().unwrap();
+
+    let commands: Vec<Box<dyn Command<ClientASync>>> = vec![
+        Box::new(Length("tests/a")),
+        Box::new(TotalLength(vec!["tests/bb", "tests/ccc"])),
+    ];
+    let mut outputs = vec![];
+    for command in &commands {
+        outputs.push(command.sequence().do_async(&client).await.unwrap());
+    }
+    assert_eq!(outputs, [8, 19]);
+}
@@ -0,0 +1,89 @@
+use crate::utils::{ClientASync, ClientSync, StubRequest, StubResponse, StubServer};
+use proton_api_rs::http::{
+    HttpCache, JsonResponse, MemoryCacheStore, Method, OwnedRequest, RequestData, Sequence,
+};
+use serde::Deserialize;
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Labels {
+    #[serde(rename = "Labels")]
+    labels: Vec<String>,
+}
+
+/// Server which answers 304 when the client already has the current version of the labels.
+fn etag_server() -> StubServer {
+    StubServer::new(|request: &StubRequest| {
+        if request.header("If-None-Match") == Some("\"v1\"") {
+            return StubResponse::new(304).header("ETag", "\"v1\"");
+        }
+        StubResponse::json(200, serde_json::json!({"Code": 1000, "Labels": ["Inbox"]}))
+            .header("ETag", "\"v1\"")
+    })
+}
+
+fn request() -> OwnedRequest<JsonResponse<Labels>> {
+    OwnedRequest::new(RequestData::new(Method::Get, "core/v4/labels"))
+}
+
+fn cache() -> HttpCache {
+    HttpCache::new(MemoryCacheStore::new(1024 * 1024))
+}
+
+fn assert_revalidated(server: &StubServer) {
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].header("If-None-Match"), None);
+    assert_eq!(requests[1].header("If-None-Match"), Some("\"v1\""));
+}
+
+#[test]
+fn not_modified_is_served_from_cache_sync() {
+    let server = etag_server();
+    let client = server
+        .client_builder()
+        .http_cache(cache())
+        .build::<ClientSync>()
+        .unwrap();
+
+    let first = request().do_sync(&client).unwrap();
+    let second = request().do_sync(&client).unwrap();
+    assert_eq!(first, second);
+    assert_eq!(second.labels, vec!["Inbox".to_string()]);
+    assert_revalidated(&server);
+}
+
+#[tokio::test]
+async fn not_modified_is_served_from_cache_async() {
+    let server = etag_server();
+    let client = server
+        .client_builder()
+        .http_cache(cache())
+        .build::<ClientASync>()
+        .unwrap();
+
+    let first = request().do_async(&client).await.unwrap();
+    let second = request().do_async(&client).await.unwrap();
+    assert_eq!(first, second);
+    assert_revalidated(&server);
+}
+
+#[test]
+fn no_store_responses_are_not_cached() {
+    let server = StubServer::new(|_| {
+        StubResponse::json(200, serde_json::json!({"Code": 1000, "Labels": []}))
+            .header("ETag", "\"v1\"")
+            .header("Cache-Control", "no-store")
+    });
+    let client = server
+        .client_builder()
+        .http_cache(cache())
+        .build::<ClientSync>()
+        .unwrap();
+
+    request().do_sync(&client).unwrap();
+    request().do_sync(&client).unwrap();
+    assert!(server
+        .requests()
+        .iter()
+        .all(|r| r.header("If-None-Match").is_none()));
+}
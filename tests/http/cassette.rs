@@ -0,0 +1,72 @@
+use crate::utils::{ClientASync, ClientSync, StubResponse, StubServer};
+use proton_api_rs::http::cassette::{RecordedBody, RecordingClient};
+use proton_api_rs::http::{Method, OwnedRequest, RequestData, Sequence, StreamingResponse};
+
+fn attachment() -> Vec<u8> {
+    (0..64 * 1024).map(|i| (i % 251) as u8).collect()
+}
+
+fn attachment_server() -> StubServer {
+    StubServer::new(|_| StubResponse::new(200).body(attachment()))
+}
+
+fn download() -> OwnedRequest<StreamingResponse> {
+    OwnedRequest::new(RequestData::new(Method::Get, "mail/v4/attachments/att-id"))
+}
+
+#[test]
+fn record_and_replay_download_sync() {
+    let server = attachment_server();
+    let client = server
+        .client_builder()
+        .build::<RecordingClient<ClientSync>>()
+        .unwrap();
+
+    let mut output = vec![];
+    download()
+        .write_to(&mut output, |_| {})
+        .do_sync(&client)
+        .unwrap();
+    assert_eq!(output, attachment());
+
+    let cassette = client.cassette();
+    assert!(matches!(
+        cassette.interactions()[0].response.body,
+        Some(RecordedBody::Base64(_))
+    ));
+
+    let replay = cassette.replay();
+    let mut replayed = vec![];
+    let written = download()
+        .write_to(&mut replayed, |_| {})
+        .do_sync(&replay)
+        .unwrap();
+    assert_eq!(written, attachment().len() as u64);
+    assert_eq!(replayed, attachment());
+}
+
+#[tokio::test]
+async fn record_and_replay_download_async() {
+    let server = attachment_server();
+    let client = server
+        .client_builder()
+        .build::<RecordingClient<ClientASync>>()
+        .unwrap();
+
+    let mut output = vec![];
+    download()
+        .write_to(&mut output, |_| {})
+        .do_async(&client)
+        .await
+        .unwrap();
+    assert_eq!(output, attachment());
+
+    let replay = client.cassette().replay();
+    let mut replayed = vec![];
+    download()
+        .write_to(&mut replayed, |_| {})
+        .do_async(&replay)
+        .await
+        .unwrap();
+    assert_eq!(replayed, attachment());
+}
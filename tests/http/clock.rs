@@ -0,0 +1,91 @@
+use crate::utils::{ClientASync, ClientSync, StubResponse, StubServer};
+use proton_api_rs::http::{
+    ClientRequestBuilder, Error, Method, OwnedRequest, RequestData, Sequence, ServerClock,
+    StringResponse,
+};
+use std::time::{Duration, SystemTime};
+
+const SKEW: Duration = Duration::from_secs(3600);
+
+/// Server whose clock is one hour ahead of the local clock.
+fn ahead_server() -> StubServer {
+    StubServer::new(|_| {
+        let date = httpdate::fmt_http_date(SystemTime::now() + SKEW);
+        StubResponse::new(200).header("Date", &date).body("ok")
+    })
+}
+
+fn request() -> OwnedRequest<StringResponse> {
+    OwnedRequest::new(RequestData::new(Method::Get, "tests/ping"))
+}
+
+fn assert_ahead(server_now: SystemTime) {
+    let offset = server_now.duration_since(SystemTime::now()).unwrap();
+    assert!(offset > SKEW - Duration::from_secs(2) && offset <= SKEW + Duration::from_secs(1));
+}
+
+#[test]
+fn server_clock_sync() {
+    let server = ahead_server();
+    let client = server.client_builder().build::<ClientSync>().unwrap();
+    assert_eq!(client.server_clock().unwrap().offset_millis(), None);
+
+    request().do_sync(&client).unwrap();
+    assert_ahead(client.server_now());
+}
+
+#[test]
+fn server_clock_per_client() {
+    let server = ahead_server();
+    let builder = server.client_builder();
+    let client = builder.clone().build::<ClientSync>().unwrap();
+    let other = builder.build::<ClientSync>().unwrap();
+
+    request().do_sync(&client).unwrap();
+    assert!(client.server_clock().unwrap().offset_millis().is_some());
+    assert_eq!(other.server_clock().unwrap().offset_millis(), None);
+}
+
+#[test]
+fn server_clock_shared_when_set() {
+    let server = ahead_server();
+    let clock = ServerClock::new();
+    let builder = server.client_builder().server_clock(clock.clone());
+    let client = builder.clone().build::<ClientSync>().unwrap();
+    let other = builder.build::<ClientSync>().unwrap();
+
+    request().do_sync(&client).unwrap();
+    assert_ahead(clock.now());
+    assert_ahead(other.server_now());
+}
+
+#[tokio::test]
+async fn server_clock_async() {
+    let server = ahead_server();
+    let client = server.client_builder().build::<ClientASync>().unwrap();
+
+    request().do_async(&client).await.unwrap();
+    assert_ahead(client.server_now());
+}
+
+#[test]
+fn retry_after_date_uses_server_clock() {
+    // The server clock is one hour behind, so the Retry-After date is in the local past.
+    let server = StubServer::new(|_| {
+        let now = SystemTime::now() - SKEW;
+        StubResponse::json(503, serde_json::json!({"Code": 503}))
+            .header("Date", &httpdate::fmt_http_date(now))
+            .header(
+                "Retry-After",
+                &httpdate::fmt_http_date(now + Duration::from_secs(5)),
+            )
+    });
+    let client = server.client_builder().build::<ClientSync>().unwrap();
+
+    let Err(Error::API(e)) = request().do_sync(&client) else {
+        panic!("expected an API error")
+    };
+    assert_eq!(e.retry_after, Some(Duration::from_secs(5)));
+    let offset = client.server_clock().unwrap().offset_millis().unwrap();
+    assert!(offset < -(SKEW.as_millis() as i64) + 2000);
+}
@@ -0,0 +1,111 @@
+use crate::utils::{ClientASync, ClientSync, StubResponse, StubServer};
+use proton_api_rs::http::{
+    CancellationToken, Error, Method, OwnedRequest, RequestData, RetryPolicy, Sequence,
+    StringResponse,
+};
+use std::time::{Duration, Instant};
+
+/// Server which takes two seconds to answer.
+fn slow_server() -> StubServer {
+    StubServer::new(|_| {
+        std::thread::sleep(Duration::from_secs(2));
+        StubResponse::new(200).body("ok")
+    })
+}
+
+fn request(data: RequestData) -> OwnedRequest<StringResponse> {
+    OwnedRequest::new(data)
+}
+
+fn ping() -> RequestData {
+    RequestData::new(Method::Get, "tests/ping")
+}
+
+#[test]
+fn request_timeout_sync() {
+    let server = slow_server();
+    let client = server.client_builder().build::<ClientSync>().unwrap();
+
+    let start = Instant::now();
+    let result = request(ping().timeout(Duration::from_millis(200))).do_sync(&client);
+    assert!(matches!(result, Err(Error::Timeout(_))), "{result:?}");
+    assert!(start.elapsed() < Duration::from_millis(1500));
+}
+
+#[tokio::test]
+async fn request_timeout_async() {
+    let server = slow_server();
+    let client = server.client_builder().build::<ClientASync>().unwrap();
+
+    let start = Instant::now();
+    let result = request(ping().timeout(Duration::from_millis(200)))
+        .do_async(&client)
+        .await;
+    assert!(matches!(result, Err(Error::Timeout(_))), "{result:?}");
+    assert!(start.elapsed() < Duration::from_millis(1500));
+}
+
+#[tokio::test]
+async fn cancel_in_flight_async() {
+    let server = slow_server();
+    let client = server.client_builder().build::<ClientASync>().unwrap();
+    let token = CancellationToken::new();
+
+    let canceller = token.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        canceller.cancel();
+    });
+
+    let start = Instant::now();
+    let result = request(ping())
+        .with_cancellation(token)
+        .do_async(&client)
+        .await;
+    assert!(matches!(result, Err(Error::Cancelled)), "{result:?}");
+    assert!(start.elapsed() < Duration::from_millis(1500));
+}
+
+#[test]
+fn cancelled_before_sending_sync() {
+    let server = slow_server();
+    let client = server.client_builder().build::<ClientSync>().unwrap();
+    let token = CancellationToken::new();
+    token.cancel();
+
+    let result = request(ping().cancellation_token(token)).do_sync(&client);
+    assert!(matches!(result, Err(Error::Cancelled)), "{result:?}");
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn deadline_stops_retries_sync() {
+    let server = StubServer::new(|_| {
+        StubResponse::json(503, serde_json::json!({"Code": 503})).header("Retry-After", "1")
+    });
+    let client = server
+        .client_builder()
+        .retry_policy(RetryPolicy::new().max_attempts(5))
+        .build::<ClientSync>()
+        .unwrap();
+
+    let start = Instant::now();
+    let result = request(ping())
+        .with_deadline(Duration::from_millis(500))
+        .do_sync(&client);
+    assert!(matches!(result, Err(Error::Timeout(_))), "{result:?}");
+    assert!(start.elapsed() < Duration::from_millis(900));
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn deadline_starts_when_polled_async() {
+    let server = StubServer::new(|_| StubResponse::new(200).body("ok"));
+    let client = server.client_builder().build::<ClientASync>().unwrap();
+
+    let future = request(ping())
+        .with_deadline(Duration::from_millis(300))
+        .do_async(&client);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(future.await.unwrap(), "ok");
+}
@@ -0,0 +1,123 @@
+use crate::utils::{ClientASync, ClientSync, StubResponse, StubServer};
+use proton_api_rs::http::{
+    AppVersion, ClientBuilder, Error, Method, OwnedRequest, RequestData, Sequence, StringResponse,
+};
+
+/// Server which only accepts app versions from 2.0.0 on.
+fn version_server() -> StubServer {
+    StubServer::new(|r| {
+        let version = r
+            .header("X-Pm-Appversion")
+            .and_then(|v| v.parse::<AppVersion>().ok());
+        match version {
+            Some(v) if v.semver() >= (2, 0, 0) => StubResponse::new(200).body("ok"),
+            _ => StubResponse::json(
+                400,
+                serde_json::json!({"Code": 5003, "Error": "This version of the app is no longer supported"}),
+            ),
+        }
+    })
+}
+
+fn request() -> OwnedRequest<StringResponse> {
+    OwnedRequest::new(RequestData::new(Method::Get, "tests/ping"))
+}
+
+fn identity(builder: ClientBuilder, version: AppVersion) -> ClientBuilder {
+    builder
+        .with_app_version(version)
+        .locale("de_CH")
+        .timezone("Europe/Zurich")
+}
+
+#[test]
+fn identity_headers_sync() {
+    let server = version_server();
+    let version = AppVersion::new("linux", "test", 2, 1, 0);
+    let client = identity(server.client_builder(), version)
+        .build::<ClientSync>()
+        .unwrap();
+
+    assert_eq!(request().do_sync(&client).unwrap(), "ok");
+    let localized = RequestData::new(Method::Get, "tests/ping").header("Accept-Language", "fr");
+    OwnedRequest::<StringResponse>::new(localized)
+        .do_sync(&client)
+        .unwrap();
+
+    let requests = server.requests();
+    assert_eq!(
+        requests[0].header("X-Pm-Appversion"),
+        Some("linux-test@2.1.0")
+    );
+    assert_eq!(requests[0].header("Accept-Language"), Some("de-CH"));
+    assert_eq!(requests[0].header("X-Pm-Locale"), Some("de_CH"));
+    assert_eq!(requests[0].header("X-Pm-Timezone"), Some("Europe/Zurich"));
+    assert_eq!(requests[1].header("Accept-Language"), Some("fr"));
+}
+
+#[tokio::test]
+async fn identity_headers_async() {
+    let server = version_server();
+    let version = AppVersion::new("linux", "test", 2, 0, 0).pre_release("beta.1");
+    let client = identity(server.client_builder(), version)
+        .build::<ClientASync>()
+        .unwrap();
+
+    assert_eq!(request().do_async(&client).await.unwrap(), "ok");
+
+    let requests = server.requests();
+    assert_eq!(
+        requests[0].header("X-Pm-Appversion"),
+        Some("linux-test@2.0.0-beta.1")
+    );
+    assert_eq!(requests[0].header("Accept-Language"), Some("de-CH"));
+    assert_eq!(requests[0].header("X-Pm-Timezone"), Some("Europe/Zurich"));
+}
+
+#[test]
+fn outdated_app_version() {
+    let server = version_server();
+    let builder = server.client_builder().app_version("linux-test@1.9.3");
+
+    let client = builder.clone().build::<ClientSync>().unwrap();
+    let Err(Error::UnsupportedAppVersion(e)) = request().do_sync(&client) else {
+        panic!("expected an unsupported app version error")
+    };
+    assert_eq!(e.api_code, 5003);
+
+    let client = builder.build::<ClientASync>().unwrap();
+    let result = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(request().do_async(&client));
+    assert!(matches!(result, Err(Error::UnsupportedAppVersion(_))));
+}
+
+#[test]
+fn unchecked_app_version_is_sent() {
+    let server = StubServer::new(|_| StubResponse::new(200).body("ok"));
+    let client = server
+        .client_builder()
+        .app_version_unchecked("MyApp@0.1.1")
+        .build::<ClientSync>()
+        .unwrap();
+
+    request().do_sync(&client).unwrap();
+    assert_eq!(
+        server.requests()[0].header("X-Pm-Appversion"),
+        Some("MyApp@0.1.1")
+    );
+}
+
+#[test]
+fn invalid_identity_fails_build() {
+    for builder in [
+        ClientBuilder::new().app_version("MyApp@0.1.1"),
+        ClientBuilder::new().app_version_unchecked("MyApp@0.1.1\r\nX-Injected: 1"),
+        ClientBuilder::new().app_version_unchecked(""),
+        ClientBuilder::new().locale("en US"),
+        ClientBuilder::new().timezone("Europe/Zurich\r\nX-Injected: 1"),
+    ] {
+        assert!(builder.clone().build::<ClientSync>().is_err());
+        assert!(builder.build::<ClientASync>().is_err());
+    }
+}
@@ -0,0 +1,112 @@
+use crate::utils::{ClientASync, ClientSync, StubResponse, StubServer};
+use proton_api_rs::http::{join_all, Method, OwnedRequest, RequestData, Sequence, StringResponse};
+use std::time::{Duration, Instant};
+
+/// Server which answers with the request path after `delay`, or with a 404 for `tests/fail`.
+fn echo_server(delay: Duration) -> StubServer {
+    StubServer::new(move |req| {
+        std::thread::sleep(delay);
+        if req.path.ends_with("/fail") {
+            StubResponse::json(404, serde_json::json!({"Code": 2501, "Error": "Not found"}))
+        } else {
+            StubResponse::new(200).body(req.path.clone())
+        }
+    })
+}
+
+fn get(path: &str) -> OwnedRequest<StringResponse> {
+    OwnedRequest::new(RequestData::new(Method::Get, path))
+}
+
+fn paths(server: &StubServer) -> Vec<String> {
+    server.requests().into_iter().map(|r| r.path).collect()
+}
+
+#[test]
+fn join_sync_runs_in_order() {
+    let server = echo_server(Duration::ZERO);
+    let client = server.client_builder().build::<ClientSync>().unwrap();
+
+    let (a, b) = get("tests/a")
+        .join(get("tests/b"))
+        .do_sync(&client)
+        .unwrap();
+    assert_eq!((a.as_str(), b.as_str()), ("/tests/a", "/tests/b"));
+    assert_eq!(paths(&server), ["/tests/a", "/tests/b"]);
+}
+
+#[tokio::test]
+async fn join_all_async_runs_concurrently() {
+    let server = echo_server(Duration::from_millis(500));
+    let client = server.client_builder().build::<ClientASync>().unwrap();
+
+    let start = Instant::now();
+    let outputs = join_all(["tests/a", "tests/b", "tests/c", "tests/d"].map(get))
+        .do_async(&client)
+        .await
+        .unwrap();
+    assert_eq!(outputs, ["/tests/a", "/tests/b", "/tests/c", "/tests/d"]);
+    assert!(start.elapsed() < Duration::from_millis(1500));
+}
+
+#[test]
+fn join_all_sync_fails_fast() {
+    let server = echo_server(Duration::ZERO);
+    let client = server.client_builder().build::<ClientSync>().unwrap();
+
+    let result = join_all(["tests/a", "tests/fail", "tests/b"].map(get)).do_sync(&client);
+    assert!(result.is_err(), "{result:?}");
+    assert_eq!(paths(&server), ["/tests/a", "/tests/fail"]);
+}
+
+#[tokio::test]
+async fn join_async_fails_fast() {
+    let server = StubServer::new(|req| {
+        if req.path.ends_with("/fail") {
+            StubResponse::json(404, serde_json::json!({"Code": 2501}))
+        } else {
+            std::thread::sleep(Duration::from_secs(2));
+            StubResponse::new(200).body("ok")
+        }
+    });
+    let client = server.client_builder().build::<ClientASync>().unwrap();
+
+    let start = Instant::now();
+    let result = get("tests/slow")
+        .join(get("tests/fail"))
+        .do_async(&client)
+        .await;
+    assert!(result.is_err(), "{result:?}");
+    assert!(start.elapsed() < Duration::from_millis(1500));
+}
+
+#[test]
+fn join_all_sync_collects_errors() {
+    let server = echo_server(Duration::ZERO);
+    let client = server.client_builder().build::<ClientSync>().unwrap();
+
+    let results = join_all(["tests/a", "tests/fail", "tests/b"].map(get))
+        .collect_errors()
+        .do_sync(&client)
+        .unwrap();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].as_deref().unwrap(), "/tests/a");
+    assert!(results[1].is_err());
+    assert_eq!(results[2].as_deref().unwrap(), "/tests/b");
+}
+
+#[tokio::test]
+async fn join_async_collects_errors() {
+    let server = echo_server(Duration::from_millis(100));
+    let client = server.client_builder().build::<ClientASync>().unwrap();
+
+    let (a, b) = get("tests/fail")
+        .join(get("tests/a"))
+        .collect_errors()
+        .do_async(&client)
+        .await
+        .unwrap();
+    assert!(a.is_err());
+    assert_eq!(b.unwrap(), "/tests/a");
+    assert_eq!(server.requests().len(), 2);
+}
@@ -0,0 +1,23 @@
+mod alt_routing;
+mod boxed;
+mod cassette;
+mod cache;
+mod clock;
+mod deadline;
+mod identity;
+mod join;
+mod metrics;
+mod middleware;
+mod multipart;
+mod pagination;
+mod pinning;
+mod pooling;
+mod rate_limit;
+mod response_meta;
+mod retry;
+#[cfg(feature = "tracing")]
+mod spans;
+mod spawn;
+mod streaming;
+mod tls;
+mod utils;
@@ -0,0 +1,92 @@
+use crate::utils::{ClientASync, ClientSync, StubResponse, StubServer};
+use proton_api_rs::http::{
+    Histogram, InMemoryMetrics, Method, MetricsSnapshot, OwnedRequest, RequestData, Sequence,
+    StringResponse,
+};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+fn server() -> StubServer {
+    StubServer::new(|request| match request.method.as_str() {
+        "GET" => StubResponse::new(200).body("0123456789"),
+        _ => StubResponse::json(422, json!({"Code": 2001, "Error": "Invalid"})),
+    })
+}
+
+fn users() -> OwnedRequest<StringResponse> {
+    OwnedRequest::new(RequestData::new(Method::Get, "core/v4/users"))
+}
+
+fn send() -> OwnedRequest<StringResponse> {
+    OwnedRequest::new(
+        RequestData::new(Method::Post, "mail/v4/messages/AbC-12==/send").bytes(vec![0; 5]),
+    )
+}
+
+/// Remove the durations, which differ between runs.
+fn without_durations(mut snapshot: MetricsSnapshot) -> MetricsSnapshot {
+    for endpoint in &mut snapshot.endpoints {
+        assert_eq!(endpoint.duration.count, endpoint.requests);
+        endpoint.duration = Histogram {
+            buckets: vec![],
+            count: 0,
+            sum: Duration::ZERO,
+        };
+    }
+    snapshot
+}
+
+fn check(snapshot: &MetricsSnapshot) {
+    let users = snapshot.endpoint("core/v4/users", Method::Get).unwrap();
+    assert_eq!(users.requests, 2);
+    assert_eq!(users.statuses, BTreeMap::from([(200, 2)]));
+    assert_eq!(users.response_bytes, 20);
+    assert!(users.errors.is_empty());
+
+    let send = snapshot
+        .endpoint("mail/v4/messages/{id}/send", Method::Post)
+        .unwrap();
+    assert_eq!(send.requests, 1);
+    assert_eq!(send.statuses, BTreeMap::from([(422, 1)]));
+    assert_eq!(send.api_codes, BTreeMap::from([(2001, 1)]));
+    assert_eq!(send.errors, BTreeMap::from([("api", 1)]));
+    assert_eq!(send.request_bytes, 5);
+}
+
+#[tokio::test]
+async fn metrics_are_identical_for_both_clients() {
+    let server = server();
+
+    let sync_metrics = InMemoryMetrics::new();
+    let client = server
+        .client_builder()
+        .metrics_observer(sync_metrics.clone())
+        .build::<ClientSync>()
+        .unwrap();
+    let sync_client = client.clone();
+    tokio::task::spawn_blocking(move || {
+        users().do_sync(&sync_client).unwrap();
+        users().do_sync(&sync_client).unwrap();
+        assert!(send().do_sync(&sync_client).is_err());
+    })
+    .await
+    .unwrap();
+
+    let async_metrics = InMemoryMetrics::new();
+    let client = server
+        .client_builder()
+        .metrics_observer(async_metrics.clone())
+        .build::<ClientASync>()
+        .unwrap();
+    users().do_async(&client).await.unwrap();
+    users().do_async(&client).await.unwrap();
+    assert!(send().do_async(&client).await.is_err());
+
+    check(&sync_metrics.snapshot());
+    check(&async_metrics.snapshot());
+    assert_eq!(
+        without_durations(sync_metrics.snapshot()),
+        without_durations(async_metrics.snapshot())
+    );
+}
@@ -0,0 +1,134 @@
+use crate::utils::{ClientASync, ClientSync, StubResponse, StubServer};
+use proton_api_rs::http::{
+    Error, Method, Middleware, OwnedRequest, RequestData, ResponseMeta, Sequence, StringResponse,
+};
+use std::sync::{Arc, Mutex};
+
+#[derive(Default, Clone)]
+struct Recorder {
+    responses: Arc<Mutex<Vec<(u16, String)>>>,
+}
+
+impl Middleware for Recorder {
+    fn on_request(&self, request: &mut RequestData) -> proton_api_rs::http::Result<()> {
+        request
+            .headers_mut()
+            .insert("X-Gateway".to_string(), "corp".to_string());
+        Ok(())
+    }
+
+    fn on_response(
+        &self,
+        _: &RequestData,
+        response: &ResponseMeta,
+        body: &[u8],
+    ) -> proton_api_rs::http::Result<()> {
+        self.responses
+            .lock()
+            .unwrap()
+            .push((response.status(), String::from_utf8_lossy(body).to_string()));
+        Ok(())
+    }
+}
+
+struct Deny;
+
+impl Middleware for Deny {
+    fn on_request(&self, _: &mut RequestData) -> proton_api_rs::http::Result<()> {
+        Err(Error::Other(anyhow::anyhow!("denied")))
+    }
+}
+
+fn echo_server() -> StubServer {
+    StubServer::new(|r| {
+        if r.path == "/missing" {
+            return StubResponse::json(404, serde_json::json!({"Code": 2501}));
+        }
+        StubResponse::new(200).body(r.header("X-Gateway").unwrap_or_default().to_string())
+    })
+}
+
+fn request(url: &str) -> OwnedRequest<StringResponse> {
+    OwnedRequest::new(RequestData::new(Method::Get, url))
+}
+
+#[test]
+fn middleware_sync() {
+    let server = echo_server();
+    let recorder = Recorder::default();
+    let client = server
+        .client_builder()
+        .app_version("linux-test@1.0.0")
+        .middleware(recorder.clone())
+        .build::<ClientSync>()
+        .unwrap();
+
+    assert_eq!(request("ping").do_sync(&client).unwrap(), "corp");
+    assert!(request("missing").do_sync(&client).is_err());
+
+    let requests = server.requests();
+    assert_eq!(
+        requests[0].header("X-Pm-Appversion"),
+        Some("linux-test@1.0.0")
+    );
+    assert_eq!(
+        *recorder.responses.lock().unwrap(),
+        vec![
+            (200, "corp".to_string()),
+            (404, r#"{"Code":2501}"#.to_string())
+        ]
+    );
+}
+
+#[tokio::test]
+async fn middleware_async() {
+    let server = echo_server();
+    let recorder = Recorder::default();
+    let client = server
+        .client_builder()
+        .app_version("linux-test@1.0.0")
+        .middleware(recorder.clone())
+        .build::<ClientASync>()
+        .unwrap();
+
+    assert_eq!(request("ping").do_async(&client).await.unwrap(), "corp");
+    assert!(request("missing").do_async(&client).await.is_err());
+
+    let requests = server.requests();
+    assert_eq!(
+        requests[0].header("X-Pm-Appversion"),
+        Some("linux-test@1.0.0")
+    );
+    assert_eq!(
+        *recorder.responses.lock().unwrap(),
+        vec![
+            (200, "corp".to_string()),
+            (404, r#"{"Code":2501}"#.to_string())
+        ]
+    );
+}
+
+#[tokio::test]
+async fn middleware_error_aborts_request() {
+    let server = echo_server();
+    let sync_client = server
+        .client_builder()
+        .middleware(Deny)
+        .build::<ClientSync>()
+        .unwrap();
+    let async_client = server
+        .client_builder()
+        .middleware(Deny)
+        .build::<ClientASync>()
+        .unwrap();
+
+    assert!(matches!(
+        request("ping").do_sync(&sync_client),
+        Err(Error::Other(_))
+    ));
+    assert!(matches!(
+        request("ping").do_async(&async_client).await,
+        Err(Error::Other(_))
+    ));
+    assert!(server.requests().is_empty());
+}
@@ -0,0 +1,122 @@
+use crate::utils::{ClientASync, ClientSync, StubResponse, StubServer};
+use proton_api_rs::http::{
+    BinaryResponse, Method, Multipart, OwnedRequest, Part, RequestData, Sequence,
+};
+
+/// Part of a parsed multipart body: the part headers and the part content.
+type ParsedPart = (Vec<String>, Vec<u8>);
+
+fn echo_server() -> StubServer {
+    StubServer::new(|r| StubResponse::new(200).body(r.body.clone()))
+}
+
+fn request(file: &std::path::Path) -> OwnedRequest<BinaryResponse> {
+    let attachment: Vec<u8> = (0..200_000).map(|i| (i % 256) as u8).collect();
+    OwnedRequest::new(
+        RequestData::new(Method::Post, "mail/v4/attachments").multipart(
+            Multipart::new()
+                .text("MessageID", "message-id")
+                .part(
+                    "KeyPackets",
+                    Part::bytes(vec![1, 2, 3]).content_type("application/octet-stream"),
+                )
+                .part(
+                    "DataPacket",
+                    Part::reader(std::io::Cursor::new(attachment))
+                        .file_name("blob")
+                        .content_type("application/octet-stream"),
+                )
+                .part("Signature", Part::file(file).content_type("text/plain")),
+        ),
+    )
+}
+
+fn boundary(content_type: &str) -> String {
+    let (mime, boundary) = content_type.split_once("; boundary=").unwrap();
+    assert_eq!(mime, "multipart/form-data");
+    boundary.trim_matches('"').to_string()
+}
+
+fn parse(body: &[u8], boundary: &str) -> Vec<ParsedPart> {
+    let delimiter = format!("--{boundary}");
+    let close = format!("{delimiter}--\r\n");
+    assert!(body.ends_with(close.as_bytes()));
+
+    let body = &body[..body.len() - close.len()];
+    split(body, format!("{delimiter}\r\n").as_bytes())
+        .into_iter()
+        .filter(|p| !p.is_empty())
+        .map(|part| {
+            let end = find(part, b"\r\n\r\n").unwrap();
+            let headers = String::from_utf8(part[..end].to_vec()).unwrap();
+            let mut headers = headers
+                .split("\r\n")
+                .map(|h| {
+                    let (name, value) = h.split_once(": ").unwrap();
+                    format!("{}: {value}", name.to_lowercase())
+                })
+                .collect::<Vec<_>>();
+            headers.sort();
+            let content = part[end + 4..].strip_suffix(b"\r\n").unwrap().to_vec();
+            (headers, content)
+        })
+        .collect()
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn split<'a>(mut data: &'a [u8], delimiter: &[u8]) -> Vec<&'a [u8]> {
+    let mut parts = vec![];
+    while let Some(pos) = find(data, delimiter) {
+        parts.push(&data[..pos]);
+        data = &data[pos + delimiter.len()..];
+    }
+    parts.push(data);
+    parts
+}
+
+#[tokio::test]
+async fn multipart_round_trip() {
+    let file = std::env::temp_dir().join(format!("multipart-{}.txt", std::process::id()));
+    std::fs::write(&file, "signature").unwrap();
+
+    let server = echo_server();
+    let sync_client = server.client_builder().build::<ClientSync>().unwrap();
+    let async_client = server.client_builder().build::<ClientASync>().unwrap();
+
+    let sync_body = request(&file).do_sync(&sync_client).unwrap();
+    let async_body = request(&file).do_async(&async_client).await.unwrap();
+    std::fs::remove_file(&file).unwrap();
+
+    let requests = server.requests();
+    let sync_parts = parse(
+        &sync_body,
+        &boundary(requests[0].header("Content-Type").unwrap()),
+    );
+    let async_parts = parse(
+        &async_body,
+        &boundary(requests[1].header("Content-Type").unwrap()),
+    );
+
+    assert_eq!(sync_parts.len(), 4);
+    assert_eq!(sync_parts, async_parts);
+    assert_eq!(
+        sync_parts[0],
+        (
+            vec!["content-disposition: form-data; name=\"MessageID\"".to_string()],
+            b"message-id".to_vec()
+        )
+    );
+    assert_eq!(sync_parts[2].1.len(), 200_000);
+    assert_eq!(
+        sync_parts[3].0,
+        vec![
+            "content-disposition: form-data; name=\"Signature\"; filename=\"multipart-{}.txt\""
+                .replace("{}", &std::process::id().to_string()),
+            "content-type: text/plain".to_string(),
+        ]
+    );
+    assert_eq!(sync_parts[3].1, b"signature");
+}
@@ -0,0 +1,167 @@
+use crate::utils::{ClientASync, ClientSync, StubRequest, StubResponse, StubServer};
+use futures_util::StreamExt;
+use proton_api_rs::domain::{MessageFilter, UserUid};
+use proton_api_rs::http::{ClientAsync, Sequence};
+use proton_api_rs::Session;
+use serde_json::json;
+use std::time::Duration;
+
+const MESSAGES: [&str; 7] = ["m0", "m1", "m2", "m3", "m4", "m5", "m6"];
+
+/// Server listing `MESSAGES` with `EndID` paging, which answers message listings after
+/// `delay`.
+fn mail_server(delay: Duration) -> StubServer {
+    StubServer::new(move |request: &StubRequest| {
+        let (path, query) = request.path.split_once('?').unwrap_or((&request.path, ""));
+        match path {
+            "/auth/v4/refresh" => StubResponse::json(
+                200,
+                json!({
+                    "UID": "uid",
+                    "AccessToken": "access",
+                    "RefreshToken": "refresh",
+                    "Scope": "full",
+                }),
+            ),
+            "/mail/v4/messages" => {
+                std::thread::sleep(delay);
+                let param = |name: &str| {
+                    query
+                        .split('&')
+                        .find_map(|kv| kv.strip_prefix(name)?.strip_prefix('='))
+                };
+                let page_size = param("PageSize").map_or(50, |v| v.parse().unwrap());
+                let start = param("EndID")
+                    .map_or(0, |id| MESSAGES.iter().position(|m| *m == id).unwrap() + 1);
+                let messages = MESSAGES
+                    .iter()
+                    .skip(start)
+                    .take(page_size)
+                    .map(|id| {
+                        json!({
+                            "ID": id,
+                            "ConversationID": "conversation",
+                            "AddressID": "address",
+                            "Subject": "",
+                            "Sender": {"Name": "", "Address": "foo@bar.com"},
+                            "Time": 0,
+                            "Size": 0,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                StubResponse::json(200, json!({"Messages": messages, "Total": MESSAGES.len()}))
+            }
+            _ => StubResponse::json(404, json!({"Code": 2501})),
+        }
+    })
+}
+
+fn listing_queries(server: &StubServer) -> Vec<String> {
+    server
+        .requests()
+        .into_iter()
+        .filter_map(|r| Some(r.path.strip_prefix("/mail/v4/messages?")?.to_string()))
+        .collect()
+}
+
+async fn session_async<T: ClientAsync>(client: &T) -> Session {
+    Session::refresh(&UserUid::from("uid"), "refresh")
+        .do_async(client)
+        .await
+        .unwrap()
+}
+
+#[test]
+fn iterate_messages_sync() {
+    let server = mail_server(Duration::ZERO);
+    let client = server.client_builder().build::<ClientSync>().unwrap();
+    let session = Session::refresh(&UserUid::from("uid"), "refresh")
+        .do_sync(&client)
+        .unwrap();
+
+    let ids = session
+        .messages(MessageFilter::new().with_label("0"))
+        .page_size(3)
+        .iter(&client)
+        .map(|m| m.unwrap().id.0)
+        .collect::<Vec<_>>();
+    assert_eq!(ids, MESSAGES);
+    assert_eq!(
+        listing_queries(&server),
+        [
+            "LabelID=0&PageSize=3",
+            "LabelID=0&PageSize=3&EndID=m2",
+            "LabelID=0&PageSize=3&EndID=m5",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn stream_messages_async() {
+    let server = mail_server(Duration::ZERO);
+    let client = server.client_builder().build::<ClientASync>().unwrap();
+    let session = session_async(&client).await;
+
+    let ids = session
+        .messages(MessageFilter::new())
+        .page_size(7)
+        .stream(&client)
+        .map(|m| m.unwrap().id.0)
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(ids, MESSAGES);
+    // The second page is empty since the first one was full.
+    assert_eq!(
+        listing_queries(&server),
+        ["PageSize=7", "PageSize=7&EndID=m6"]
+    );
+}
+
+#[tokio::test]
+async fn stream_messages_prefetch() {
+    let server = mail_server(Duration::from_millis(100));
+    let client = server.client_builder().build::<ClientASync>().unwrap();
+    let session = session_async(&client).await;
+
+    let mut stream = session
+        .messages(MessageFilter::new())
+        .page_size(4)
+        .prefetch(true)
+        .stream(&client);
+    assert_eq!(stream.next().await.unwrap().unwrap().id.0, "m0");
+
+    // The next page is requested while the first one is consumed.
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(listing_queries(&server).len(), 2);
+
+    let mut ids = vec!["m0".to_string()];
+    while let Some(message) = stream.next().await {
+        ids.push(message.unwrap().id.0);
+    }
+    assert_eq!(ids, MESSAGES);
+}
+
+#[test]
+fn iterate_messages_stops_on_error() {
+    let server = StubServer::new(|request: &StubRequest| {
+        if request.path.starts_with("/auth/v4/refresh") {
+            StubResponse::json(
+                200,
+                json!({"UID": "uid", "AccessToken": "a", "RefreshToken": "r", "Scope": "full"}),
+            )
+        } else {
+            StubResponse::json(422, json!({"Code": 2001, "Error": "Invalid filter"}))
+        }
+    });
+    let client = server.client_builder().build::<ClientSync>().unwrap();
+    let session = Session::refresh(&UserUid::from("uid"), "refresh")
+        .do_sync(&client)
+        .unwrap();
+
+    let results = session
+        .messages(MessageFilter::new())
+        .iter(&client)
+        .collect::<Vec<_>>();
+    assert_eq!(results.len(), 1);
+    assert!(results[0].is_err());
+}
@@ -0,0 +1,195 @@
+use crate::utils::{ClientASync, ClientSync, StubResponse, StubServer};
+use proton_api_rs::http::{
+    spki_pin, ClientBuilder, Error, Method, OwnedRequest, RequestData, Sequence, SpkiPins,
+    StreamingResponse, StringResponse,
+};
+
+/// Self-signed certificate for `localhost` and its pin.
+struct TestCertificate {
+    der: Vec<u8>,
+    pem: String,
+    key: Vec<u8>,
+    pin: String,
+}
+
+impl TestCertificate {
+    fn new() -> Self {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let der = cert.serialize_der().unwrap();
+        let pin = spki_pin(&der).unwrap();
+        Self {
+            der,
+            pem: cert.serialize_pem().unwrap(),
+            key: cert.serialize_private_key_der(),
+            pin,
+        }
+    }
+
+    fn server(&self) -> StubServer {
+        let config = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![rustls::Certificate(self.der.clone())],
+                rustls::PrivateKey(self.key.clone()),
+            )
+            .unwrap();
+        StubServer::with_tls(config, |_| StubResponse::new(200).body("pinned"))
+    }
+}
+
+/// Client builder which trusts the self-signed `cert` of `server`.
+fn builder(server: &StubServer, cert: &TestCertificate, pins: SpkiPins) -> ClientBuilder {
+    ClientBuilder::new()
+        .base_url(&server.url())
+        .root_certificate_pem(&cert.pem)
+        .spki_pins(pins)
+}
+
+fn request() -> OwnedRequest<StringResponse> {
+    OwnedRequest::new(RequestData::new(Method::Get, "tests/ping"))
+}
+
+#[test]
+fn spki_pin_matches_public_key() {
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let spki = cert.get_key_pair().public_key_der();
+    let expected = {
+        use base64::Engine;
+        let digest = ring::digest::digest(&ring::digest::SHA256, &spki);
+        base64::engine::general_purpose::STANDARD.encode(digest)
+    };
+
+    assert_eq!(spki_pin(&cert.serialize_der().unwrap()), Some(expected));
+    assert_eq!(spki_pin(b"not a certificate"), None);
+}
+
+#[test]
+fn pin_match_sync() {
+    let cert = TestCertificate::new();
+    let server = cert.server();
+    let client = builder(
+        &server,
+        &cert,
+        SpkiPins::new().pin("localhost", &[&cert.pin]),
+    )
+    .build::<ClientSync>()
+    .unwrap();
+
+    assert_eq!(request().do_sync(&client).unwrap(), "pinned");
+}
+
+#[tokio::test]
+async fn pin_match_async() {
+    let cert = TestCertificate::new();
+    let server = cert.server();
+    let client = builder(
+        &server,
+        &cert,
+        SpkiPins::new().pin("localhost", &[&cert.pin]),
+    )
+    .build::<ClientASync>()
+    .unwrap();
+
+    assert_eq!(request().do_async(&client).await.unwrap(), "pinned");
+}
+
+#[test]
+fn pin_mismatch_sync() {
+    let cert = TestCertificate::new();
+    let server = cert.server();
+    let other = TestCertificate::new();
+    let client = builder(
+        &server,
+        &cert,
+        SpkiPins::new().pin("localhost", &[&other.pin]),
+    )
+    .build::<ClientSync>()
+    .unwrap();
+
+    match request().do_sync(&client) {
+        Err(Error::PinMismatch(host)) => assert_eq!(host, "localhost"),
+        r => panic!("unexpected result {r:?}"),
+    }
+    assert!(server.requests().is_empty());
+}
+
+/// ureq wraps TLS failures in its own transport error, the pin mismatch must
+/// still be recognised when the response would be streamed.
+#[test]
+fn pin_mismatch_ureq_streaming() {
+    let cert = TestCertificate::new();
+    let server = cert.server();
+    let other = TestCertificate::new();
+    let client = builder(
+        &server,
+        &cert,
+        SpkiPins::new().pin("localhost", &[&other.pin]),
+    )
+    .build::<ClientSync>()
+    .unwrap();
+
+    let request =
+        OwnedRequest::<StreamingResponse>::new(RequestData::new(Method::Get, "tests/ping"));
+    match request.do_sync(&client) {
+        Err(Error::PinMismatch(host)) => assert_eq!(host, "localhost"),
+        Err(e) => panic!("unexpected error {e:?}"),
+        Ok(_) => panic!("pinned request succeeded"),
+    }
+    assert!(server.requests().is_empty());
+}
+
+#[tokio::test]
+async fn pin_mismatch_async() {
+    let cert = TestCertificate::new();
+    let server = cert.server();
+    let other = TestCertificate::new();
+    let client = builder(
+        &server,
+        &cert,
+        SpkiPins::new().pin("localhost", &[&other.pin]),
+    )
+    .build::<ClientASync>()
+    .unwrap();
+
+    match request().do_async(&client).await {
+        Err(Error::PinMismatch(host)) => assert_eq!(host, "localhost"),
+        r => panic!("unexpected result {r:?}"),
+    }
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn pinned_certificate_must_be_trusted() {
+    let cert = TestCertificate::new();
+    let server = cert.server();
+    let client = ClientBuilder::new()
+        .base_url(&server.url())
+        .spki_pins(SpkiPins::new().pin("localhost", &[&cert.pin]))
+        .build::<ClientSync>()
+        .unwrap();
+
+    // A matching pin does not replace the verification of the chain.
+    assert!(matches!(
+        request().do_sync(&client),
+        Err(e) if !matches!(e, Error::PinMismatch(_))
+    ));
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn unpinned_host_uses_root_verification() {
+    let cert = TestCertificate::new();
+    let server = cert.server();
+    let client = ClientBuilder::new()
+        .base_url(&server.url())
+        .spki_pins(SpkiPins::new().pin("example.com", &[&cert.pin]))
+        .build::<ClientSync>()
+        .unwrap();
+
+    // The self-signed certificate is not trusted by any root.
+    assert!(matches!(
+        request().do_sync(&client),
+        Err(e) if !matches!(e, Error::PinMismatch(_))
+    ));
+}
@@ -0,0 +1,154 @@
+use crate::utils::{ClientASync, ClientSync, StubRequest, StubResponse, StubServer};
+use proton_api_rs::domain::UserUid;
+use proton_api_rs::http::{
+    ClientBuilder, Method, OwnedRequest, RequestData, Sequence, StringResponse,
+};
+use proton_api_rs::Session;
+use serde_json::json;
+use std::time::Duration;
+
+fn ping() -> OwnedRequest<StringResponse> {
+    OwnedRequest::new(RequestData::new(Method::Get, "tests/ping"))
+}
+
+fn ping_server() -> StubServer {
+    StubServer::new(|_| StubResponse::new(200).body("pong"))
+}
+
+/// Server whose first access token has already expired, so that the session has to refresh
+/// it before the user request succeeds.
+fn expired_session_server() -> StubServer {
+    StubServer::new(|request: &StubRequest| match request.path.as_str() {
+        "/auth/v4/refresh" => {
+            let body = serde_json::from_slice::<serde_json::Value>(&request.body).unwrap();
+            let token = match body["RefreshToken"].as_str() {
+                Some("refresh-0") => "access-1",
+                _ => "access-2",
+            };
+            StubResponse::json(
+                200,
+                json!({
+                    "UID": "uid",
+                    "AccessToken": token,
+                    "RefreshToken": "refresh-1",
+                    "Scope": "full",
+                }),
+            )
+        }
+        "/core/v4/users" if request.header("Authorization") == Some("Bearer access-2") => {
+            StubResponse::json(
+                200,
+                json!({
+                    "User": {
+                        "ID": "user-id",
+                        "Name": "foo",
+                        "DisplayName": "Foo",
+                        "Email": "foo@bar.com",
+                        "UsedSpace": 0,
+                        "MaxSpace": 0,
+                        "MaxUpload": 0,
+                        "Credit": 0,
+                        "Currency": "EUR",
+                        "Keys": [],
+                    }
+                }),
+            )
+        }
+        _ => StubResponse::json(401, json!({"Code": 401, "Error": "Invalid access token"})),
+    })
+}
+
+#[test]
+fn connections_are_reused_sync() {
+    let server = ping_server();
+    let client = server.client_builder().build::<ClientSync>().unwrap();
+
+    for _ in 0..5 {
+        ping().do_sync(&client).unwrap();
+    }
+    assert_eq!(server.connection_count(), 1);
+}
+
+#[tokio::test]
+async fn connections_are_reused_async() {
+    let server = ping_server();
+    let client = server.client_builder().build::<ClientASync>().unwrap();
+
+    for _ in 0..5 {
+        ping().do_async(&client).await.unwrap();
+    }
+    assert_eq!(server.connection_count(), 1);
+}
+
+#[test]
+fn pooling_can_be_disabled() {
+    let server = ping_server();
+    let builder = server.client_builder().pool_max_idle_per_host(0);
+    let sync_client = builder.clone().build::<ClientSync>().unwrap();
+    let async_client = builder.build::<ClientASync>().unwrap();
+
+    ping().do_sync(&sync_client).unwrap();
+    ping().do_sync(&sync_client).unwrap();
+    assert_eq!(server.connection_count(), 2);
+
+    tokio::runtime::Runtime::new().unwrap().block_on(async {
+        ping().do_async(&async_client).await.unwrap();
+        ping().do_async(&async_client).await.unwrap();
+    });
+    assert_eq!(server.connection_count(), 4);
+}
+
+#[test]
+fn idle_connections_expire() {
+    let server = ping_server();
+    let builder: ClientBuilder = server
+        .client_builder()
+        .pool_idle_timeout(Some(Duration::from_millis(50)));
+    let sync_client = builder.clone().build::<ClientSync>().unwrap();
+    let async_client = builder.build::<ClientASync>().unwrap();
+
+    ping().do_sync(&sync_client).unwrap();
+    std::thread::sleep(Duration::from_millis(200));
+    ping().do_sync(&sync_client).unwrap();
+    assert_eq!(server.connection_count(), 2);
+
+    tokio::runtime::Runtime::new().unwrap().block_on(async {
+        ping().do_async(&async_client).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        ping().do_async(&async_client).await.unwrap();
+    });
+    assert_eq!(server.connection_count(), 4);
+}
+
+#[test]
+fn refresh_retry_reuses_connection_sync() {
+    let server = expired_session_server();
+    let client = server.client_builder().build::<ClientSync>().unwrap();
+
+    let uid = UserUid::from("uid");
+    let session = Session::refresh(&uid, "refresh-0")
+        .do_sync(&client)
+        .unwrap();
+    let user = session.get_user().do_sync(&client).unwrap();
+
+    assert_eq!(user.id.as_ref(), "user-id");
+    assert_eq!(server.requests().len(), 4);
+    assert_eq!(server.connection_count(), 1);
+}
+
+#[tokio::test]
+async fn refresh_retry_reuses_connection_async() {
+    let server = expired_session_server();
+    let client = server.client_builder().build::<ClientASync>().unwrap();
+
+    let uid = UserUid::from("uid");
+    let session = Session::refresh(&uid, "refresh-0")
+        .do_async(&client)
+        .await
+        .unwrap();
+    let user = session.get_user().do_async(&client).await.unwrap();
+
+    assert_eq!(user.id.as_ref(), "user-id");
+    assert_eq!(server.requests().len(), 4);
+    assert_eq!(server.connection_count(), 1);
+}
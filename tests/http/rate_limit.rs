@@ -0,0 +1,170 @@
+use crate::utils::{ClientASync, ClientSync, StubResponse, StubServer};
+use proton_api_rs::http::{
+    ClientBuilder, Error, Method, OwnedRequest, RateLimit, RateLimiter, RequestData, Sequence,
+    StringResponse,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Server which takes 50ms to answer and records the highest number of concurrent requests.
+fn slow_server() -> (StubServer, Arc<AtomicUsize>) {
+    let active = Arc::new(AtomicUsize::new(0));
+    let max_active = Arc::new(AtomicUsize::new(0));
+    let handler_max = max_active.clone();
+    let server = StubServer::new(move |_| {
+        let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+        handler_max.fetch_max(now, Ordering::SeqCst);
+        std::thread::sleep(Duration::from_millis(50));
+        active.fetch_sub(1, Ordering::SeqCst);
+        StubResponse::new(200).body("ok")
+    });
+    (server, max_active)
+}
+
+fn request(url: &str) -> OwnedRequest<StringResponse> {
+    OwnedRequest::new(RequestData::new(Method::Get, url))
+}
+
+fn limited(server: &StubServer, limiter: RateLimiter) -> ClientBuilder {
+    server.client_builder().rate_limiter(limiter)
+}
+
+#[tokio::test]
+async fn max_in_flight_async() {
+    let (server, max_active) = slow_server();
+    let client = limited(
+        &server,
+        RateLimiter::new().endpoint("mail/v4/messages", RateLimit::new().max_in_flight(2)),
+    )
+    .build::<ClientASync>()
+    .unwrap();
+
+    let requests = (0..8).map(|i| {
+        let client = client.clone();
+        async move {
+            request(&format!("mail/v4/messages/{i}"))
+                .do_async(&client)
+                .await
+                .unwrap()
+        }
+    });
+    futures_util::future::join_all(requests).await;
+
+    assert_eq!(max_active.load(Ordering::SeqCst), 2);
+    assert_eq!(server.requests().len(), 8);
+}
+
+#[test]
+fn max_in_flight_shared_across_clones() {
+    let (server, max_active) = slow_server();
+    let client = limited(
+        &server,
+        RateLimiter::new().global(RateLimit::new().max_in_flight(1)),
+    )
+    .build::<ClientSync>()
+    .unwrap();
+
+    let threads = (0..4)
+        .map(|_| {
+            let client = client.clone();
+            std::thread::spawn(move || request("core/v4/users").do_sync(&client).unwrap())
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    assert_eq!(max_active.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn rate_limit_sync() {
+    let server = StubServer::new(|_| StubResponse::new(200));
+    let client = limited(
+        &server,
+        RateLimiter::new().global(RateLimit::new().rate(20, Duration::from_secs(1)).burst(1)),
+    )
+    .build::<ClientSync>()
+    .unwrap();
+
+    let start = Instant::now();
+    for _ in 0..5 {
+        request("core/v4/users").do_sync(&client).unwrap();
+    }
+    assert!(start.elapsed() >= Duration::from_millis(190));
+}
+
+#[tokio::test]
+async fn rate_limit_only_applies_to_endpoint() {
+    let server = StubServer::new(|_| StubResponse::new(200));
+    let client = limited(
+        &server,
+        RateLimiter::new().endpoint(
+            "mail/v4/messages",
+            RateLimit::new().rate(1, Duration::from_secs(60)),
+        ),
+    )
+    .build::<ClientASync>()
+    .unwrap();
+
+    let start = Instant::now();
+    request("mail/v4/messages").do_async(&client).await.unwrap();
+    for _ in 0..5 {
+        request("core/v4/users").do_async(&client).await.unwrap();
+    }
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn rate_limit_wait_respects_deadline_sync() {
+    let server = StubServer::new(|_| StubResponse::new(200));
+    let client = limited(
+        &server,
+        RateLimiter::new().global(RateLimit::new().rate(1, Duration::from_secs(60))),
+    )
+    .build::<ClientSync>()
+    .unwrap();
+
+    request("core/v4/users").do_sync(&client).unwrap();
+    let start = Instant::now();
+    let data = RequestData::new(Method::Get, "core/v4/users").timeout(Duration::from_millis(200));
+    let result = OwnedRequest::<StringResponse>::new(data).do_sync(&client);
+    assert!(matches!(result, Err(Error::Timeout(_))), "{result:?}");
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn rate_limit_wait_does_not_hold_in_flight_slot() {
+    let server = StubServer::new(|_| StubResponse::new(200));
+    let client = limited(
+        &server,
+        RateLimiter::new()
+            .global(RateLimit::new().max_in_flight(1))
+            .endpoint(
+                "mail/v4/messages",
+                RateLimit::new().rate(1, Duration::from_secs(60)),
+            ),
+    )
+    .build::<ClientASync>()
+    .unwrap();
+
+    request("mail/v4/messages").do_async(&client).await.unwrap();
+    let waiting = {
+        let client = client.clone();
+        tokio::spawn(async move {
+            let data =
+                RequestData::new(Method::Get, "mail/v4/messages").timeout(Duration::from_secs(2));
+            OwnedRequest::<StringResponse>::new(data)
+                .do_async(&client)
+                .await
+        })
+    };
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let start = Instant::now();
+    request("core/v4/users").do_async(&client).await.unwrap();
+    assert!(start.elapsed() < Duration::from_millis(500));
+    assert!(waiting.await.unwrap().is_err());
+}
@@ -0,0 +1,58 @@
+use crate::utils::{ClientASync, ClientSync, StubResponse, StubServer};
+use proton_api_rs::http::{
+    JsonResponse, Method, OwnedRequest, RequestData, ResponseWithMeta, Sequence, StreamingResponse,
+    WithMeta,
+};
+use serde_json::{json, Value};
+
+fn server() -> StubServer {
+    StubServer::new(|_| {
+        StubResponse::json(201, json!({"Code": 1000}))
+            .header("ETag", "\"abc\"")
+            .header("x-pm-code", "1000")
+    })
+}
+
+fn request() -> OwnedRequest<WithMeta<JsonResponse<Value>>> {
+    OwnedRequest::new(RequestData::new(Method::Get, "core/v4/users"))
+}
+
+fn check(response: ResponseWithMeta<Value>) {
+    assert_eq!(response.value, json!({"Code": 1000}));
+    assert_eq!(response.meta.status(), 201);
+    assert_eq!(response.meta.header("etag"), Some("\"abc\""));
+    assert_eq!(response.meta.header("X-Pm-Code"), Some("1000"));
+}
+
+#[test]
+fn response_meta_sync() {
+    let server = server();
+    let client = server.client_builder().build::<ClientSync>().unwrap();
+
+    check(request().do_sync(&client).unwrap());
+}
+
+#[tokio::test]
+async fn response_meta_async() {
+    let server = server();
+    let client = server.client_builder().build::<ClientASync>().unwrap();
+
+    check(request().do_async(&client).await.unwrap());
+}
+
+#[test]
+fn response_meta_streaming() {
+    let server = server();
+    let client = server.client_builder().build::<ClientSync>().unwrap();
+
+    let response = OwnedRequest::<WithMeta<StreamingResponse>>::new(RequestData::new(
+        Method::Get,
+        "core/v4/users",
+    ))
+    .do_sync(&client)
+    .unwrap();
+
+    assert_eq!(response.meta.header("ETag"), Some("\"abc\""));
+    let mut body = vec![];
+    assert_eq!(response.value.copy_to(&mut body, |_| {}).unwrap(), 13);
+}
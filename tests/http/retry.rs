@@ -0,0 +1,281 @@
+use crate::utils::{ClientASync, ClientSync, StubResponse, StubServer};
+use proton_api_rs::http::{
+    retry_with, CancellationToken, Error, Method, OwnedRequest, RateLimitPolicy, RequestData,
+    RetryPolicy, Sequence, StringResponse, TransientErrorPolicy,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// Server which fails the first `failures` requests with a 429.
+fn throttling_server(failures: usize) -> StubServer {
+    let count = AtomicUsize::new(0);
+    StubServer::new(move |_| {
+        if count.fetch_add(1, Ordering::SeqCst) < failures {
+            StubResponse::json(429, serde_json::json!({"Code": 2028, "Error": "Slow down"}))
+                .header("Retry-After", "0")
+        } else {
+            StubResponse::new(200).body("ok")
+        }
+    })
+}
+
+fn policy() -> RetryPolicy {
+    RetryPolicy::new()
+        .max_attempts(3)
+        .base_delay(Duration::from_millis(1))
+}
+
+fn request(method: Method) -> OwnedRequest<StringResponse> {
+    OwnedRequest::new(RequestData::new(method, "tests/ping"))
+}
+
+#[test]
+fn retry_sync_on_too_many_requests() {
+    let server = throttling_server(2);
+    let client = server
+        .client_builder()
+        .retry_policy(policy())
+        .build::<ClientSync>()
+        .unwrap();
+
+    let body = request(Method::Get).do_sync(&client).unwrap();
+    assert_eq!(body, "ok");
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 3);
+    assert!(requests
+        .iter()
+        .all(|r| r.method == "GET" && r.path == "/tests/ping"));
+}
+
+#[tokio::test]
+async fn retry_async_on_too_many_requests() {
+    let server = throttling_server(2);
+    let client = server
+        .client_builder()
+        .retry_policy(policy())
+        .build::<ClientASync>()
+        .unwrap();
+
+    let body = request(Method::Get).do_async(&client).await.unwrap();
+    assert_eq!(body, "ok");
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[test]
+fn retry_gives_up_after_max_attempts() {
+    let server = throttling_server(5);
+    let client = server
+        .client_builder()
+        .retry_policy(policy())
+        .build::<ClientSync>()
+        .unwrap();
+
+    let result = request(Method::Get).do_sync(&client);
+    assert!(matches!(result, Err(Error::API(e)) if e.http_code == 429 && e.api_code == 2028));
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn retry_skips_non_idempotent_requests() {
+    let server = throttling_server(1);
+    let client = server
+        .client_builder()
+        .retry_policy(policy())
+        .build::<ClientASync>()
+        .unwrap();
+
+    let result = request(Method::Post).do_async(&client).await;
+    assert!(matches!(result, Err(Error::API(e)) if e.http_code == 429));
+    assert_eq!(server.requests().len(), 1);
+
+    let server = throttling_server(1);
+    let client = server
+        .client_builder()
+        .retry_policy(policy())
+        .build::<ClientASync>()
+        .unwrap();
+
+    let opt_in = OwnedRequest::<StringResponse>::new(
+        RequestData::new(Method::Post, "tests/ping").allow_retry(),
+    );
+    let body = opt_in.do_async(&client).await.unwrap();
+    assert_eq!(body, "ok");
+    assert_eq!(server.requests().len(), 2);
+}
+
+/// Server which fails the first `failures` requests to `tests/send` with the API code 2500.
+fn flaky_send_server(failures: usize) -> StubServer {
+    let count = AtomicUsize::new(0);
+    StubServer::new(move |req| {
+        if req.path.ends_with("/send") && count.fetch_add(1, Ordering::SeqCst) < failures {
+            StubResponse::json(
+                422,
+                serde_json::json!({"Code": 2500, "Error": "Draft changed"}),
+            )
+        } else {
+            StubResponse::new(200).body("ok")
+        }
+    })
+}
+
+fn retry_on_draft_changed(attempt: u32, error: &Error) -> Option<Duration> {
+    match error {
+        Error::API(e) if e.api_code == 2500 && attempt < 3 => Some(Duration::from_millis(1)),
+        _ => None,
+    }
+}
+
+fn paths(server: &StubServer) -> Vec<String> {
+    server.requests().into_iter().map(|r| r.path).collect()
+}
+
+#[test]
+fn sequence_retry_sync_reruns_all_steps() {
+    let server = flaky_send_server(1);
+    let client = server.client_builder().build::<ClientSync>().unwrap();
+
+    let body = retry_with(
+        || {
+            OwnedRequest::<StringResponse>::new(RequestData::new(Method::Put, "tests/draft")).chain(
+                |_| {
+                    Ok(OwnedRequest::<StringResponse>::new(RequestData::new(
+                        Method::Post,
+                        "tests/send",
+                    )))
+                },
+            )
+        },
+        retry_on_draft_changed,
+    )
+    .do_sync(&client)
+    .unwrap();
+    assert_eq!(body, "ok");
+    assert_eq!(
+        paths(&server),
+        ["/tests/draft", "/tests/send", "/tests/draft", "/tests/send"]
+    );
+}
+
+#[tokio::test]
+async fn sequence_retry_async_clones_sequence() {
+    let server = flaky_send_server(2);
+    let client = server.client_builder().build::<ClientASync>().unwrap();
+
+    let body = OwnedRequest::<StringResponse>::new(RequestData::new(Method::Post, "tests/send"))
+        .retry(retry_on_draft_changed)
+        .do_async(&client)
+        .await
+        .unwrap();
+    assert_eq!(body, "ok");
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[test]
+fn sequence_retry_gives_up() {
+    let server = flaky_send_server(5);
+    let client = server.client_builder().build::<ClientSync>().unwrap();
+
+    let result = request(Method::Post)
+        .map(|_| Ok::<_, Error>(()))
+        .chain(|_| {
+            Ok(OwnedRequest::<StringResponse>::new(RequestData::new(
+                Method::Post,
+                "tests/send",
+            )))
+        })
+        .retry(retry_on_draft_changed)
+        .do_sync(&client);
+    assert!(
+        matches!(result, Err(Error::API(ref e)) if e.api_code == 2500),
+        "{result:?}"
+    );
+    assert_eq!(server.requests().len(), 6);
+}
+
+#[tokio::test]
+async fn sequence_retry_rate_limit_policy() {
+    let server = throttling_server(2);
+    let client = server.client_builder().build::<ClientASync>().unwrap();
+
+    let body = request(Method::Post)
+        .retry(RateLimitPolicy::new().max_attempts(3))
+        .do_async(&client)
+        .await
+        .unwrap();
+    assert_eq!(body, "ok");
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[test]
+fn sequence_retry_transient_policy_ignores_api_errors() {
+    let server = throttling_server(1);
+    let client = server.client_builder().build::<ClientSync>().unwrap();
+
+    let result = request(Method::Post)
+        .retry(TransientErrorPolicy::new().base_delay(Duration::from_millis(1)))
+        .do_sync(&client);
+    assert!(matches!(result, Err(Error::API(_))), "{result:?}");
+    assert_eq!(server.requests().len(), 1);
+}
+
+fn retry_after_ten_seconds(_: u32, _: &Error) -> Option<Duration> {
+    Some(Duration::from_secs(10))
+}
+
+#[test]
+fn sequence_retry_sync_respects_deadline() {
+    let server = flaky_send_server(5);
+    let client = server.client_builder().build::<ClientSync>().unwrap();
+
+    let start = Instant::now();
+    let result = OwnedRequest::<StringResponse>::new(RequestData::new(Method::Post, "tests/send"))
+        .retry(retry_after_ten_seconds)
+        .with_deadline(Duration::from_secs(2))
+        .do_sync(&client);
+    assert!(matches!(result, Err(Error::Timeout(_))), "{result:?}");
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[test]
+fn sequence_retry_sync_cancelled_while_waiting() {
+    let server = flaky_send_server(5);
+    let client = server.client_builder().build::<ClientSync>().unwrap();
+    let token = CancellationToken::new();
+    let canceller = token.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(100));
+        canceller.cancel();
+    });
+
+    let start = Instant::now();
+    let result = OwnedRequest::<StringResponse>::new(RequestData::new(Method::Post, "tests/send"))
+        .retry(retry_after_ten_seconds)
+        .with_cancellation(token)
+        .do_sync(&client);
+    assert!(matches!(result, Err(Error::Cancelled)), "{result:?}");
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn sequence_retry_async_cancelled_while_waiting() {
+    let server = flaky_send_server(5);
+    let client = server.client_builder().build::<ClientASync>().unwrap();
+    let token = CancellationToken::new();
+    let canceller = token.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        canceller.cancel();
+    });
+
+    let start = Instant::now();
+    let result = OwnedRequest::<StringResponse>::new(RequestData::new(Method::Post, "tests/send"))
+        .retry(retry_after_ten_seconds)
+        .with_cancellation(token)
+        .do_async(&client)
+        .await;
+    assert!(matches!(result, Err(Error::Cancelled)), "{result:?}");
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(server.requests().len(), 1);
+}
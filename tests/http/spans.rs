@@ -0,0 +1,128 @@
+use crate::utils::{ClientSync, StubResponse, StubServer};
+use proton_api_rs::domain::UserUid;
+use proton_api_rs::http::Sequence;
+use proton_api_rs::Session;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata};
+
+type Fields = BTreeMap<String, String>;
+
+/// Subscriber which records the fields of all the spans.
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<(&'static str, Fields)>>>);
+
+impl Recorder {
+    fn spans(&self, name: &str) -> Vec<Fields> {
+        let spans = self.0.lock().unwrap();
+        spans
+            .iter()
+            .filter(|(n, _)| *n == name)
+            .map(|(_, f)| f.clone())
+            .collect()
+    }
+}
+
+struct FieldVisitor<'a>(&'a mut Fields);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.insert(
+            field.name().to_string(),
+            format!("{value:?}").replace('"', ""),
+        );
+    }
+}
+
+impl tracing::Subscriber for Recorder {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut fields = Fields::new();
+        span.record(&mut FieldVisitor(&mut fields));
+        let mut spans = self.0.lock().unwrap();
+        spans.push((span.metadata().name(), fields));
+        Id::from_u64(spans.len() as u64)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut spans = self.0.lock().unwrap();
+        let (_, fields) = &mut spans[span.into_u64() as usize - 1];
+        values.record(&mut FieldVisitor(fields));
+    }
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, _: &Event<'_>) {}
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[test]
+fn spans_for_session_refresh() {
+    let server = StubServer::new(|request| match request.path.as_str() {
+        "/auth/v4/refresh" => StubResponse::json(
+            200,
+            json!({
+                "UID": "uid",
+                "AccessToken": "secret-access",
+                "RefreshToken": "secret-refresh",
+                "Scope": "full",
+            }),
+        ),
+        _ => StubResponse::json(401, json!({"Code": 401})),
+    });
+    let client = server.client_builder().build::<ClientSync>().unwrap();
+    let recorder = Recorder::default();
+
+    tracing::subscriber::with_default(recorder.clone(), || {
+        let uid = UserUid::from("uid");
+        let session = Session::refresh(&uid, "secret-refresh")
+            .do_sync(&client)
+            .unwrap();
+        assert!(session.get_user().do_sync(&client).is_err());
+    });
+
+    let requests = recorder.spans("http_request");
+    let summary = requests
+        .iter()
+        .map(|f| format!("{} {} {}", f["method"], f["path"], f["status"]))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![
+            "POST auth/v4/refresh 200",
+            "GET core/v4/users 401",
+            "POST auth/v4/refresh 200",
+            "GET core/v4/users 401",
+        ]
+    );
+    assert!(requests.iter().all(|f| f["attempt"] == "1"));
+    assert!(requests.iter().all(|f| f.contains_key("latency_ms")));
+    assert_eq!(requests[1]["error"], "api");
+
+    let steps = recorder
+        .spans("sequence_step")
+        .into_iter()
+        .map(|f| format!("{} {}", f["step"], f["failed"]))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        steps,
+        vec![
+            "session_refresh false",
+            "session_refresh false",
+            "retry_after_refresh true"
+        ]
+    );
+
+    // Secrets are never recorded.
+    let all = format!("{:?}", recorder.0.lock().unwrap());
+    assert!(!all.contains("secret"));
+}
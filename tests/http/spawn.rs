@@ -0,0 +1,74 @@
+use crate::utils::{ClientASync, StubRequest, StubResponse, StubServer};
+use proton_api_rs::domain::{MessageFilter, UserUid};
+use proton_api_rs::http::{
+    join_all, Method, OwnedRequest, RequestData, Sequence, StringResponse, TransientErrorPolicy,
+};
+use proton_api_rs::Session;
+use serde_json::json;
+use std::sync::Arc;
+
+fn mail_server() -> StubServer {
+    StubServer::new(|request: &StubRequest| match request.path.as_str() {
+        "/auth/v4/refresh" => StubResponse::json(
+            200,
+            json!({
+                "UID": "uid",
+                "AccessToken": "access",
+                "RefreshToken": "refresh",
+                "Scope": "full",
+            }),
+        ),
+        "/mail/v4/messages" => StubResponse::json(200, json!({"Messages": [], "Total": 0})),
+        path => StubResponse::new(200).body(path.to_string()),
+    })
+}
+
+fn get(path: &str) -> OwnedRequest<StringResponse> {
+    OwnedRequest::new(RequestData::new(Method::Get, path))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn spawn_session_sequence() {
+    let server = mail_server();
+    let client = Arc::new(server.client_builder().build::<ClientASync>().unwrap());
+
+    let uid = UserUid::from("uid");
+    let session = Session::refresh(&uid, "refresh")
+        .do_async(&*client)
+        .await
+        .unwrap();
+    let session = Arc::new(session);
+
+    let (messages, total) = tokio::spawn(async move {
+        session
+            .get_messages(MessageFilter::new())
+            .do_async(&*client)
+            .await
+    })
+    .await
+    .unwrap()
+    .unwrap();
+    assert!(messages.is_empty());
+    assert_eq!(total, 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn spawn_combined_sequence() {
+    let server = mail_server();
+    let client = Arc::new(server.client_builder().build::<ClientASync>().unwrap());
+
+    let sequence = get("tests/a")
+        .join(join_all(["tests/b", "tests/c"].map(get)).collect_errors())
+        .map(|(a, rest)| {
+            let rest = rest.into_iter().collect::<Result<Vec<_>, _>>()?;
+            Ok::<_, proton_api_rs::http::Error>(format!("{a} {}", rest.join(" ")))
+        })
+        .retry(TransientErrorPolicy::new())
+        .boxed();
+
+    let output = tokio::spawn(async move { sequence.do_async(&*client).await })
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(output, "/tests/a /tests/b /tests/c");
+}
@@ -0,0 +1,112 @@
+use crate::utils::{ClientASync, ClientSync, StubResponse, StubServer};
+use proton_api_rs::http::{
+    BinaryResponse, Error, Method, OwnedRequest, RequestData, Sequence, StreamingResponse,
+};
+
+const BODY_SIZE: usize = 3 * 1024 * 1024;
+
+fn body() -> Vec<u8> {
+    (0..BODY_SIZE).map(|i| (i % 251) as u8).collect()
+}
+
+fn attachment_server() -> StubServer {
+    StubServer::new(|r| {
+        if r.path == "/missing" {
+            return StubResponse::json(404, serde_json::json!({"Code": 2501}));
+        }
+        StubResponse::new(200).body(body())
+    })
+}
+
+fn stream_request(url: &str) -> OwnedRequest<StreamingResponse> {
+    OwnedRequest::new(RequestData::new(Method::Get, url))
+}
+
+fn binary_request() -> OwnedRequest<BinaryResponse> {
+    OwnedRequest::new(RequestData::new(Method::Get, "attachment"))
+}
+
+#[test]
+fn stream_sync() {
+    let server = attachment_server();
+    let client = server
+        .client_builder()
+        .max_body_size(1024)
+        .build::<ClientSync>()
+        .unwrap();
+
+    let mut output = vec![];
+    let mut progress = vec![];
+    let written = stream_request("attachment")
+        .write_to(&mut output, |n| progress.push(n))
+        .do_sync(&client)
+        .unwrap();
+
+    assert_eq!(written, BODY_SIZE as u64);
+    assert_eq!(output, body());
+    assert!(progress.len() > 1);
+    assert_eq!(progress.last(), Some(&(BODY_SIZE as u64)));
+
+    assert!(matches!(
+        stream_request("missing").do_sync(&client),
+        Err(Error::API(e)) if e.http_code == 404
+    ));
+}
+
+#[tokio::test]
+async fn stream_async() {
+    let server = attachment_server();
+    let client = server
+        .client_builder()
+        .max_body_size(1024)
+        .build::<ClientASync>()
+        .unwrap();
+
+    let mut output = vec![];
+    let mut progress = vec![];
+    let written = stream_request("attachment")
+        .write_to(&mut output, |n| progress.push(n))
+        .do_async(&client)
+        .await
+        .unwrap();
+
+    assert_eq!(written, BODY_SIZE as u64);
+    assert_eq!(output, body());
+    assert_eq!(progress.last(), Some(&(BODY_SIZE as u64)));
+
+    assert!(matches!(
+        stream_request("missing").do_async(&client).await,
+        Err(Error::API(e)) if e.http_code == 404
+    ));
+}
+
+#[tokio::test]
+async fn body_size_limit() {
+    let server = attachment_server();
+    let sync_client = server
+        .client_builder()
+        .max_body_size(BODY_SIZE - 1)
+        .build::<ClientSync>()
+        .unwrap();
+    let async_client = server
+        .client_builder()
+        .max_body_size(BODY_SIZE - 1)
+        .build::<ClientASync>()
+        .unwrap();
+
+    assert!(matches!(
+        binary_request().do_sync(&sync_client),
+        Err(Error::BodyTooLarge(limit)) if limit == BODY_SIZE - 1
+    ));
+    assert!(matches!(
+        binary_request().do_async(&async_client).await,
+        Err(Error::BodyTooLarge(limit)) if limit == BODY_SIZE - 1
+    ));
+
+    let client = server
+        .client_builder()
+        .max_body_size(BODY_SIZE)
+        .build::<ClientSync>()
+        .unwrap();
+    assert_eq!(binary_request().do_sync(&client).unwrap(), body());
+}
@@ -0,0 +1,113 @@
+use crate::utils::{ClientASync, ClientSync, StubResponse, StubServer};
+use proton_api_rs::http::{
+    ClientBuilder, Error, Method, OwnedRequest, RequestData, Sequence, StringResponse,
+};
+use std::net::SocketAddr;
+
+const HOST: &str = "api.proton.test";
+
+/// Local certificate authority and a server certificate for [`HOST`] signed by it.
+struct TestAuthority {
+    ca_pem: String,
+    server_der: Vec<u8>,
+    server_key: Vec<u8>,
+}
+
+impl TestAuthority {
+    fn new() -> Self {
+        let mut ca_params = rcgen::CertificateParams::new(vec![]);
+        ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        let ca = rcgen::Certificate::from_params(ca_params).unwrap();
+
+        let server =
+            rcgen::Certificate::from_params(rcgen::CertificateParams::new(vec![HOST.to_string()]))
+                .unwrap();
+
+        Self {
+            ca_pem: ca.serialize_pem().unwrap(),
+            server_der: server.serialize_der_with_signer(&ca).unwrap(),
+            server_key: server.serialize_private_key_der(),
+        }
+    }
+
+    fn server(&self) -> StubServer {
+        let config = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![rustls::Certificate(self.server_der.clone())],
+                rustls::PrivateKey(self.server_key.clone()),
+            )
+            .unwrap();
+        StubServer::with_tls(config, |_| StubResponse::new(200).body("local"))
+    }
+}
+
+/// Builder for a client reaching `server` through [`HOST`], without allowing plain http.
+fn builder(server: &StubServer) -> ClientBuilder {
+    ClientBuilder::new()
+        .base_url(&format!("https://{HOST}:{}", server.port()))
+        .resolve(HOST, SocketAddr::from(([127, 0, 0, 1], 0)))
+}
+
+fn request() -> OwnedRequest<StringResponse> {
+    OwnedRequest::new(RequestData::new(Method::Get, "tests/ping"))
+}
+
+#[test]
+fn custom_root_with_dns_override() {
+    let authority = TestAuthority::new();
+    let server = authority.server();
+    let builder = builder(&server).root_certificate_pem(&authority.ca_pem);
+
+    let client = builder.clone().build::<ClientSync>().unwrap();
+    assert_eq!(request().do_sync(&client).unwrap(), "local");
+
+    let client = builder.build::<ClientASync>().unwrap();
+    let response = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(request().do_async(&client));
+    assert_eq!(response.unwrap(), "local");
+}
+
+#[tokio::test]
+async fn custom_root_without_system_roots() {
+    let authority = TestAuthority::new();
+    let server = authority.server();
+    let client = builder(&server)
+        .root_certificate_pem(&authority.ca_pem)
+        .disable_system_roots()
+        .build::<ClientASync>()
+        .unwrap();
+
+    assert_eq!(request().do_async(&client).await.unwrap(), "local");
+}
+
+#[test]
+fn unknown_root_is_rejected() {
+    let server = TestAuthority::new().server();
+    let other = TestAuthority::new();
+    let builder = builder(&server).root_certificate_pem(&other.ca_pem);
+
+    let client = builder.clone().build::<ClientSync>().unwrap();
+    assert!(matches!(
+        request().do_sync(&client),
+        Err(Error::Connection(_))
+    ));
+
+    let client = builder
+        .disable_system_roots()
+        .build::<ClientASync>()
+        .unwrap();
+    let response = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(request().do_async(&client));
+    assert!(matches!(response, Err(Error::Connection(_))));
+}
+
+#[test]
+fn invalid_root_fails_build() {
+    let builder = ClientBuilder::new().root_certificate_pem("not a certificate");
+    assert!(builder.clone().build::<ClientSync>().is_err());
+    assert!(builder.build::<ClientASync>().is_err());
+}
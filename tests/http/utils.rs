@@ -0,0 +1,256 @@
+use proton_api_rs::http;
+use proton_api_rs::http::ClientBuilder;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+pub type ClientSync = http::ureq_client::UReqClient;
+pub type ClientASync = http::reqwest_client::ReqwestClient;
+
+/// Request as received by the [`StubServer`].
+#[derive(Debug, Clone)]
+pub struct StubRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl StubRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Scripted response returned by the [`StubServer`].
+#[derive(Debug, Clone)]
+pub struct StubResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl StubResponse {
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body: vec![],
+        }
+    }
+
+    pub fn json(status: u16, value: serde_json::Value) -> Self {
+        Self::new(status)
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_vec(&value).unwrap())
+    }
+
+    pub fn header(mut self, key: &str, value: &str) -> Self {
+        self.headers.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+}
+
+type Handler = dyn Fn(&StubRequest) -> StubResponse + Send + Sync;
+
+struct State {
+    handler: Box<Handler>,
+    requests: Mutex<Vec<StubRequest>>,
+    connections: AtomicUsize,
+    stop: AtomicBool,
+}
+
+/// Minimal local HTTP/1.1 server which answers every request with the response produced by
+/// its handler and records all the requests it received.
+pub struct StubServer {
+    state: Arc<State>,
+    port: u16,
+    tls: bool,
+}
+
+impl StubServer {
+    pub fn new(handler: impl Fn(&StubRequest) -> StubResponse + Send + Sync + 'static) -> Self {
+        Self::start(None, handler)
+    }
+
+    /// Start a server which only accepts TLS connections using `config`.
+    pub fn with_tls(
+        config: rustls::ServerConfig,
+        handler: impl Fn(&StubRequest) -> StubResponse + Send + Sync + 'static,
+    ) -> Self {
+        Self::start(Some(Arc::new(config)), handler)
+    }
+
+    fn start(
+        tls: Option<Arc<rustls::ServerConfig>>,
+        handler: impl Fn(&StubRequest) -> StubResponse + Send + Sync + 'static,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind stub server");
+        let port = listener.local_addr().unwrap().port();
+        let state = Arc::new(State {
+            handler: Box::new(handler),
+            requests: Mutex::new(vec![]),
+            connections: AtomicUsize::new(0),
+            stop: AtomicBool::new(false),
+        });
+
+        let thread_state = state.clone();
+        let thread_tls = tls.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                if thread_state.stop.load(Ordering::SeqCst) {
+                    break;
+                }
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                thread_state.connections.fetch_add(1, Ordering::SeqCst);
+                let state = thread_state.clone();
+                let tls = thread_tls.clone();
+                std::thread::spawn(move || match tls {
+                    Some(config) => {
+                        let Ok(connection) = rustls::ServerConnection::new(config) else {
+                            return;
+                        };
+                        serve_connection(rustls::StreamOwned::new(connection, stream), &state)
+                    }
+                    None => serve_connection(stream, &state),
+                });
+            }
+        });
+
+        Self {
+            state,
+            port,
+            tls: tls.is_some(),
+        }
+    }
+
+    pub fn url(&self) -> String {
+        if self.tls {
+            format!("https://localhost:{}", self.port)
+        } else {
+            format!("http://127.0.0.1:{}", self.port)
+        }
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn requests(&self) -> Vec<StubRequest> {
+        self.state.requests.lock().unwrap().clone()
+    }
+
+    /// Number of connections accepted so far.
+    pub fn connection_count(&self) -> usize {
+        self.state.connections.load(Ordering::SeqCst)
+    }
+
+    pub fn client_builder(&self) -> ClientBuilder {
+        ClientBuilder::new().base_url(&self.url()).allow_http()
+    }
+}
+
+impl Drop for StubServer {
+    fn drop(&mut self) {
+        self.state.stop.store(true, Ordering::SeqCst);
+        // Wake up the accept loop.
+        let _ = TcpStream::connect(("127.0.0.1", self.port));
+    }
+}
+
+fn serve_connection(stream: impl Read + Write, state: &State) {
+    let mut reader = BufReader::new(stream);
+
+    while let Some(request) = read_request(&mut reader) {
+        let response = (state.handler)(&request);
+        state.requests.lock().unwrap().push(request);
+
+        if write_response(reader.get_mut(), &response).is_err() {
+            return;
+        }
+    }
+}
+
+fn read_request(reader: &mut impl BufRead) -> Option<StubRequest> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).ok()? == 0 {
+        return None;
+    }
+
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let path = parts.next()?.to_string();
+
+    let mut headers = vec![];
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let (k, v) = line.split_once(':')?;
+        headers.push((k.trim().to_string(), v.trim().to_string()));
+    }
+
+    let mut request = StubRequest {
+        method,
+        path,
+        headers,
+        body: vec![],
+    };
+
+    if let Some(len) = request.header("Content-Length") {
+        let mut body = vec![0u8; len.parse().ok()?];
+        reader.read_exact(&mut body).ok()?;
+        request.body = body;
+    } else if request
+        .header("Transfer-Encoding")
+        .map(|v| v.eq_ignore_ascii_case("chunked"))
+        .unwrap_or(false)
+    {
+        request.body = read_chunked(reader)?;
+    }
+
+    Some(request)
+}
+
+fn read_chunked(reader: &mut impl BufRead) -> Option<Vec<u8>> {
+    let mut body = vec![];
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        let size = usize::from_str_radix(line.trim().split(';').next()?, 16).ok()?;
+        let mut chunk = vec![0u8; size + 2];
+        reader.read_exact(&mut chunk).ok()?;
+        if size == 0 {
+            return Some(body);
+        }
+        body.extend_from_slice(&chunk[..size]);
+    }
+}
+
+/// Write the whole response at once, small writes on a kept alive connection would otherwise
+/// be delayed by Nagle's algorithm.
+fn write_response(writer: &mut impl Write, response: &StubResponse) -> std::io::Result<()> {
+    let mut buffer = vec![];
+    write!(buffer, "HTTP/1.1 {} Stub\r\n", response.status)?;
+    for (k, v) in &response.headers {
+        write!(buffer, "{k}: {v}\r\n")?;
+    }
+    write!(buffer, "Content-Length: {}\r\n\r\n", response.body.len())?;
+    buffer.extend_from_slice(&response.body);
+    writer.write_all(&buffer)?;
+    writer.flush()
+}